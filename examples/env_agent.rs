@@ -0,0 +1,78 @@
+//! Play a few episodes of the training environment with a hand-written
+//! agent that reads the ray observation: it heads for fruit it can see and
+//! otherwise goes straight until something is in the way.
+//!
+//! ```text
+//! cargo run --example env_agent --no-default-features
+//! ```
+
+use rsnake::board::{Board, Topology};
+use rsnake::env::{Encoding, Env, EnvConfig, Observation, ACTIONS};
+use rsnake::physics::Direction;
+use rsnake::sim::Rules;
+
+const EPISODES: u64 = 5;
+/// Obstacles closer than this, as `1 / distance`, are worth steering away from
+const NEAR: f32 = 0.5;
+/// Wall, body and fruit for every ray
+const RAY_FEATURES: usize = 3;
+/// The rays looking left, right and ahead, with the quarter turns clockwise
+/// from the heading they stand for. Of equally good ones the last is taken,
+/// so the snake rather goes straight.
+const LOOKS: [(usize, usize); 3] = [(6, 3), (2, 1), (0, 0)];
+
+fn act(observation: &Observation) -> Direction {
+    let data = &observation.data;
+    // The heading is one hot encoded after the rays
+    let heading = data[data.len() - ACTIONS.len()..]
+        .iter()
+        .position(|&hot| hot == 1.0)
+        .unwrap_or(0);
+
+    let value = |ray: usize| {
+        let features = &data[ray * RAY_FEATURES..][..RAY_FEATURES];
+        let (wall, body, fruit) = (features[0], features[1], features[2]);
+        let danger = wall.max(body);
+        if danger >= 1.0 {
+            // The next cell is deadly
+            f32::MIN
+        } else if fruit > danger {
+            1.0 + fruit
+        } else if danger >= NEAR {
+            -danger
+        } else {
+            0.0
+        }
+    };
+
+    let (_, turn) = LOOKS
+        .iter()
+        .copied()
+        .max_by(|a, b| value(a.0).total_cmp(&value(b.0)))
+        .unwrap_or(LOOKS[2]);
+    ACTIONS[(heading + turn) % ACTIONS.len()]
+}
+
+fn main() {
+    let mut config = EnvConfig::new(Rules::new(Board::new(12, 12, Topology::Walled)));
+    config.encoding = Encoding::Rays;
+    config.max_steps = Some(2000);
+    let mut env = Env::new(config);
+
+    for seed in 0..EPISODES {
+        let mut observation = env.reset(seed);
+        let mut total = 0.0;
+        loop {
+            let (next, reward, done, info) = env.step(act(&observation));
+            observation = next;
+            total += reward;
+            if done {
+                println!(
+                    "seed {}: {:?} after {} ticks, score {}, length {}, return {:.2}",
+                    seed, info.status, info.tick, info.score, info.length, total
+                );
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! The Battlesnake standard ruleset, as a game mode next to the classic
+//! rules in `sim`.
+//!
+//! Any number of snakes move at the same time. Every snake starts with 100
+//! health and a length of 3 stacked on one cell, loses one health per turn
+//! and gets it all back by eating food, which grows it by one. There is
+//! always some food on the board, with a chance of more every turn. A snake
+//! is eliminated when it runs out of health, leaves the board, runs into a
+//! body, or loses a head-on collision (the longer snake survives, equal
+//! lengths both lose). The game ends when at most one snake is left, or in
+//! a solo game when there is none.
+//!
+//! With hazards on (the "royale" variant), every few turns a row or column
+//! at the edge of the safe area turns into a hazard, which costs extra
+//! health per turn for every snake with its head in it.
+//!
+//! Like `sim`, a game is fully determined by its seed and the moves, so it
+//! runs just as well without a window.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::colors::{Color, Palette};
+use crate::draw::{draw_block, draw_fruit, draw_snake_head};
+use crate::fruit::{Fruit, FruitKind};
+use crate::physics::{Direction, Position};
+use crate::render::Renderer;
+
+pub const MAX_HEALTH: u32 = 100;
+pub const START_LENGTH: usize = 3;
+pub const DEFAULT_SIZE: u32 = 11;
+/// Turns between two shrinks of the safe area in royale games
+pub const SHRINK_EVERY: u32 = 25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaRules {
+    pub width: u32,
+    pub height: u32,
+    /// Chance in percent of a new food appearing on a turn
+    pub food_spawn_chance: u32,
+    /// Food on the board is topped up to this much every turn
+    pub minimum_food: usize,
+    /// Health lost per turn in a hazard, on top of the usual 1
+    pub hazard_damage: u32,
+    /// Turns between two shrinks of the safe area, `None` for no hazards
+    pub shrink_every: Option<u32>,
+}
+
+impl Default for ArenaRules {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_SIZE,
+            height: DEFAULT_SIZE,
+            food_spawn_chance: 15,
+            minimum_food: 1,
+            hazard_damage: 14,
+            shrink_every: None,
+        }
+    }
+}
+
+/// Why a snake is out of the game.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Elimination {
+    OutOfHealth,
+    OutOfBounds,
+    SelfCollision,
+    /// Ran into the body of the snake with this index
+    Collision(usize),
+    /// Lost a head-on collision with the snake with this index
+    HeadToHead(usize),
+}
+
+impl fmt::Display for Elimination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Elimination::OutOfHealth => write!(f, "starved"),
+            Elimination::OutOfBounds => write!(f, "left the board"),
+            Elimination::SelfCollision => write!(f, "ran into itself"),
+            Elimination::Collision(other) => write!(f, "ran into snake {}", other + 1),
+            Elimination::HeadToHead(other) => write!(f, "lost head-on to snake {}", other + 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaSnake {
+    /// Head first, segments may be stacked on one cell after eating and at
+    /// the start
+    pub body: VecDeque<Position>,
+    pub health: u32,
+    /// Direction of the last move, the default when there is no new one
+    pub direction: Direction,
+    /// `None` while still in the game
+    pub eliminated: Option<(Elimination, u32)>,
+}
+
+impl ArenaSnake {
+    fn new(start: Position) -> Self {
+        Self {
+            body: std::iter::repeat_n(start, START_LENGTH).collect(),
+            health: MAX_HEALTH,
+            direction: Direction::Up,
+            eliminated: None,
+        }
+    }
+
+    pub fn get_head(&self) -> &Position {
+        &self.body[0]
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.eliminated.is_none()
+    }
+}
+
+#[derive(Clone)]
+pub struct ArenaState<R = ChaCha8Rng> {
+    rules: ArenaRules,
+    snakes: Vec<ArenaSnake>,
+    food: Vec<Position>,
+    hazards: HashSet<Position>,
+    // The part of the board without hazards, as x, y, width, height
+    safe_area: (i32, i32, i32, i32),
+    turn: u32,
+    rng: R,
+}
+
+fn step(pos: &Position, dir: Direction) -> Position {
+    let mut next = pos.clone();
+    next.move_to_dir(dir);
+    next
+}
+
+impl ArenaState {
+    pub fn new(rules: ArenaRules, snakes: usize, seed: u64) -> Self {
+        Self::with_rng(rules, snakes, ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> ArenaState<R> {
+    pub fn with_rng(rules: ArenaRules, snakes: usize, mut rng: R) -> Self {
+        let (w, h) = (rules.width as i32, rules.height as i32);
+        // Corners first, then the middle of the edges, one cell in
+        let (left, right, top, bottom) = (1, w - 2, 1, h - 2);
+        let mut corners = vec![
+            Position { x: left, y: top },
+            Position {
+                x: right,
+                y: bottom,
+            },
+            Position { x: left, y: bottom },
+            Position { x: right, y: top },
+        ];
+        let mut edges = vec![
+            Position { x: w / 2, y: top },
+            Position {
+                x: w / 2,
+                y: bottom,
+            },
+            Position { x: left, y: h / 2 },
+            Position { x: right, y: h / 2 },
+        ];
+        corners.shuffle(&mut rng);
+        edges.shuffle(&mut rng);
+        let mut starts: Vec<Position> = corners.into_iter().chain(edges).collect();
+        // More snakes than start positions get random cells
+        while starts.len() < snakes {
+            let pos = Position {
+                x: rng.gen_range(0..w),
+                y: rng.gen_range(0..h),
+            };
+            if !starts.contains(&pos) {
+                starts.push(pos);
+            }
+        }
+
+        let snakes: Vec<ArenaSnake> = starts
+            .into_iter()
+            .take(snakes)
+            .map(ArenaSnake::new)
+            .collect();
+
+        let mut state = Self {
+            rules,
+            snakes,
+            food: Vec::new(),
+            hazards: HashSet::new(),
+            safe_area: (0, 0, w, h),
+            turn: 0,
+            rng,
+        };
+
+        // A food diagonally next to every snake and one in the center
+        for i in 0..state.snakes.len() {
+            let head = state.snakes[i].get_head().clone();
+            let mut options: Vec<Position> = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+                .iter()
+                .map(|(dx, dy)| Position {
+                    x: head.x + dx,
+                    y: head.y + dy,
+                })
+                .filter(|pos| state.is_free(pos))
+                .collect();
+            options.shuffle(&mut state.rng);
+            if let Some(pos) = options.pop() {
+                state.food.push(pos);
+            }
+        }
+        let center = Position { x: w / 2, y: h / 2 };
+        if state.is_free(&center) {
+            state.food.push(center);
+        }
+
+        state
+    }
+
+    pub fn get_rules(&self) -> &ArenaRules {
+        &self.rules
+    }
+
+    pub fn get_snakes(&self) -> &[ArenaSnake] {
+        &self.snakes
+    }
+
+    pub fn get_food(&self) -> &[Position] {
+        &self.food
+    }
+
+    pub fn get_hazards(&self) -> &HashSet<Position> {
+        &self.hazards
+    }
+
+    /// Number of turns played.
+    pub fn get_turn(&self) -> u32 {
+        self.turn
+    }
+
+    pub fn contains(&self, pos: &Position) -> bool {
+        pos.x >= 0
+            && pos.y >= 0
+            && pos.x < self.rules.width as i32
+            && pos.y < self.rules.height as i32
+    }
+
+    fn is_free(&self, pos: &Position) -> bool {
+        self.contains(pos)
+            && !self.food.contains(pos)
+            && !self
+                .snakes
+                .iter()
+                .filter(|snake| snake.is_alive())
+                .any(|snake| snake.body.contains(pos))
+    }
+
+    pub fn is_over(&self) -> bool {
+        let alive = self.snakes.iter().filter(|snake| snake.is_alive()).count();
+        if self.snakes.len() > 1 {
+            alive <= 1
+        } else {
+            alive == 0
+        }
+    }
+
+    /// The last snake standing once the game is over, `None` for a draw or
+    /// a solo game.
+    pub fn get_winner(&self) -> Option<usize> {
+        if self.snakes.len() < 2 || !self.is_over() {
+            return None;
+        }
+        self.snakes.iter().position(|snake| snake.is_alive())
+    }
+
+    fn spawn_food(&mut self) {
+        let free: Vec<Position> = (0..self.rules.height as i32)
+            .flat_map(|y| (0..self.rules.width as i32).map(move |x| Position { x, y }))
+            .filter(|pos| self.is_free(pos) && !self.hazards.contains(pos))
+            .collect();
+        if let Some(pos) = free.choose(&mut self.rng) {
+            self.food.push(pos.clone());
+        }
+    }
+
+    /// Turn one more row or column at the edge of the safe area into a
+    /// hazard.
+    fn shrink(&mut self) {
+        let (x, y, w, h) = self.safe_area;
+        if w <= 0 || h <= 0 {
+            return;
+        }
+
+        let (x, y, w, h, cells): (i32, i32, i32, i32, Vec<Position>) =
+            match self.rng.gen_range(0..4) {
+                0 => (
+                    x + 1,
+                    y,
+                    w - 1,
+                    h,
+                    (y..y + h).map(|y| Position { x, y }).collect(),
+                ),
+                1 => (
+                    x,
+                    y,
+                    w - 1,
+                    h,
+                    (y..y + h).map(|y| Position { x: x + w - 1, y }).collect(),
+                ),
+                2 => (
+                    x,
+                    y + 1,
+                    w,
+                    h - 1,
+                    (x..x + w).map(|x| Position { x, y }).collect(),
+                ),
+                _ => (
+                    x,
+                    y,
+                    w,
+                    h - 1,
+                    (x..x + w).map(|x| Position { x, y: y + h - 1 }).collect(),
+                ),
+            };
+        self.safe_area = (x, y, w, h);
+        self.hazards.extend(cells);
+    }
+}
+
+impl<R> ArenaState<R> {
+    /// Draw the hazards, the food and the snakes still in the game, snake
+    /// `i` in `colors[i]` or the palette's snake color.
+    pub fn draw(&self, r: &mut dyn Renderer, palette: &Palette, colors: &[Color]) {
+        for pos in self.hazards.iter() {
+            draw_block(r, palette.hazard, pos);
+        }
+        for pos in self.food.iter() {
+            let fruit = Fruit::new(pos.clone(), FruitKind::Normal);
+            draw_fruit(r, palette.fruit, &fruit);
+        }
+        for (i, snake) in self.snakes.iter().enumerate() {
+            if !snake.is_alive() {
+                continue;
+            }
+            let color = colors.get(i).copied().unwrap_or(palette.snake);
+            for pos in snake.body.iter().skip(1) {
+                draw_block(r, color, pos);
+            }
+            draw_snake_head(
+                r,
+                color,
+                palette.background,
+                snake.get_head(),
+                &snake.direction,
+            );
+        }
+    }
+}
+
+/// Play one turn with a move for every snake, in order. Snakes without a
+/// move keep going in their last direction. Returns the snakes eliminated
+/// on this turn.
+pub fn step_arena<R: Rng>(
+    state: &mut ArenaState<R>,
+    moves: &[Option<Direction>],
+) -> Vec<(usize, Elimination)> {
+    if state.is_over() {
+        return Vec::new();
+    }
+    state.turn += 1;
+
+    // Move
+    for (i, snake) in state.snakes.iter_mut().enumerate() {
+        if !snake.is_alive() {
+            continue;
+        }
+        if let Some(Some(dir)) = moves.get(i) {
+            snake.direction = *dir;
+        }
+        let head = step(snake.get_head(), snake.direction);
+        snake.body.push_front(head);
+        snake.body.pop_back();
+    }
+
+    // Health, hazards and food
+    let mut eaten = HashSet::new();
+    for snake in state.snakes.iter_mut().filter(|snake| snake.is_alive()) {
+        let head = snake.get_head().clone();
+        snake.health = snake.health.saturating_sub(1);
+
+        if state.food.contains(&head) {
+            snake.health = MAX_HEALTH;
+            let tail = snake.body.back().cloned().unwrap_or_else(|| head.clone());
+            snake.body.push_back(tail);
+            eaten.insert(head);
+        } else if state.hazards.contains(&head) {
+            snake.health = snake.health.saturating_sub(state.rules.hazard_damage);
+        }
+    }
+    state.food.retain(|pos| !eaten.contains(pos));
+
+    // Eliminations are decided on the board after everyone moved
+    let mut eliminated = Vec::new();
+    for (i, snake) in state.snakes.iter().enumerate() {
+        if !snake.is_alive() {
+            continue;
+        }
+        let head = snake.get_head();
+        let cause = if snake.health == 0 {
+            Some(Elimination::OutOfHealth)
+        } else if !state.contains(head) {
+            Some(Elimination::OutOfBounds)
+        } else if snake.body.iter().skip(1).any(|pos| pos == head) {
+            Some(Elimination::SelfCollision)
+        } else {
+            state
+                .snakes
+                .iter()
+                .enumerate()
+                .filter(|(j, other)| *j != i && other.is_alive())
+                .find_map(|(j, other)| {
+                    if other.body.iter().skip(1).any(|pos| pos == head) {
+                        Some(Elimination::Collision(j))
+                    } else if other.get_head() == head && snake.body.len() <= other.body.len() {
+                        Some(Elimination::HeadToHead(j))
+                    } else {
+                        None
+                    }
+                })
+        };
+        if let Some(cause) = cause {
+            eliminated.push((i, cause));
+        }
+    }
+    let turn = state.turn;
+    for (i, cause) in eliminated.iter() {
+        state.snakes[*i].eliminated = Some((*cause, turn));
+    }
+
+    // Hazards and new food
+    if let Some(every) = state.rules.shrink_every {
+        if every > 0 && turn.is_multiple_of(every) {
+            state.shrink();
+        }
+    }
+    if state.food.len() < state.rules.minimum_food {
+        for _ in state.food.len()..state.rules.minimum_food {
+            state.spawn_food();
+        }
+    } else if state.rng.gen_range(0..100) < state.rules.food_spawn_chance {
+        state.spawn_food();
+    }
+
+    eliminated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game on an 11x11 board without food, with one snake for every
+    /// list of cells (head first) heading in the given direction.
+    fn arena(snakes: &[(&[(i32, i32)], Direction)]) -> ArenaState {
+        let rules = ArenaRules {
+            food_spawn_chance: 0,
+            minimum_food: 0,
+            ..ArenaRules::default()
+        };
+        let mut state = ArenaState::new(rules, snakes.len(), 1);
+        state.food.clear();
+        for (snake, (cells, direction)) in state.snakes.iter_mut().zip(snakes) {
+            snake.body = cells.iter().map(|&(x, y)| Position { x, y }).collect();
+            snake.direction = *direction;
+        }
+        state
+    }
+
+    const RIGHT: &[(i32, i32)] = &[(2, 5), (1, 5), (0, 5)];
+    const LEFT: &[(i32, i32)] = &[(6, 5), (7, 5), (8, 5)];
+
+    #[test]
+    fn head_on_with_the_same_length_eliminates_both() {
+        let mut state = arena(&[(RIGHT, Direction::Right), (LEFT, Direction::Left)]);
+
+        assert!(step_arena(&mut state, &[]).is_empty());
+        assert_eq!(
+            step_arena(&mut state, &[]),
+            vec![
+                (0, Elimination::HeadToHead(1)),
+                (1, Elimination::HeadToHead(0))
+            ]
+        );
+        assert!(state.is_over());
+        assert_eq!(state.get_winner(), None);
+    }
+
+    #[test]
+    fn head_on_is_won_by_the_longer_snake() {
+        let longer: &[(i32, i32)] = &[(6, 5), (7, 5), (8, 5), (9, 5)];
+        let mut state = arena(&[(RIGHT, Direction::Right), (longer, Direction::Left)]);
+
+        step_arena(&mut state, &[]);
+        assert_eq!(
+            step_arena(&mut state, &[]),
+            vec![(0, Elimination::HeadToHead(1))]
+        );
+        assert_eq!(state.get_winner(), Some(1));
+    }
+
+    #[test]
+    fn running_into_a_body() {
+        // Snake 1 goes up through the cell in front of snake 0
+        let up: &[(i32, i32)] = &[(3, 4), (3, 5), (3, 6)];
+        let mut state = arena(&[(RIGHT, Direction::Right), (up, Direction::Up)]);
+
+        assert_eq!(
+            step_arena(&mut state, &[]),
+            vec![(0, Elimination::Collision(1))]
+        );
+        assert!(state.get_snakes()[1].is_alive());
+
+        // Following a tail is fine, it moves out of the way
+        let mut state = arena(&[(RIGHT, Direction::Right)]);
+        step_arena(&mut state, &[Some(Direction::Down)]);
+        step_arena(&mut state, &[Some(Direction::Left)]);
+        assert!(step_arena(&mut state, &[Some(Direction::Up)]).is_empty());
+
+        // Turning into its own neck is not
+        assert_eq!(
+            step_arena(&mut state, &[Some(Direction::Down)]),
+            vec![(0, Elimination::SelfCollision)]
+        );
+    }
+
+    #[test]
+    fn eliminations_are_recorded_in_order() {
+        let edge: &[(i32, i32)] = &[(10, 0), (9, 0), (8, 0)];
+        let up: &[(i32, i32)] = &[(4, 6), (4, 7), (4, 8)];
+        let mut state = arena(&[
+            (RIGHT, Direction::Right),
+            (up, Direction::Up),
+            (edge, Direction::Right),
+        ]);
+        state.snakes[1].health = 2;
+
+        assert_eq!(
+            step_arena(&mut state, &[]),
+            vec![(2, Elimination::OutOfBounds)]
+        );
+        // Snake 0 runs into snake 1 on the turn it starves
+        assert_eq!(
+            step_arena(&mut state, &[]),
+            vec![
+                (0, Elimination::Collision(1)),
+                (1, Elimination::OutOfHealth)
+            ]
+        );
+
+        let eliminated: Vec<_> = state.get_snakes().iter().map(|s| s.eliminated).collect();
+        assert_eq!(
+            eliminated,
+            vec![
+                Some((Elimination::Collision(1), 2)),
+                Some((Elimination::OutOfHealth, 2)),
+                Some((Elimination::OutOfBounds, 1)),
+            ]
+        );
+        assert!(state.is_over());
+        assert!(step_arena(&mut state, &[]).is_empty());
+        assert_eq!(state.get_turn(), 2);
+    }
+}
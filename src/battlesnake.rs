@@ -0,0 +1,520 @@
+//! Hosting games between Battlesnake servers.
+//!
+//! rsnake plays the part of the Battlesnake game engine: every snake is an
+//! HTTP server implementing the Battlesnake API, usually on localhost. The
+//! engine asks `GET /` for the snake's looks, then calls `POST /start` once,
+//! `POST /move` every turn and `POST /end` when the game is over, each with
+//! the game as JSON in the Battlesnake format. The game itself follows the
+//! standard rules in `arena`.
+//!
+//! Every request has the same timeout, which is also sent to the servers as
+//! `game.timeout`. A snake that doesn't answer a move in time, or answers
+//! something that isn't a move, keeps going in the direction of its last
+//! move, like it would on the official engine. Battlesnake counts `y` up
+//! from the bottom of the board, rsnake down from the top, so positions are
+//! flipped on the way out.
+
+use serde::{Deserialize, Serialize};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::arena::{step_arena, ArenaRules, ArenaSnake, ArenaState, Elimination};
+use crate::colors::Color;
+use crate::physics::{Direction, Position};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+const API_VERSION: &str = "1";
+const RULESET_VERSION: &str = "v1.2.3";
+
+/// Parse a `#rrggbb` color as used by Battlesnake customizations.
+fn parse_color(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(hex.get(i..i + 2)?, 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+
+    Some([channel(0)?, channel(2)?, channel(4)?, 1.0])
+}
+
+#[derive(Serialize)]
+struct Coord {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Customizations<'a> {
+    color: &'a str,
+    head: &'a str,
+    tail: &'a str,
+}
+
+#[derive(Serialize)]
+struct SnakeMessage<'a> {
+    id: String,
+    name: &'a str,
+    health: u32,
+    body: Vec<Coord>,
+    latency: String,
+    head: Coord,
+    length: usize,
+    shout: &'a str,
+    customizations: Customizations<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RoyaleSettings {
+    shrink_every_n_turns: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Settings {
+    food_spawn_chance: u32,
+    minimum_food: usize,
+    hazard_damage_per_turn: u32,
+    royale: RoyaleSettings,
+}
+
+#[derive(Serialize)]
+struct Ruleset {
+    name: &'static str,
+    version: &'static str,
+    settings: Settings,
+}
+
+#[derive(Serialize)]
+struct GameMessage<'a> {
+    id: &'a str,
+    ruleset: Ruleset,
+    map: &'static str,
+    timeout: u128,
+    source: &'static str,
+}
+
+#[derive(Serialize)]
+struct BoardMessage<'a> {
+    height: u32,
+    width: u32,
+    food: Vec<Coord>,
+    hazards: Vec<Coord>,
+    snakes: Vec<SnakeMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct GameRequest<'a> {
+    game: GameMessage<'a>,
+    turn: u32,
+    board: BoardMessage<'a>,
+    you: SnakeMessage<'a>,
+}
+
+/// The answer to `GET /`, every field is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SnakeInfo {
+    pub apiversion: String,
+    pub author: String,
+    pub color: String,
+    pub head: String,
+    pub tail: String,
+    pub version: String,
+}
+
+#[derive(Deserialize)]
+struct MoveReply {
+    #[serde(rename = "move")]
+    direction: String,
+    #[serde(default)]
+    shout: String,
+}
+
+/// One Battlesnake server taking part in the games.
+pub struct Server {
+    url: String,
+    name: String,
+    info: SnakeInfo,
+    agent: ureq::Agent,
+    // Of the last move request
+    latency: Option<Duration>,
+    shout: String,
+}
+
+impl Server {
+    /// Connect to the server at `url` and ask for its looks. `name` is
+    /// shown in the game, the server's author if `None`.
+    pub fn connect(url: &str, name: Option<&str>, timeout: Duration) -> Result<Self, String> {
+        let url = url.trim_end_matches('/').to_string();
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+
+        let info: SnakeInfo = agent
+            .get(&url)
+            .call()
+            .map_err(|err| format!("{}: {}", url, err))?
+            .into_json()
+            .map_err(|err| format!("{}: invalid snake info: {}", url, err))?;
+        if !info.apiversion.is_empty() && info.apiversion != API_VERSION {
+            return Err(format!(
+                "{}: API version {} is not supported, expected {}",
+                url, info.apiversion, API_VERSION
+            ));
+        }
+
+        let name = match name {
+            Some(name) => name.to_string(),
+            None if !info.author.is_empty() => info.author.clone(),
+            None => url.clone(),
+        };
+
+        Ok(Self {
+            url,
+            name,
+            info,
+            agent,
+            latency: None,
+            shout: String::new(),
+        })
+    }
+
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_info(&self) -> &SnakeInfo {
+        &self.info
+    }
+
+    /// The snake's color, if it has one that parses.
+    pub fn get_color(&self) -> Option<Color> {
+        parse_color(&self.info.color)
+    }
+
+    /// Time the last move took, `None` if it timed out.
+    pub fn get_latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// What the snake said with its last move.
+    pub fn get_shout(&self) -> &str {
+        &self.shout
+    }
+
+    fn post(&self, path: &str, body: &str) -> Result<ureq::Response, String> {
+        self.agent
+            .post(&format!("{}/{}", self.url, path))
+            .set("Content-Type", "application/json")
+            .send_string(body)
+            .map_err(|err| format!("{} /{}: {}", self.name, path, err))
+    }
+
+    fn request_move(&self, body: &str) -> Result<(Direction, String), String> {
+        let reply: MoveReply = self
+            .post("move", body)?
+            .into_json()
+            .map_err(|err| format!("{} /move: invalid reply: {}", self.name, err))?;
+
+        let dir = match reply.direction.as_str() {
+            "up" => Direction::Up,
+            "down" => Direction::Down,
+            "left" => Direction::Left,
+            "right" => Direction::Right,
+            other => return Err(format!("{} /move: unknown move '{}'", self.name, other)),
+        };
+
+        Ok((dir, reply.shout))
+    }
+}
+
+/// A game between Battlesnake servers, one snake each in order.
+pub struct ArenaGame {
+    id: String,
+    seed: u64,
+    state: ArenaState,
+    servers: Vec<Server>,
+    timeout: Duration,
+    started: bool,
+    ended: bool,
+}
+
+impl ArenaGame {
+    pub fn new(servers: Vec<Server>, rules: ArenaRules, seed: u64, timeout: Duration) -> Self {
+        Self {
+            id: format!("rsnake-{}", seed),
+            seed,
+            state: ArenaState::new(rules, servers.len(), seed),
+            servers,
+            timeout,
+            started: false,
+            ended: false,
+        }
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_state(&self) -> &ArenaState {
+        &self.state
+    }
+
+    pub fn get_servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.ended
+    }
+
+    fn coord(&self, pos: &Position) -> Coord {
+        Coord {
+            x: pos.x,
+            y: self.state.get_rules().height as i32 - 1 - pos.y,
+        }
+    }
+
+    fn snake_message<'a>(&'a self, index: usize, snake: &ArenaSnake) -> SnakeMessage<'a> {
+        let server = &self.servers[index];
+        SnakeMessage {
+            id: format!("snake-{}", index + 1),
+            name: &server.name,
+            health: snake.health,
+            body: snake.body.iter().map(|pos| self.coord(pos)).collect(),
+            latency: server
+                .latency
+                .map_or(0, |latency| latency.as_millis())
+                .to_string(),
+            head: self.coord(snake.get_head()),
+            length: snake.body.len(),
+            shout: &server.shout,
+            customizations: Customizations {
+                color: &server.info.color,
+                head: &server.info.head,
+                tail: &server.info.tail,
+            },
+        }
+    }
+
+    /// The game as seen by snake `index`, as JSON.
+    fn request(&self, index: usize) -> String {
+        let rules = self.state.get_rules();
+        let snakes = self.state.get_snakes();
+        let mut hazards: Vec<&Position> = self.state.get_hazards().iter().collect();
+        hazards.sort_by_key(|pos| (pos.y, pos.x));
+
+        let request = GameRequest {
+            game: GameMessage {
+                id: &self.id,
+                ruleset: Ruleset {
+                    name: if rules.shrink_every.is_some() {
+                        "royale"
+                    } else {
+                        "standard"
+                    },
+                    version: RULESET_VERSION,
+                    settings: Settings {
+                        food_spawn_chance: rules.food_spawn_chance,
+                        minimum_food: rules.minimum_food,
+                        hazard_damage_per_turn: rules.hazard_damage,
+                        royale: RoyaleSettings {
+                            shrink_every_n_turns: rules.shrink_every.unwrap_or(0),
+                        },
+                    },
+                },
+                map: "standard",
+                timeout: self.timeout.as_millis(),
+                source: "custom",
+            },
+            turn: self.state.get_turn(),
+            board: BoardMessage {
+                height: rules.height,
+                width: rules.width,
+                food: self
+                    .state
+                    .get_food()
+                    .iter()
+                    .map(|pos| self.coord(pos))
+                    .collect(),
+                hazards: hazards.into_iter().map(|pos| self.coord(pos)).collect(),
+                snakes: snakes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, snake)| snake.is_alive())
+                    .map(|(i, snake)| self.snake_message(i, snake))
+                    .collect(),
+            },
+            you: self.snake_message(index, &snakes[index]),
+        };
+
+        // Plain structs of strings and numbers always serialize
+        serde_json::to_string(&request).unwrap_or_default()
+    }
+
+    /// Post the game to every server in `players` at the same time, with
+    /// what each answered.
+    fn post_all<T: Send>(
+        &self,
+        players: &[usize],
+        send: impl Fn(&Server, &str) -> T + Sync,
+    ) -> Vec<(usize, T)> {
+        let requests: Vec<(usize, String)> =
+            players.iter().map(|i| (*i, self.request(*i))).collect();
+        let send = &send;
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = requests
+                .iter()
+                .map(|(i, body)| {
+                    let server = &self.servers[*i];
+                    (*i, scope.spawn(move || send(server, body)))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|(i, handle)| handle.join().ok().map(|reply| (i, reply)))
+                .collect()
+        })
+    }
+
+    fn alive(&self) -> Vec<usize> {
+        (0..self.servers.len())
+            .filter(|i| self.state.get_snakes()[*i].is_alive())
+            .collect()
+    }
+
+    /// Tell every server the game starts. Servers that fail to answer
+    /// still play.
+    pub fn start(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        for (_, reply) in self.post_all(&self.alive(), |server, body| server.post("start", body)) {
+            if let Err(err) = reply {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    /// Ask every snake still in the game for its move and play one turn.
+    /// Returns the snakes eliminated on it.
+    pub fn turn(&mut self) -> Vec<(usize, Elimination)> {
+        if !self.started {
+            self.start();
+        }
+        if self.ended {
+            return Vec::new();
+        }
+
+        let replies = self.post_all(&self.alive(), |server, body| {
+            let start = Instant::now();
+            server
+                .request_move(body)
+                .map(|reply| (reply, start.elapsed()))
+        });
+
+        let mut moves = vec![None; self.servers.len()];
+        for (i, reply) in replies {
+            let server = &mut self.servers[i];
+            match reply {
+                Ok(((dir, shout), latency)) => {
+                    moves[i] = Some(dir);
+                    server.latency = Some(latency);
+                    server.shout = shout;
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    server.latency = None;
+                }
+            }
+        }
+
+        let eliminated = step_arena(&mut self.state, &moves);
+        if self.state.is_over() {
+            self.end();
+        }
+
+        eliminated
+    }
+
+    /// Tell every server the game is over, including the snakes that were
+    /// eliminated before.
+    pub fn end(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let everyone: Vec<usize> = (0..self.servers.len()).collect();
+        for (_, reply) in self.post_all(&everyone, |server, body| server.post("end", body)) {
+            if let Err(err) = reply {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    /// End the game and start a new one on the same servers.
+    pub fn restart(&mut self, seed: u64) {
+        self.end();
+        let rules = self.state.get_rules().clone();
+        let mut servers = std::mem::take(&mut self.servers);
+        for server in servers.iter_mut() {
+            server.latency = None;
+            server.shout.clear();
+        }
+        *self = Self::new(servers, rules, seed, self.timeout);
+    }
+
+    /// How the game ended, one line per snake after a headline.
+    pub fn summary(&self) -> String {
+        let state = &self.state;
+        let mut lines = vec![match (state.is_over(), state.get_winner()) {
+            (false, _) => format!("stopped after {} turns", state.get_turn()),
+            (true, Some(winner)) => format!(
+                "{} wins after {} turns",
+                self.servers[winner].name,
+                state.get_turn()
+            ),
+            (true, None) if self.servers.len() > 1 => {
+                format!("draw after {} turns", state.get_turn())
+            }
+            (true, None) => format!("game over after {} turns", state.get_turn()),
+        }];
+        for (server, snake) in self.servers.iter().zip(state.get_snakes()) {
+            lines.push(match snake.eliminated {
+                Some((cause, turn)) => format!(
+                    "  {}: {} on turn {}, length {}",
+                    server.name,
+                    cause,
+                    turn,
+                    snake.body.len()
+                ),
+                None => format!(
+                    "  {}: alive, length {}, health {}",
+                    server.name,
+                    snake.body.len(),
+                    snake.health
+                ),
+            });
+        }
+
+        lines.join("\n")
+    }
+
+    /// Play until the game is over or `max_turns` turns have passed.
+    pub fn play(&mut self, max_turns: u32) {
+        self.start();
+        while !self.ended && self.state.get_turn() < max_turns {
+            self.turn();
+        }
+        self.end();
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::physics::{Direction, Position};
+
+/// What happens when the snake leaves the board.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Topology {
+    /// Classic rules, the edges are walls.
+    Walled,
+    /// The board is a torus, leaving one edge enters the opposite one.
+    Wrap,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    pub width: u32,
+    pub height: u32,
+    pub topology: Topology,
+    /// Cells blocked by obstacles from a level, see `level.rs`
+    pub walls: HashSet<Position>,
+    /// Both cells of every portal pair, each mapped to its partner
+    pub portals: HashMap<Position, Position>,
+}
+
+impl Board {
+    pub fn new(width: u32, height: u32, topology: Topology) -> Self {
+        Self {
+            width,
+            height,
+            topology,
+            walls: HashSet::new(),
+            portals: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, pos: &Position) -> bool {
+        pos.x >= 0 && pos.x < self.width as i32 && pos.y >= 0 && pos.y < self.height as i32
+    }
+
+    pub fn is_wall(&self, pos: &Position) -> bool {
+        self.walls.contains(pos)
+    }
+
+    pub fn is_portal(&self, pos: &Position) -> bool {
+        self.portals.contains_key(pos)
+    }
+
+    /// Connect two cells with a portal.
+    pub fn add_portal(&mut self, a: Position, b: Position) {
+        self.portals.insert(a.clone(), b.clone());
+        self.portals.insert(b, a);
+    }
+
+    /// Number of cells the snake can move on.
+    pub fn free_cells(&self) -> usize {
+        (self.width * self.height) as usize - self.walls.len() - self.portals.len()
+    }
+
+    /// The cell one step from `pos` in `dir`. If that is a portal, it is the
+    /// cell one step past its partner instead, keeping the heading. On a
+    /// walled board this may be off the board; on a wrapping board it never
+    /// is.
+    pub fn neighbor(&self, pos: &Position, dir: Direction) -> Position {
+        match self.portal_towards(pos, dir) {
+            Some((_, exit)) => self.step(exit, dir),
+            None => self.step(pos, dir),
+        }
+    }
+
+    /// The portal entered by moving from `pos` in `dir` and its partner the
+    /// snake comes out of, if there is one.
+    pub fn portal_towards(&self, pos: &Position, dir: Direction) -> Option<(Position, &Position)> {
+        let next = self.step(pos, dir);
+        let exit = self.portals.get(&next)?;
+        Some((next, exit))
+    }
+
+    fn step(&self, pos: &Position, dir: Direction) -> Position {
+        let mut next = pos.clone();
+        next.move_to_dir(dir);
+
+        if self.topology == Topology::Wrap {
+            next.x = next.x.rem_euclid(self.width as i32);
+            next.y = next.y.rem_euclid(self.height as i32);
+        }
+
+        next
+    }
+}
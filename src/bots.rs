@@ -0,0 +1,327 @@
+//! Built-in bots for demos, AI opponents and headless runs.
+//!
+//! - `GreedyBot` takes the shortest path to the fruit (a breadth-first
+//!   search, every step costs the same), going around every snake. Without
+//!   a path it moves to where it has the most room. Fast, but it eventually
+//!   traps itself.
+//! - `HamiltonianBot` follows a cycle through every cell of the board, so
+//!   it can't run into itself and fills the whole board. While the snake is
+//!   short it takes shortcuts along the cycle towards the fruit. Boards the
+//!   cycle can't be built on (walls, portals, both sides odd) and other
+//!   snakes in the way fall back to the greedy bot.
+
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+
+use crate::board::Board;
+use crate::controller::{BoardView, Controller};
+use crate::physics::{Direction, Position};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+/// Shortcuts stay this many cells behind the tail, room for the snake to
+/// grow while it follows the cycle
+const SHORTCUT_MARGIN: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BotKind {
+    Greedy,
+    Hamiltonian,
+}
+
+impl BotKind {
+    pub fn create(self) -> Box<dyn Controller> {
+        match self {
+            BotKind::Greedy => Box::new(GreedyBot),
+            BotKind::Hamiltonian => Box::new(HamiltonianBot::default()),
+        }
+    }
+}
+
+impl FromStr for BotKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "greedy" => Ok(BotKind::Greedy),
+            "hamiltonian" => Ok(BotKind::Hamiltonian),
+            _ => Err(format!(
+                "unknown bot '{}', expected greedy or hamiltonian",
+                s
+            )),
+        }
+    }
+}
+
+/// Moves that don't kill the snake right away, with the cell each leads to.
+fn safe_moves<'a>(view: &'a BoardView) -> impl Iterator<Item = (Direction, Position)> + 'a {
+    let snake = view.get_snake();
+    let head = snake.get_head_pos();
+    let board = view.get_board();
+
+    DIRECTIONS
+        .iter()
+        .filter(move |dir| **dir != snake.get_dir().opposite())
+        .map(move |dir| (*dir, board.neighbor(head, *dir)))
+        .filter(move |(_, next)| !view.is_blocked(next))
+}
+
+/// Number of free cells reachable from `start`, up to `limit`.
+fn room(view: &BoardView, start: &Position, limit: usize) -> usize {
+    let board = view.get_board();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    seen.insert(start.clone());
+    queue.push_back(start.clone());
+
+    while let Some(pos) = queue.pop_front() {
+        if seen.len() >= limit {
+            break;
+        }
+        for dir in DIRECTIONS.iter() {
+            let next = board.neighbor(&pos, *dir);
+            if !view.is_blocked(&next) && seen.insert(next.clone()) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    seen.len()
+}
+
+/// First move of the shortest free path from the head to the fruit.
+fn path_to_fruit(view: &BoardView) -> Option<Direction> {
+    let board = view.get_board();
+    let target = &view.get_fruit()?.pos;
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    for (dir, next) in safe_moves(view) {
+        if seen.insert(next.clone()) {
+            queue.push_back((next, dir));
+        }
+    }
+
+    while let Some((pos, first)) = queue.pop_front() {
+        if pos == *target {
+            return Some(first);
+        }
+        for dir in DIRECTIONS.iter() {
+            let next = board.neighbor(&pos, *dir);
+            if !view.is_blocked(&next) && seen.insert(next.clone()) {
+                queue.push_back((next, first));
+            }
+        }
+    }
+
+    None
+}
+
+/// The safe move with the most room behind it, straight ahead if there is
+/// none.
+fn roomiest_move(view: &BoardView) -> Direction {
+    let limit = view.get_board().free_cells();
+
+    safe_moves(view)
+        .max_by_key(|(_, next)| room(view, next, limit))
+        .map_or_else(|| view.get_snake().get_dir(), |(dir, _)| dir)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct GreedyBot;
+
+impl Controller for GreedyBot {
+    fn next_direction(&mut self, view: &BoardView) -> Direction {
+        let length = view.get_snake().get_body_len();
+        let limit = view.get_board().free_cells();
+
+        // Only chase the fruit if the snake still fits where the path
+        // starts
+        match path_to_fruit(view) {
+            Some(dir) => {
+                let next = view
+                    .get_board()
+                    .neighbor(view.get_snake().get_head_pos(), dir);
+                if room(view, &next, limit) >= length {
+                    dir
+                } else {
+                    roomiest_move(view)
+                }
+            }
+            None => roomiest_move(view),
+        }
+    }
+}
+
+/// Order of every cell along a Hamiltonian cycle of the board, indexed by
+/// `y * width + x`. `None` if the board has walls or portals, or both of
+/// its sides are odd.
+fn hamiltonian_cycle(board: &Board) -> Option<Vec<usize>> {
+    if !board.walls.is_empty() || !board.portals.is_empty() {
+        return None;
+    }
+
+    let (width, height) = (board.width as usize, board.height as usize);
+    // Zigzag through the rows to the right of the first column and come
+    // back up along it, which needs an even number of rows; transpose the
+    // board if only the columns are even
+    let (cols, rows, transposed) = if height % 2 == 0 {
+        (width, height, false)
+    } else if width % 2 == 0 {
+        (height, width, true)
+    } else {
+        return None;
+    };
+
+    let mut path = Vec::with_capacity(cols * rows);
+    path.extend((0..cols).map(|c| (c, 0)));
+    for r in 1..rows {
+        if r % 2 == 1 {
+            path.extend((1..cols).rev().map(|c| (c, r)));
+        } else {
+            path.extend((1..cols).map(|c| (c, r)));
+        }
+    }
+    path.extend((1..rows).rev().map(|r| (0, r)));
+
+    let mut order = vec![0; width * height];
+    for (i, (c, r)) in path.into_iter().enumerate() {
+        let (x, y) = if transposed { (r, c) } else { (c, r) };
+        order[y * width + x] = i;
+    }
+
+    Some(order)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HamiltonianBot {
+    // Built for the board size of the first tick, `None` until then
+    cycle: Option<(u32, u32, Option<Vec<usize>>)>,
+}
+
+impl HamiltonianBot {
+    fn cycle(&mut self, board: &Board) -> Option<&Vec<usize>> {
+        let size = (board.width, board.height);
+        let stale = match &self.cycle {
+            Some((width, height, _)) => (*width, *height) != size,
+            None => true,
+        };
+        if stale {
+            self.cycle = Some((board.width, board.height, hamiltonian_cycle(board)));
+        }
+
+        self.cycle.as_ref().and_then(|(_, _, cycle)| cycle.as_ref())
+    }
+}
+
+impl Controller for HamiltonianBot {
+    fn next_direction(&mut self, view: &BoardView) -> Direction {
+        let board = view.get_board();
+        let cycle = match self.cycle(board) {
+            Some(cycle) => cycle,
+            None => return GreedyBot.next_direction(view),
+        };
+
+        let cells = cycle.len();
+        let width = board.width as i32;
+        let index = |pos: &Position| {
+            if board.contains(pos) {
+                Some(cycle[(pos.y * width + pos.x) as usize])
+            } else {
+                None
+            }
+        };
+        // Steps along the cycle from `a` to `b`
+        let distance = |a: usize, b: usize| (b + cells - a) % cells;
+
+        let snake = view.get_snake();
+        let head = match index(snake.get_head_pos()) {
+            Some(head) => head,
+            None => return GreedyBot.next_direction(view),
+        };
+        let moves: Vec<(Direction, usize)> = safe_moves(view)
+            .filter_map(|(dir, next)| index(&next).map(|i| (dir, i)))
+            .collect();
+
+        // Taking a shortcut is safe as long as the head stays behind the
+        // tail along the cycle. The start position isn't on the cycle
+        // yet, so a tail off the board allows no shortcuts at all.
+        let tail = snake.cells().last().and_then(index);
+        let fruit = view.get_fruit().and_then(|fruit| index(&fruit.pos));
+        let shortcut = match (tail, fruit) {
+            (Some(tail), Some(fruit)) if snake.get_body_len() * 2 < cells => moves
+                .iter()
+                .filter(|(_, next)| {
+                    let skip = distance(head, *next);
+                    skip + SHORTCUT_MARGIN < distance(head, tail) && skip <= distance(head, fruit)
+                })
+                .max_by_key(|(_, next)| distance(head, *next)),
+            _ => None,
+        };
+        if let Some((dir, _)) = shortcut {
+            return *dir;
+        }
+
+        // Otherwise the next cell along the cycle
+        match moves.iter().find(|(_, next)| *next == (head + 1) % cells) {
+            Some((dir, _)) => *dir,
+            None => GreedyBot.next_direction(view),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Topology;
+    use crate::controller;
+    use crate::replay::Replay;
+    use crate::sim::{self, GameState, GameStatus, Rules};
+
+    fn boards() -> Vec<Board> {
+        vec![
+            Board::new(10, 10, Topology::Walled),
+            Board::new(8, 8, Topology::Wrap),
+            Board::new(12, 9, Topology::Walled),
+        ]
+    }
+
+    #[test]
+    fn hamiltonian_bot_fills_even_boards() {
+        for board in boards() {
+            for seed in 0..4 {
+                let mut replay = Replay::new(seed, Rules::new(board.clone()));
+                let mut bots = [BotKind::Hamiltonian.create()];
+                let state = controller::play(&mut replay, &mut bots, 1_000_000);
+                assert_eq!(
+                    state.get_status(),
+                    GameStatus::Won,
+                    "{}x{} {:?}, seed {}",
+                    board.width,
+                    board.height,
+                    board.topology,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn greedy_bot_never_reverses() {
+        for board in boards() {
+            for seed in 0..4 {
+                let mut state = GameState::new(Rules::new(board.clone()), seed);
+                let mut bot = GreedyBot;
+                while state.get_status() == GameStatus::Playing && state.get_tick() < 10_000 {
+                    let dir = bot.next_direction(&BoardView::new(&state, 0));
+                    assert_ne!(dir, state.get_snake().get_dir().opposite());
+                    sim::step(&mut state, Some(dir));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! An ordered list of levels with a goal for each, and the player's
+//! progress through it.
+//!
+//! The campaign is read from `assets/levels/campaign.toml`:
+//!
+//! ```toml
+//! [[levels]]
+//! level = "box"
+//! goal = { fruits = 10 }
+//!
+//! [[levels]]
+//! level = "rooms"
+//! goal = { length = 20 }
+//! ```
+//!
+//! A goal is one of `fruits` (fruits eaten, of any kind), `length` (length
+//! of the snake including its head) or `score`. Progress is saved to
+//! `$XDG_DATA_HOME/rsnake/progress.toml` after every cleared level.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::level::{level_path, Level, LEVEL_DIR};
+
+pub const CAMPAIGN_FILE: &str = "campaign.toml";
+
+/// What it takes to clear a level.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Goal {
+    Fruits(u32),
+    Length(usize),
+    Score(u32),
+}
+
+impl Goal {
+    pub fn is_met(&self, fruits: u32, length: usize, score: u32) -> bool {
+        match *self {
+            Goal::Fruits(n) => fruits >= n,
+            Goal::Length(n) => length >= n,
+            Goal::Score(n) => score >= n,
+        }
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Goal::Fruits(n) => write!(f, "Eat {} fruits", n),
+            Goal::Length(n) => write!(f, "Reach length {}", n),
+            Goal::Score(n) => write!(f, "Score {} points", n),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignEntry {
+    level: String,
+    goal: Goal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CampaignFile {
+    levels: Vec<CampaignEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub level: Level,
+    pub goal: Goal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub stages: Vec<Stage>,
+}
+
+impl Campaign {
+    /// Load the campaign and all of its levels from the asset directory.
+    pub fn load(assets: &Path) -> Result<Self, String> {
+        let path = assets.join(LEVEL_DIR).join(CAMPAIGN_FILE);
+        let text =
+            fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let file: CampaignFile =
+            toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        if file.levels.is_empty() {
+            return Err(format!("{}: campaign has no levels", path.display()));
+        }
+
+        let mut stages = Vec::with_capacity(file.levels.len());
+        for entry in file.levels {
+            let level_file = level_path(assets, &entry.level);
+            let level = Level::load(&level_file)
+                .map_err(|err| format!("{}: {}", level_file.display(), err))?;
+            stages.push(Stage {
+                level,
+                goal: entry.goal,
+            });
+        }
+
+        Ok(Self { stages })
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+/// How far the player got in the campaign.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    /// Number of levels cleared, the next one is the first locked one
+    pub cleared: usize,
+}
+
+impl Progress {
+    /// `progress.toml` in the user's data directory, if there is one.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rsnake").join("progress.toml"))
+    }
+
+    /// Load the saved progress, starting from scratch if there is none or
+    /// it can't be read.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    /// Whether level `index` (zero based) can be played.
+    pub fn is_unlocked(&self, index: usize) -> bool {
+        index <= self.cleared
+    }
+
+    /// Record that level `index` was cleared, returns whether that unlocked
+    /// a new level.
+    pub fn clear(&mut self, index: usize) -> bool {
+        if index + 1 > self.cleared {
+            self.cleared = index + 1;
+            true
+        } else {
+            false
+        }
+    }
+}
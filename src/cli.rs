@@ -0,0 +1,510 @@
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rsnake::arena::{self, ArenaRules, SHRINK_EVERY};
+use rsnake::battlesnake;
+use rsnake::board::{Board, Topology};
+use rsnake::bots::BotKind;
+use rsnake::clip::PaletteKind;
+use rsnake::controller::Controller;
+use rsnake::external::{ExternalBot, OnTimeout, DEFAULT_MOVE_TIMEOUT};
+use rsnake::fruit::FruitWeights;
+use rsnake::level::Level;
+use rsnake::netplay::{DEFAULT_INPUT_DELAY, DEFAULT_PORT, MAX_INPUT_DELAY};
+use rsnake::sim::Rules;
+use rsnake::snake::DEFAULT_START_LENGTH;
+use rsnake::spectate;
+use rsnake::speed::{Curve, SpeedCurve};
+use rsnake::versus::{check_best_of, DEFAULT_BEST_OF};
+
+const REPLAY_CONFLICTS: [&str; 7] = [
+    "width", "height", "seed", "length", "wrap", "level", "campaign",
+];
+const LEVEL_CONFLICTS: [&str; 3] = ["width", "height", "wrap"];
+const CAMPAIGN_CONFLICTS: [&str; 5] = ["width", "height", "wrap", "level", "seed"];
+const VERSUS_CONFLICTS: [&str; 3] = ["level", "campaign", "replay"];
+const BOT_CONFLICTS: [&str; 2] = ["campaign", "replay"];
+const HOST_CONFLICTS: [&str; 6] = [
+    "level",
+    "campaign",
+    "replay",
+    "versus",
+    "opponent",
+    "opponent_command",
+];
+// The host decides the rules
+const CONNECT_CONFLICTS: [&str; 14] = [
+    "width",
+    "height",
+    "seed",
+    "length",
+    "wrap",
+    "level",
+    "campaign",
+    "replay",
+    "versus",
+    "best_of",
+    "opponent",
+    "opponent_command",
+    "host",
+    "input_delay",
+];
+const ARENA_CONFLICTS: [&str; 8] = [
+    "length", "wrap", "level", "campaign", "versus", "replay", "host", "connect",
+];
+const SPECTATE_CONFLICTS: [&str; 2] = ["battlesnake", "headless"];
+// Spectators only watch
+const WATCH_CONFLICTS: [&str; 19] = [
+    "width",
+    "height",
+    "seed",
+    "length",
+    "wrap",
+    "level",
+    "campaign",
+    "replay",
+    "versus",
+    "best_of",
+    "host",
+    "connect",
+    "bot",
+    "opponent",
+    "bot_command",
+    "opponent_command",
+    "battlesnake",
+    "headless",
+    "spectate",
+];
+const TERMINAL_CONFLICTS: [&str; 3] = ["campaign", "battlesnake", "headless"];
+// Only games that can be played to a tick at once
+const RENDER_CONFLICTS: [&str; 8] = [
+    "campaign",
+    "host",
+    "connect",
+    "watch",
+    "battlesnake",
+    "spectate",
+    "terminal",
+    "headless",
+];
+/// Snakes in a Battlesnake game, one per start position
+const MAX_ARENA_SNAKES: usize = 8;
+/// Blocks across and down the board outside of Battlesnake games
+const DEFAULT_BOARD_SIZE: u32 = 25;
+
+/// A classic Snake game with particle effects and Snake Jazz.
+#[derive(Parser, Debug)]
+#[command(name = "rsnake", version, about)]
+#[command(group(ArgGroup::new("player_bot").args(["bot", "bot_command", "battlesnake"])))]
+#[command(group(ArgGroup::new("opponent_bot").args(["opponent", "opponent_command"])))]
+#[command(group(ArgGroup::new("two_players").args(["versus", "host"]).multiple(true)))]
+#[command(group(ArgGroup::new("no_window").args(["headless", "render_frame", "render_gif"])))]
+pub struct Args {
+    /// Board width in blocks [default: 25, 11 for Battlesnake]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(5..=100))]
+    pub width: Option<u32>,
+
+    /// Board height in blocks [default: 25, 11 for Battlesnake]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(5..=100))]
+    pub height: Option<u32>,
+
+    /// Game ticks per second at the start, higher is faster [default: from config, 10]
+    #[arg(long, value_parser = parse_tick_rate)]
+    pub tick_rate: Option<f64>,
+
+    /// Highest tick rate the snake speeds up to [default: from config, 20]
+    #[arg(long, value_parser = parse_tick_rate)]
+    pub max_tick_rate: Option<f64>,
+
+    /// How the speed grows with the snake: constant, linear or exponential [default: from config, linear]
+    #[arg(long, value_name = "CURVE")]
+    pub speed_curve: Option<Curve>,
+
+    /// Seed for the first game (random if omitted)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Starting length of the snake, including its head
+    #[arg(long, default_value_t = DEFAULT_START_LENGTH, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub length: usize,
+
+    /// Wrap around the board edges instead of dying on them
+    #[arg(long)]
+    pub wrap: bool,
+
+    /// Level to play, a name from assets/levels or a path to a level file
+    #[arg(long, value_name = "LEVEL", conflicts_with_all = LEVEL_CONFLICTS)]
+    pub level: Option<String>,
+
+    /// Play the campaign, starting on the level select screen
+    #[arg(long, conflicts_with_all = CAMPAIGN_CONFLICTS)]
+    pub campaign: bool,
+
+    /// Two players on one keyboard, WASD against the arrow keys
+    #[arg(long, conflicts_with_all = VERSUS_CONFLICTS)]
+    pub versus: bool,
+
+    /// Rounds in a versus match, the first to win the majority wins the match
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BEST_OF, requires = "two_players", value_parser = parse_best_of)]
+    pub best_of: u32,
+
+    /// Host an online versus match and wait for a player to --connect
+    #[arg(long, conflicts_with_all = HOST_CONFLICTS)]
+    pub host: bool,
+
+    /// Port to host on
+    #[arg(long, default_value_t = DEFAULT_PORT, requires = "host")]
+    pub port: u16,
+
+    /// Join an online match at HOST or HOST:PORT, the host sets the rules
+    #[arg(long, value_name = "ADDR", conflicts_with_all = CONNECT_CONFLICTS)]
+    pub connect: Option<String>,
+
+    /// Ticks between pressing a key and the turn in an online match
+    #[arg(long, value_name = "TICKS", default_value_t = DEFAULT_INPUT_DELAY, requires = "host", value_parser = clap::value_parser!(u64).range(0..=MAX_INPUT_DELAY))]
+    pub input_delay: u64,
+
+    /// Let spectators --watch the game
+    #[arg(long, conflicts_with_all = SPECTATE_CONFLICTS)]
+    pub spectate: bool,
+
+    /// Port spectators connect to
+    #[arg(long, value_name = "PORT", default_value_t = spectate::DEFAULT_PORT, requires = "spectate")]
+    pub spectate_port: u16,
+
+    /// Watch a game at HOST or HOST:PORT that lets spectators in
+    #[arg(long, value_name = "ADDR", conflicts_with_all = WATCH_CONFLICTS)]
+    pub watch: Option<String>,
+
+    /// Let a bot steer the (first) snake, online the local one: greedy or hamiltonian
+    #[arg(long, value_name = "BOT", conflicts_with_all = BOT_CONFLICTS)]
+    pub bot: Option<BotKind>,
+
+    /// Bot steering the second snake in versus instead of the arrow keys
+    #[arg(long, value_name = "BOT", requires = "versus")]
+    pub opponent: Option<BotKind>,
+
+    /// Let a program steer the (first) snake, see external.rs for the protocol
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = BOT_CONFLICTS)]
+    pub bot_command: Option<String>,
+
+    /// Program steering the second snake in versus
+    #[arg(long, value_name = "COMMAND", requires = "versus")]
+    pub opponent_command: Option<String>,
+
+    /// Host a Battlesnake game between HTTP servers, give once per snake
+    #[arg(long, value_name = "URL", conflicts_with_all = ARENA_CONFLICTS)]
+    pub battlesnake: Vec<String>,
+
+    /// Battlesnake royale: hazards close in from the edges every 25 turns
+    #[arg(long, requires = "battlesnake")]
+    pub hazards: bool,
+
+    /// Milliseconds a bot program gets for each move [default: 100, 500 for Battlesnake servers]
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..=10_000))]
+    pub move_timeout: Option<u64>,
+
+    /// What a bot program that is too slow or answers nonsense does: straight or forfeit
+    #[arg(long, value_name = "POLICY", default_value = "straight")]
+    pub on_timeout: OnTimeout,
+
+    /// Play in the terminal instead of a window, e.g. over SSH
+    #[arg(long, conflicts_with_all = TERMINAL_CONFLICTS)]
+    pub terminal: bool,
+
+    /// Play without a window and print the result, needs a bot or Battlesnake servers
+    #[arg(long, requires = "player_bot")]
+    pub headless: bool,
+
+    /// Draw the game into a PNG without a window and exit: a --replay, or a game the bots play
+    #[arg(long, value_name = "FILE", conflicts_with_all = RENDER_CONFLICTS)]
+    pub render_frame: Option<PathBuf>,
+
+    /// Write every tick of the game into an animated GIF without a window and exit, like --render-frame
+    #[arg(long, value_name = "FILE", conflicts_with_all = RENDER_CONFLICTS)]
+    pub render_gif: Option<PathBuf>,
+
+    /// Size of the GIF relative to the window
+    #[arg(long, value_name = "SCALE", default_value_t = 1.0, requires = "render_gif", value_parser = parse_gif_scale)]
+    pub gif_scale: f64,
+
+    /// Ticks left out between two frames of the GIF
+    #[arg(
+        long,
+        value_name = "TICKS",
+        default_value_t = 0,
+        requires = "render_gif"
+    )]
+    pub frame_skip: u64,
+
+    /// Colors of the GIF: local picks them for every frame, global once for all (smaller files)
+    #[arg(
+        long,
+        value_name = "PALETTE",
+        default_value = "local",
+        requires = "render_gif"
+    )]
+    pub gif_palette: PaletteKind,
+
+    /// Leave the particle bursts out of the GIF
+    #[arg(long, requires = "render_gif")]
+    pub no_particles: bool,
+
+    /// Stop a headless game after this many ticks (turns for Battlesnake), or draw the frame at it
+    #[arg(
+        long,
+        value_name = "TICKS",
+        default_value_t = 1_000_000,
+        requires = "no_window"
+    )]
+    pub max_ticks: u64,
+
+    /// Number of turns buffered between two ticks [default: from config, 3]
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..=8))]
+    pub input_buffer: Option<usize>,
+
+    /// Start with the music muted
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Directory containing the font, music and sound effects
+    #[arg(long, value_name = "DIR")]
+    pub assets: Option<PathBuf>,
+
+    /// Config file to use instead of the one in the user config directory
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Play back a recorded game instead of starting a new one
+    #[arg(long, value_name = "FILE", conflicts_with_all = REPLAY_CONFLICTS)]
+    pub replay: Option<PathBuf>,
+}
+
+fn with_port(addr: &str, port: u16) -> String {
+    if addr.contains(':') {
+        addr.to_string()
+    } else {
+        format!("{}:{}", addr, port)
+    }
+}
+
+fn parse_tick_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+
+    if !(1.0..=60.0).contains(&rate) {
+        return Err("must be between 1 and 60 ticks per second".to_string());
+    }
+
+    Ok(rate)
+}
+
+fn parse_gif_scale(s: &str) -> Result<f64, String> {
+    let scale: f64 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+
+    if !(0.1..=4.0).contains(&scale) {
+        return Err("must be between 0.1 and 4".to_string());
+    }
+
+    Ok(scale)
+}
+
+fn parse_best_of(s: &str) -> Result<u32, String> {
+    let rounds: u32 = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+    check_best_of(rounds)?;
+
+    Ok(rounds)
+}
+
+impl Args {
+    /// Parse the command line, exiting with a usage message on bad input.
+    pub fn parse_and_validate() -> Self {
+        let args = Self::parse();
+
+        let (width, height) = args.board_size();
+        if args.length > height as usize {
+            Self::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "--length {} does not fit on a board {} blocks high",
+                        args.length, height
+                    ),
+                )
+                .exit();
+        }
+
+        // Both snakes start a quarter of the board in from the side walls,
+        // with their tails towards the walls
+        let versus_length = width as usize / 4 + 1;
+        if (args.versus || args.host) && args.length > versus_length {
+            Self::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "--length {} does not fit two snakes on a board {} blocks wide, at most {}",
+                        args.length, width, versus_length
+                    ),
+                )
+                .exit();
+        }
+
+        if args.headless
+            && args.versus
+            && args.opponent.is_none()
+            && args.opponent_command.is_none()
+        {
+            Self::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--headless --versus needs an --opponent bot for the second snake",
+                )
+                .exit();
+        }
+
+        if args.battlesnake.len() > MAX_ARENA_SNAKES {
+            Self::command()
+                .error(
+                    ErrorKind::TooManyValues,
+                    format!(
+                        "--battlesnake given {} times, at most {} snakes play",
+                        args.battlesnake.len(),
+                        MAX_ARENA_SNAKES
+                    ),
+                )
+                .exit();
+        }
+
+        if let Some(dir) = &args.assets {
+            if !dir.is_dir() {
+                Self::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!("asset directory {} does not exist", dir.display()),
+                    )
+                    .exit();
+            }
+        }
+
+        args
+    }
+
+    /// The speed curve from the config with the command line overrides
+    /// applied, exiting if they don't fit together.
+    pub fn speed_curve(&self, config: &SpeedCurve) -> SpeedCurve {
+        let mut curve = config.clone();
+        if let Some(start) = self.tick_rate {
+            curve.start = start;
+            curve.max = curve.max.max(start);
+        }
+        if let Some(max) = self.max_tick_rate {
+            curve.max = max;
+        }
+        if let Some(shape) = self.speed_curve {
+            curve.curve = shape;
+        }
+
+        if curve.max < curve.start {
+            Self::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "--max-tick-rate {} is below the starting tick rate {}",
+                        curve.max, curve.start
+                    ),
+                )
+                .exit();
+        }
+
+        curve
+    }
+
+    /// The bots and bot programs steering the snakes, by player. `None`
+    /// for snakes steered by the keyboard.
+    pub fn bots(&self) -> io::Result<Vec<Option<Box<dyn Controller>>>> {
+        let timeout = self
+            .move_timeout
+            .map_or(DEFAULT_MOVE_TIMEOUT, Duration::from_millis);
+        let create = |kind: Option<BotKind>, command: &Option<String>| match (kind, command) {
+            (Some(kind), _) => Ok(Some(kind.create())),
+            (None, Some(command)) => ExternalBot::spawn(command, timeout, self.on_timeout)
+                .map(|bot| Some(Box::new(bot) as Box<dyn Controller>)),
+            (None, None) => Ok(None),
+        };
+
+        Ok(vec![
+            create(self.bot, &self.bot_command)?,
+            create(self.opponent, &self.opponent_command)?,
+        ])
+    }
+
+    /// Address to connect to for an online match, with the default port
+    /// if none was given.
+    pub fn connect_addr(&self) -> Option<String> {
+        self.connect
+            .as_ref()
+            .map(|addr| with_port(addr, DEFAULT_PORT))
+    }
+
+    /// Address of the game to watch, with the default port if none was
+    /// given.
+    pub fn watch_addr(&self) -> Option<String> {
+        self.watch
+            .as_ref()
+            .map(|addr| with_port(addr, spectate::DEFAULT_PORT))
+    }
+
+    /// Time a Battlesnake server gets for each request.
+    pub fn arena_timeout(&self) -> Duration {
+        self.move_timeout
+            .map_or(battlesnake::DEFAULT_TIMEOUT, Duration::from_millis)
+    }
+
+    /// Width and height of the board, except in Battlesnake games.
+    pub fn board_size(&self) -> (u32, u32) {
+        (
+            self.width.unwrap_or(DEFAULT_BOARD_SIZE),
+            self.height.unwrap_or(DEFAULT_BOARD_SIZE),
+        )
+    }
+
+    /// The Battlesnake rules, on the standard 11x11 board unless the size
+    /// is given.
+    pub fn arena_rules(&self) -> ArenaRules {
+        ArenaRules {
+            width: self.width.unwrap_or(arena::DEFAULT_SIZE),
+            height: self.height.unwrap_or(arena::DEFAULT_SIZE),
+            shrink_every: if self.hazards {
+                Some(SHRINK_EVERY)
+            } else {
+                None
+            },
+            ..ArenaRules::default()
+        }
+    }
+
+    pub fn rules(&self, fruit_weights: &FruitWeights, level: Option<&Level>) -> Rules {
+        let mut rules = match level {
+            Some(level) => Rules::from_level(level),
+            None => {
+                let topology = if self.wrap {
+                    Topology::Wrap
+                } else {
+                    Topology::Walled
+                };
+                let (width, height) = self.board_size();
+                Rules::new(Board::new(width, height, topology))
+            }
+        };
+        rules.start_length = self.length;
+        rules.fruit_weights = fruit_weights.clone();
+        if self.versus || self.host {
+            rules.players = 2;
+        }
+
+        rules
+    }
+}
@@ -0,0 +1,148 @@
+//! Animated GIFs of games, e.g. highlight clips, from frames drawn on the
+//! CPU (see `software`).
+//!
+//! A GIF has at most 256 colors per frame. With a local palette every
+//! frame picks its own, which looks best. A global palette is learned once
+//! from a few sample frames and shared by all of them, which makes smaller
+//! files and keeps colors from flickering between frames, but colors that
+//! are not in the samples come out a little off.
+
+use color_quant::NeuQuant;
+use gif::{Encoder, Frame, Repeat};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::software::Pixmap;
+
+/// Trade-off between speed and quality when picking colors, 1 is the best
+/// and slowest, 30 the worst and fastest
+const QUANTIZE_SPEED: i32 = 10;
+/// Colors in a GIF palette
+const PALETTE_COLORS: usize = 256;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PaletteKind {
+    /// Every frame gets its own palette
+    Local,
+    /// One palette for the whole animation
+    Global,
+}
+
+impl FromStr for PaletteKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(PaletteKind::Local),
+            "global" => Ok(PaletteKind::Global),
+            _ => Err(format!("unknown palette '{}', expected local or global", s)),
+        }
+    }
+}
+
+fn encoding_error(err: gif::EncodingError) -> io::Error {
+    match err {
+        gif::EncodingError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+/// An animated GIF being written, looping forever.
+pub struct GifWriter {
+    encoder: Encoder<BufWriter<File>>,
+    width: u16,
+    height: u16,
+    // The global palette, `None` for local ones
+    quantizer: Option<NeuQuant>,
+    // Seconds of animation written so far, and the same in the hundredths
+    // of a second GIF delays are in, so rounding doesn't add up
+    time: f64,
+    delays: u64,
+}
+
+impl GifWriter {
+    /// A new GIF at `path` of `width` x `height` frames. A global palette is
+    /// learned from `samples`, which should show the colors that matter.
+    pub fn create(
+        path: &Path,
+        (width, height): (usize, usize),
+        palette: PaletteKind,
+        samples: &[Pixmap],
+    ) -> io::Result<Self> {
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}x{} is too large for a GIF", width, height),
+                ))
+            }
+        };
+
+        let quantizer = match palette {
+            PaletteKind::Local => None,
+            PaletteKind::Global => {
+                let pixels: Vec<u8> = samples.iter().flat_map(Pixmap::to_rgba8).collect();
+                Some(NeuQuant::new(QUANTIZE_SPEED, PALETTE_COLORS, &pixels))
+            }
+        };
+        let global_palette = match &quantizer {
+            Some(quantizer) => quantizer.color_map_rgb(),
+            None => Vec::new(),
+        };
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder =
+            Encoder::new(file, width, height, &global_palette).map_err(encoding_error)?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(encoding_error)?;
+
+        Ok(Self {
+            encoder,
+            width,
+            height,
+            quantizer,
+            time: 0.0,
+            delays: 0,
+        })
+    }
+
+    /// Add `frame`, shown for `duration` seconds.
+    pub fn add_frame(&mut self, frame: &Pixmap, duration: f64) -> io::Result<()> {
+        if frame.get_size() != (self.width as usize, self.height as usize) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame size differs from the GIF",
+            ));
+        }
+
+        let mut rgba = frame.to_rgba8();
+        let mut gif_frame = match &self.quantizer {
+            Some(quantizer) => {
+                let indices: Vec<u8> = rgba
+                    .chunks_exact(4)
+                    .map(|pixel| quantizer.index_of(pixel) as u8)
+                    .collect();
+                Frame::from_indexed_pixels(self.width, self.height, indices, None)
+            }
+            None => Frame::from_rgba_speed(self.width, self.height, &mut rgba, QUANTIZE_SPEED),
+        };
+
+        self.time += duration;
+        let until = (self.time * 100.0).round() as u64;
+        gif_frame.delay = (until - self.delays).min(u16::MAX as u64) as u16;
+        self.delays = until;
+
+        self.encoder.write_frame(&gif_frame).map_err(encoding_error)
+    }
+
+    /// Write the end of the GIF. Without this it is written when dropped,
+    /// but errors go unnoticed.
+    pub fn finish(self) -> io::Result<()> {
+        self.encoder.into_inner()?.flush()
+    }
+}
@@ -1,11 +1,103 @@
-use piston_window::types::Color;
+use serde::Deserialize;
+
+use crate::fruit::FruitKind;
+
+/// RGBA color, layout compatible with `piston_window::types::Color`.
+pub type Color = [f32; 4];
 
 pub const BACKGROUND: Color = [0.0, 0.0, 0.0, 1.0];
 pub const SCORE: Color = [1.0, 1.0, 1.0, 1.0];
 pub const SNAKE: Color = [0.1, 0.9, 0.1, 1.0];
+pub const SNAKE2: Color = [1.0, 0.55, 0.1, 1.0];
 pub const FRUIT: Color = [1.0, 0.0, 0.0, 1.0];
+pub const GOLDEN_FRUIT: Color = [1.0, 0.8, 0.1, 1.0];
+pub const POISON_FRUIT: Color = [0.6, 0.1, 0.8, 1.0];
+pub const SPEED_FRUIT: Color = [0.1, 0.7, 1.0, 1.0];
 pub const OVERLAY: Color = [1.0, 0.0, 0.0, 0.5];
+pub const WALL: Color = [0.45, 0.42, 0.4, 1.0];
+pub const PORTAL: Color = [0.3, 0.5, 1.0, 1.0];
+pub const WON_OVERLAY: Color = [1.0, 0.85, 0.0, 0.5];
+pub const HAZARD: Color = [0.55, 0.1, 0.6, 0.45];
 
 // Grid background colors
 pub const GRID_LIGHT: Color = [0.2, 0.35, 0.15, 1.0]; // Subtle green
 pub const GRID_DARK: Color = [0.25, 0.2, 0.15, 1.0]; // Subtle brown
+
+/// The colors used to draw the game. Defaults to the constants above and can
+/// be overridden in the `[colors]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    pub background: Color,
+    pub score: Color,
+    pub snake: Color,
+    /// The second player's snake in versus
+    pub snake2: Color,
+    pub fruit: Color,
+    pub golden_fruit: Color,
+    pub poison_fruit: Color,
+    pub speed_fruit: Color,
+    pub wall: Color,
+    pub portal: Color,
+    pub overlay: Color,
+    pub won_overlay: Color,
+    /// Hazard cells in Battlesnake arena games
+    pub hazard: Color,
+    pub grid_light: Color,
+    pub grid_dark: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: BACKGROUND,
+            score: SCORE,
+            snake: SNAKE,
+            snake2: SNAKE2,
+            fruit: FRUIT,
+            golden_fruit: GOLDEN_FRUIT,
+            poison_fruit: POISON_FRUIT,
+            speed_fruit: SPEED_FRUIT,
+            wall: WALL,
+            portal: PORTAL,
+            overlay: OVERLAY,
+            won_overlay: WON_OVERLAY,
+            hazard: HAZARD,
+            grid_light: GRID_LIGHT,
+            grid_dark: GRID_DARK,
+        }
+    }
+}
+
+impl Palette {
+    /// Every color with its name, for validation and error messages.
+    pub fn entries(&self) -> [(&'static str, Color); 15] {
+        [
+            ("background", self.background),
+            ("score", self.score),
+            ("snake", self.snake),
+            ("snake2", self.snake2),
+            ("fruit", self.fruit),
+            ("golden_fruit", self.golden_fruit),
+            ("poison_fruit", self.poison_fruit),
+            ("speed_fruit", self.speed_fruit),
+            ("wall", self.wall),
+            ("portal", self.portal),
+            ("overlay", self.overlay),
+            ("won_overlay", self.won_overlay),
+            ("hazard", self.hazard),
+            ("grid_light", self.grid_light),
+            ("grid_dark", self.grid_dark),
+        ]
+    }
+
+    /// Color of a fruit, also used for its particles.
+    pub fn fruit_color(&self, kind: FruitKind) -> Color {
+        match kind {
+            FruitKind::Normal => self.fruit,
+            FruitKind::Golden => self.golden_fruit,
+            FruitKind::Poison => self.poison_fruit,
+            FruitKind::Speed => self.speed_fruit,
+        }
+    }
+}
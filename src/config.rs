@@ -0,0 +1,314 @@
+//! User configuration.
+//!
+//! Read from `$XDG_CONFIG_HOME/rsnake/config.toml` (usually
+//! `~/.config/rsnake/config.toml`). Every setting is optional, anything
+//! missing keeps its default:
+//!
+//! ```toml
+//! block_size = 25.0
+//!
+//! [colors]
+//! snake = [0.1, 0.9, 0.1, 1.0]
+//!
+//! [particles]
+//! count = 12
+//! lifetime = 0.8
+//! speed = 150.0
+//! size = 6.0
+//!
+//! [audio]
+//! music_volume = 0.5
+//! sfx_volume = 0.8
+//!
+//! [speed]
+//! start = 10.0
+//! max = 20.0
+//! curve = "linear"
+//! ramp = 50.0
+//!
+//! [fruit]
+//! normal = 80
+//! golden = 8
+//! poison = 6
+//! speed = 6
+//!
+//! [game]
+//! input_buffer = 3
+//! high_scores = 10
+//!
+//! [keys]
+//! up = ["W", "Up"]
+//! mute = ["M"]
+//! ```
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::colors::Palette;
+use crate::fruit::FruitWeights;
+use crate::highscores;
+use crate::input::DEFAULT_INPUT_QUEUE_DEPTH;
+use crate::speed::SpeedCurve;
+
+pub const DEFAULT_BLOCK_SIZE: f64 = 25.0;
+
+#[derive(Debug)]
+pub struct ConfigError {
+    path: PathBuf,
+    message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticleSettings {
+    /// Particles spawned per burst
+    pub count: usize,
+    /// Seconds until a particle has faded out
+    pub lifetime: f64,
+    /// Maximum speed in pixels per second
+    pub speed: f64,
+    /// Edge length in pixels
+    pub size: f64,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        Self {
+            count: 12,
+            lifetime: 0.8,
+            speed: 150.0,
+            size: 6.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.5,
+            sfx_volume: 0.8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameSettings {
+    /// Turns buffered between two ticks
+    pub input_buffer: usize,
+    /// Scores kept per game mode and board size
+    pub high_scores: usize,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            input_buffer: DEFAULT_INPUT_QUEUE_DEPTH,
+            high_scores: highscores::DEFAULT_TOP,
+        }
+    }
+}
+
+/// Key names for every action. Names are resolved by the frontend, see
+/// `keys.rs` for the accepted ones.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub restart: Vec<String>,
+    pub mute: Vec<String>,
+    pub screenshot: Vec<String>,
+    pub high_scores: Vec<String>,
+    pub pause: Vec<String>,
+    pub step: Vec<String>,
+    pub slower: Vec<String>,
+    pub faster: Vec<String>,
+    pub confirm: Vec<String>,
+    pub back: Vec<String>,
+    /// The second player's snake in versus
+    pub p2_up: Vec<String>,
+    pub p2_down: Vec<String>,
+    pub p2_left: Vec<String>,
+    pub p2_right: Vec<String>,
+}
+
+fn keys(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: keys(&["W", "Up"]),
+            down: keys(&["S", "Down"]),
+            left: keys(&["A", "Left"]),
+            right: keys(&["D", "Right"]),
+            restart: keys(&["R"]),
+            mute: keys(&["M"]),
+            screenshot: keys(&["F12"]),
+            high_scores: keys(&["H"]),
+            pause: keys(&["Space"]),
+            step: keys(&["Period"]),
+            slower: keys(&["Minus"]),
+            faster: keys(&["Equals"]),
+            confirm: keys(&["Return"]),
+            back: keys(&["Escape"]),
+            p2_up: keys(&["Up"]),
+            p2_down: keys(&["Down"]),
+            p2_left: keys(&["Left"]),
+            p2_right: keys(&["Right"]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Edge length of a board cell in pixels
+    pub block_size: f64,
+    pub colors: Palette,
+    pub particles: ParticleSettings,
+    pub audio: AudioSettings,
+    pub speed: SpeedCurve,
+    /// Spawn weights of the fruit kinds
+    pub fruit: FruitWeights,
+    pub game: GameSettings,
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            colors: Palette::default(),
+            particles: ParticleSettings::default(),
+            audio: AudioSettings::default(),
+            speed: SpeedCurve::default(),
+            fruit: FruitWeights::default(),
+            game: GameSettings::default(),
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// `config.toml` in the user's config directory, if there is one.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rsnake").join("config.toml"))
+    }
+
+    /// Load the config at the default path, or the defaults if there is no
+    /// config file.
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let error = |message: String| ConfigError {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let text = fs::read_to_string(path).map_err(|err: io::Error| error(err.to_string()))?;
+        let config: Config = toml::from_str(&text).map_err(|err| error(err.to_string()))?;
+        config.validate().map_err(error)?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(5.0..=100.0).contains(&self.block_size) {
+            return Err(format!(
+                "block_size must be between 5 and 100, got {}",
+                self.block_size
+            ));
+        }
+
+        for (name, color) in self.colors.entries().iter() {
+            if color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(format!(
+                    "colors.{} components must be between 0.0 and 1.0, got {:?}",
+                    name, color
+                ));
+            }
+        }
+
+        let particles = &self.particles;
+        if particles.count > 1000 {
+            return Err(format!(
+                "particles.count must be at most 1000, got {}",
+                particles.count
+            ));
+        }
+        for (name, value) in [
+            ("lifetime", particles.lifetime),
+            ("speed", particles.speed),
+            ("size", particles.size),
+        ]
+        .iter()
+        {
+            if value.is_nan() || *value <= 0.0 {
+                return Err(format!(
+                    "particles.{} must be positive, got {}",
+                    name, value
+                ));
+            }
+        }
+
+        for (name, volume) in [
+            ("music_volume", self.audio.music_volume),
+            ("sfx_volume", self.audio.sfx_volume),
+        ]
+        .iter()
+        {
+            if !(0.0..=1.0).contains(volume) {
+                return Err(format!(
+                    "audio.{} must be between 0.0 and 1.0, got {}",
+                    name, volume
+                ));
+            }
+        }
+
+        self.speed.validate()?;
+        self.fruit.validate()?;
+
+        if !(1..=8).contains(&self.game.input_buffer) {
+            return Err(format!(
+                "game.input_buffer must be between 1 and 8, got {}",
+                self.game.input_buffer
+            ));
+        }
+        if !(1..=100).contains(&self.game.high_scores) {
+            return Err(format!(
+                "game.high_scores must be between 1 and 100, got {}",
+                self.game.high_scores
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+//! Where the direction of a snake comes from.
+//!
+//! Every tick each snake's controller gets a read-only view of the board
+//! and picks the direction to move in. The keyboard is one controller (the
+//! `InputQueue` filled by key presses), bots are others, and any snake of a
+//! game can be driven by any of them.
+
+use rand::Rng;
+
+use crate::board::Board;
+use crate::fruit::Fruit;
+use crate::input::InputQueue;
+use crate::physics::{Direction, Position};
+use crate::replay::Replay;
+use crate::sim::{self, GameState, GameStatus, Player};
+use crate::snake::Snake;
+
+/// What a controller gets to see of the game on one tick.
+#[derive(Clone, Copy)]
+pub struct BoardView<'a> {
+    board: &'a Board,
+    players: &'a [Player],
+    fruit: Option<&'a Fruit>,
+    tick: u64,
+    // The player the view is for
+    player: usize,
+}
+
+impl<'a> BoardView<'a> {
+    /// The game as seen by `player`.
+    pub fn new<R: Rng>(state: &'a GameState<R>, player: usize) -> Self {
+        Self {
+            board: state.get_board(),
+            players: state.get_players(),
+            fruit: state.get_fruit(),
+            tick: state.get_tick(),
+            player,
+        }
+    }
+
+    /// Size, topology, walls and portals.
+    pub fn get_board(&self) -> &'a Board {
+        self.board
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.board.width, self.board.height)
+    }
+
+    /// Index of the player the view is for.
+    pub fn get_player(&self) -> usize {
+        self.player
+    }
+
+    /// The snake being controlled.
+    pub fn get_snake(&self) -> &'a Snake {
+        self.players[self.player].get_snake()
+    }
+
+    /// The snakes of all players, with the one being controlled at
+    /// `get_player()`. Snakes that lost a versus round stay on the board.
+    pub fn get_players(&self) -> &'a [Player] {
+        self.players
+    }
+
+    pub fn get_fruit(&self) -> Option<&'a Fruit> {
+        self.fruit
+    }
+
+    /// Number of ticks simulated so far.
+    pub fn get_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Whether a snake moving into `pos` dies there: off a walled board, a
+    /// wall, or part of any snake. A tail that moves away on the same tick
+    /// still counts as blocking.
+    pub fn is_blocked(&self, pos: &Position) -> bool {
+        !self.board.contains(pos)
+            || self.board.is_wall(pos)
+            || self
+                .players
+                .iter()
+                .any(|player| player.get_snake().occupies(pos))
+    }
+}
+
+/// Something that steers a snake.
+pub trait Controller: Send {
+    /// The direction to move in on this tick. Returning the snake's current
+    /// direction keeps it going straight; reversing is ignored like it is
+    /// for the keyboard.
+    fn next_direction(&mut self, view: &BoardView) -> Direction;
+
+    /// The game is over: the snake died, filled the board or cleared the
+    /// level. Called once per game, before `reset` if it is restarted.
+    fn end(&mut self) {}
+
+    /// Forget everything about the previous game, called on restart.
+    fn reset(&mut self) {}
+}
+
+/// The keyboard: the turns queued by key presses, one per tick.
+impl Controller for InputQueue {
+    fn next_direction(&mut self, view: &BoardView) -> Direction {
+        self.pop().unwrap_or_else(|| view.get_snake().get_dir())
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// Ask `controller` where `player` goes next, as the input for `sim::step`.
+/// Going straight is no input, so replays of controlled games look the same
+/// as those of keyboard games.
+pub fn decide<R: Rng>(
+    controller: &mut dyn Controller,
+    state: &GameState<R>,
+    player: usize,
+) -> Option<Direction> {
+    let view = BoardView::new(state, player);
+    let dir = controller.next_direction(&view);
+
+    if dir == view.get_snake().get_dir() {
+        None
+    } else {
+        Some(dir)
+    }
+}
+
+/// Play the game of `replay` without a window, one controller per player
+/// in order, until it ends or `max_ticks` ticks have passed. The inputs
+/// are recorded in `replay`, replacing any it had.
+pub fn play(
+    replay: &mut Replay,
+    controllers: &mut [Box<dyn Controller>],
+    max_ticks: u64,
+) -> GameState {
+    let mut state = GameState::new(replay.rules.clone(), replay.seed);
+    replay.inputs.clear();
+
+    while state.get_status() == GameStatus::Playing && state.get_tick() < max_ticks {
+        let inputs: Vec<_> = controllers
+            .iter_mut()
+            .enumerate()
+            .map(|(player, controller)| decide(controller.as_mut(), &state, player))
+            .collect();
+        replay.record(&inputs);
+        sim::step_all(&mut state, &inputs);
+    }
+
+    if state.get_status() != GameStatus::Playing {
+        for controller in controllers.iter_mut() {
+            controller.end();
+        }
+    }
+
+    state
+}
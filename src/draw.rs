@@ -1,19 +1,34 @@
-use crate::colors;
+//! Drawing helpers for the board.
+//!
+//! Everything on the board is drawn in cell units: the caller scales the
+//! renderer by the configured block size (see `board_transform`), so a
+//! cell is always a 1x1 square here.
+
+use crate::colors::{Color, Palette};
+use crate::fruit::{Fruit, FruitKind};
 use crate::physics::{Direction, Position};
+use crate::render::{Renderer, Transform};
 use noise::{NoiseFn, Perlin};
-use piston_window::types::Color;
-use piston_window::{rectangle, Context, G2d};
 use rand::Rng;
 
-pub const BLOCK_SIZE: f64 = 25.0;
+/// Size of an eye and its distance from the edge of the head, in cells
+const EYE_SIZE: f64 = 0.2;
+
+const STEM_COLOR: Color = [0.4, 0.25, 0.1, 1.0];
+const LEAF_COLOR: Color = [0.2, 0.7, 0.2, 1.0];
+const SHINE_COLOR: Color = [1.0, 1.0, 1.0, 0.8];
+const SPOT_COLOR: Color = [0.2, 0.0, 0.25, 1.0];
+
+/// Portal animation speeds in radians per second
+const PORTAL_PULSE_SPEED: f64 = 4.0;
+const PORTAL_SPIN_SPEED: f64 = 5.0;
 
 pub struct Background {
     colors: Vec<Vec<Color>>,
 }
 
 impl Background {
-    pub fn new(width: u32, height: u32) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new(width: u32, height: u32, palette: &Palette, rng: &mut impl Rng) -> Self {
         let seed: u32 = rng.gen();
         let perlin = Perlin::new(seed);
 
@@ -28,13 +43,11 @@ impl Background {
                 let t = (noise_val + 1.0) / 2.0;
 
                 // Interpolate between green and brown
+                let (light, dark) = (palette.grid_light, palette.grid_dark);
                 let color = [
-                    colors::GRID_LIGHT[0]
-                        + (colors::GRID_DARK[0] - colors::GRID_LIGHT[0]) * t as f32,
-                    colors::GRID_LIGHT[1]
-                        + (colors::GRID_DARK[1] - colors::GRID_LIGHT[1]) * t as f32,
-                    colors::GRID_LIGHT[2]
-                        + (colors::GRID_DARK[2] - colors::GRID_LIGHT[2]) * t as f32,
+                    light[0] + (dark[0] - light[0]) * t as f32,
+                    light[1] + (dark[1] - light[1]) * t as f32,
+                    light[2] + (dark[2] - light[2]) * t as f32,
                     1.0,
                 ];
                 row.push(color);
@@ -45,89 +58,103 @@ impl Background {
         Background { colors }
     }
 
-    pub fn draw(&self, ctx: &Context, g: &mut G2d) {
+    pub fn draw(&self, r: &mut dyn Renderer) {
         for (x, row) in self.colors.iter().enumerate() {
             for (y, color) in row.iter().enumerate() {
-                rectangle(
-                    *color,
-                    [
-                        x as f64 * BLOCK_SIZE,
-                        y as f64 * BLOCK_SIZE,
-                        BLOCK_SIZE,
-                        BLOCK_SIZE,
-                    ],
-                    ctx.transform,
-                    g,
-                );
+                r.fill_rect(*color, [x as f64, y as f64, 1.0, 1.0]);
             }
         }
     }
 }
 
-pub fn draw_block(ctx: &Context, g: &mut G2d, c: Color, pos: &Position) {
-    rectangle(
-        c,
-        [
-            pos.x as f64 * BLOCK_SIZE,
-            pos.y as f64 * BLOCK_SIZE,
-            BLOCK_SIZE,
-            BLOCK_SIZE,
-        ],
-        ctx.transform,
-        g,
-    );
+/// Scale `transform` so that one unit is one board cell.
+pub fn board_transform(transform: Transform, block_size: f64) -> Transform {
+    transform.scale(block_size, block_size)
 }
 
-pub fn draw_snake_head(ctx: &Context, g: &mut G2d, c: Color, pos: &Position, dir: &Direction) {
-    draw_block(ctx, g, c, pos);
+pub fn draw_block(r: &mut dyn Renderer, c: Color, pos: &Position) {
+    r.fill_rect(c, [pos.x as f64, pos.y as f64, 1.0, 1.0]);
+}
 
-    fn draw_eye(ctx: &Context, g: &mut G2d, x: f64, y: f64) {
-        rectangle(colors::BACKGROUND, [x, y, 5.0, 5.0], ctx.transform, g);
+pub fn draw_snake_head(
+    r: &mut dyn Renderer,
+    c: Color,
+    eye_color: Color,
+    pos: &Position,
+    dir: &Direction,
+) {
+    draw_block(r, c, pos);
+
+    let (near, far) = (EYE_SIZE, 1.0 - 2.0 * EYE_SIZE);
+    let eyes = match dir {
+        Direction::Up => [(near, near), (far, near)],
+        Direction::Right => [(far, near), (far, far)],
+        Direction::Down => [(near, far), (far, far)],
+        Direction::Left => [(near, near), (near, far)],
+    };
+
+    let (x, y) = (pos.x as f64, pos.y as f64);
+    for (dx, dy) in eyes.iter() {
+        r.fill_rect(eye_color, [x + dx, y + dy, EYE_SIZE, EYE_SIZE]);
     }
+}
+
+/// Draw a portal as a ring around a pulsing hole with a spark circling it.
+/// `time` is in seconds and drives the animation.
+pub fn draw_portal(r: &mut dyn Renderer, c: Color, hole: Color, pos: &Position, time: f64) {
+    let (cx, cy) = (pos.x as f64 + 0.5, pos.y as f64 + 0.5);
+
+    r.fill_ellipse(c, [cx - 0.5, cy - 0.5, 1.0, 1.0]);
+
+    let radius = 0.3 + 0.08 * (time * PORTAL_PULSE_SPEED).sin();
+    r.fill_ellipse(hole, [cx - radius, cy - radius, radius * 2.0, radius * 2.0]);
+
+    let angle = time * PORTAL_SPIN_SPEED;
+    let (sx, sy) = (cx + 0.3 * angle.cos(), cy + 0.3 * angle.sin());
+    r.fill_rect(SHINE_COLOR, [sx - 0.08, sy - 0.08, 0.16, 0.16]);
+}
 
-    let (x, y) = (
-        blocks_in_pixels(pos.x as u32) as f64,
-        blocks_in_pixels(pos.y as u32) as f64,
-    );
+/// Draw a round fruit with a stem in color `c`, plus a detail that tells
+/// the kinds apart: a leaf, a shine, spots or a lightning bolt.
+pub fn draw_fruit(r: &mut dyn Renderer, c: Color, fruit: &Fruit) {
+    let (x, y) = (fruit.pos.x as f64, fruit.pos.y as f64);
 
-    let block = blocks_in_pixels(1) as f64;
+    r.fill_ellipse(c, [x + 0.1, y + 0.15, 0.8, 0.8]);
+    r.fill_rect(STEM_COLOR, [x + 0.45, y, 0.1, 0.25]);
 
-    match dir {
-        Direction::Up => {
-            draw_eye(ctx, g, x + 5.0, y + 5.0);
-            draw_eye(ctx, g, x + block - 10.0, y + 5.0);
+    match fruit.kind {
+        FruitKind::Normal => {
+            r.fill_ellipse(LEAF_COLOR, [x + 0.55, y + 0.02, 0.3, 0.15]);
         }
-        Direction::Right => {
-            draw_eye(ctx, g, x + block - 10.0, y + 5.0);
-            draw_eye(ctx, g, x + block - 10.0, y + block - 10.0);
+        FruitKind::Golden => {
+            r.fill_ellipse(SHINE_COLOR, [x + 0.25, y + 0.3, 0.2, 0.2]);
         }
-        Direction::Down => {
-            draw_eye(ctx, g, x + 5.0, y + block - 10.0);
-            draw_eye(ctx, g, x + block - 10.0, y + block - 10.0);
+        FruitKind::Poison => {
+            r.fill_ellipse(SPOT_COLOR, [x + 0.25, y + 0.4, 0.2, 0.2]);
+            r.fill_ellipse(SPOT_COLOR, [x + 0.55, y + 0.6, 0.15, 0.15]);
         }
-        Direction::Left => {
-            draw_eye(ctx, g, x + 5.0, y + 5.0);
-            draw_eye(ctx, g, x + 5.0, y + block - 10.0);
+        FruitKind::Speed => {
+            // Two triangles, renderers only fill convex polygons
+            let upper = [
+                [x + 0.6, y + 0.25],
+                [x + 0.3, y + 0.6],
+                [x + 0.55, y + 0.55],
+            ];
+            let lower = [
+                [x + 0.45, y + 0.5],
+                [x + 0.7, y + 0.45],
+                [x + 0.4, y + 0.85],
+            ];
+            r.fill_polygon(SHINE_COLOR, &upper);
+            r.fill_polygon(SHINE_COLOR, &lower);
         }
     }
 }
 
-pub fn draw_fruit(_ctx: &Context, _g: &mut G2d, _c: Color, _pos: &Position) {}
-
-pub fn draw_overlay(ctx: &Context, g: &mut G2d, c: Color, size: (u32, u32)) {
-    rectangle(
-        c,
-        [
-            0.0,
-            0.0,
-            blocks_in_pixels(size.0) as f64,
-            blocks_in_pixels(size.1) as f64,
-        ],
-        ctx.transform,
-        g,
-    );
+pub fn draw_overlay(r: &mut dyn Renderer, c: Color, size: (u32, u32)) {
+    r.fill_rect(c, [0.0, 0.0, size.0 as f64, size.1 as f64]);
 }
 
-pub fn blocks_in_pixels(n: u32) -> u32 {
-    n * BLOCK_SIZE as u32
+pub fn blocks_in_pixels(n: u32, block_size: f64) -> u32 {
+    (n as f64 * block_size) as u32
 }
@@ -0,0 +1,396 @@
+//! The game as a reinforcement learning environment, in the style of
+//! OpenAI Gym: `reset(seed)` starts an episode, `step(action)` plays one
+//! tick and returns `(observation, reward, done, info)`.
+//!
+//! It runs on `sim` alone, without a window, so agents can be trained as
+//! fast as the CPU allows. The agent steers the first snake; in a game
+//! with more players the others keep going straight.
+//!
+//! Observations come in two encodings:
+//!
+//! - `Grid`: four `height x width` planes of 0.0 or 1.0, in the order head,
+//!   body (every snake but the agent's head), fruit and walls (the
+//!   obstacles of a level, the board edges aren't marked). Shape
+//!   `[4, height, width]`.
+//! - `Rays`: for 8 directions starting at the snake's heading and going
+//!   clockwise, `1 / distance` to the nearest wall, body segment and fruit
+//!   along that line (0.0 if there is none), followed by the heading one
+//!   hot encoded as up, right, down, left. Shape `[28]`.
+
+use serde::Deserialize;
+
+use crate::fruit::FruitKind;
+use crate::physics::{Direction, Position};
+use crate::sim::{self, Event, GameState, GameStatus, Rules};
+
+/// The discrete action space, index `i` is action `i`.
+pub const ACTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+];
+const GRID_PLANES: usize = 4;
+const RAY_COUNT: usize = 8;
+const RAY_FEATURES: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Grid,
+    Rays,
+}
+
+/// Reward for every outcome of a tick, added up.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rewards {
+    /// For a fruit worth 10 points, scaled by the points of the fruit (so
+    /// five times as much for golden fruit and nothing for poison)
+    pub fruit: f32,
+    /// For dying
+    pub death: f32,
+    /// For filling the whole board
+    pub win: f32,
+    /// For every tick, usually a small penalty against going in circles
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            fruit: 1.0,
+            death: -1.0,
+            win: 10.0,
+            step: -0.01,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvConfig {
+    pub rules: Rules,
+    pub encoding: Encoding,
+    pub rewards: Rewards,
+    /// Ticks after which an episode is cut off, `None` to play until the
+    /// snake dies or wins
+    pub max_steps: Option<u64>,
+}
+
+impl EnvConfig {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            rules,
+            encoding: Encoding::Grid,
+            rewards: Rewards::default(),
+            max_steps: None,
+        }
+    }
+}
+
+/// A flat observation with the shape it has as a tensor, row-major.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Everything about a step that isn't part of the observation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    pub score: u32,
+    /// Length of the snake including its head
+    pub length: usize,
+    pub tick: u64,
+    pub fruits_eaten: u32,
+    pub status: GameStatus,
+    /// The episode was cut off by `max_steps` rather than ended by the game
+    pub truncated: bool,
+    pub events: Vec<Event>,
+}
+
+pub struct Env {
+    config: EnvConfig,
+    state: GameState,
+}
+
+impl Env {
+    /// An environment with an episode started from seed 0.
+    pub fn new(config: EnvConfig) -> Self {
+        let state = GameState::new(config.rules.clone(), 0);
+        Self { config, state }
+    }
+
+    /// Start a new episode.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.state = GameState::new(self.config.rules.clone(), seed);
+        self.observe()
+    }
+
+    /// Play one tick with the snake turning to `action`. Turning around is
+    /// ignored like it is for the keyboard. Stepping a finished episode
+    /// changes nothing and gives no reward.
+    pub fn step(&mut self, action: Direction) -> (Observation, f32, bool, Info) {
+        let finished = self.is_done();
+        let mut events = Vec::new();
+        let mut reward = 0.0;
+
+        if !finished {
+            let mut inputs = vec![None; self.state.get_players().len()];
+            inputs[0] = Some(action);
+            events = sim::step_all(&mut self.state, &inputs);
+            reward = self.reward(&events);
+        }
+
+        let info = Info {
+            score: self.state.get_score(),
+            length: self.state.get_snake().get_body_len(),
+            tick: self.state.get_tick(),
+            fruits_eaten: self.state.get_fruits_eaten(),
+            status: self.state.get_status(),
+            truncated: self.is_truncated(),
+            events,
+        };
+
+        (self.observe(), reward, self.is_done(), info)
+    }
+
+    /// Shape of the observations `reset` and `step` return.
+    pub fn observation_shape(&self) -> Vec<usize> {
+        let (width, height) = self.state.get_size();
+        match self.config.encoding {
+            Encoding::Grid => vec![GRID_PLANES, height as usize, width as usize],
+            Encoding::Rays => vec![RAY_COUNT * RAY_FEATURES + ACTIONS.len()],
+        }
+    }
+
+    /// The game as it is right now.
+    pub fn get_state(&self) -> &GameState {
+        &self.state
+    }
+
+    fn is_truncated(&self) -> bool {
+        self.state.get_status() == GameStatus::Playing
+            && self
+                .config
+                .max_steps
+                .is_some_and(|max| self.state.get_tick() >= max)
+    }
+
+    fn is_done(&self) -> bool {
+        self.state.get_status() != GameStatus::Playing || self.is_truncated()
+    }
+
+    fn reward(&self, events: &[Event]) -> f32 {
+        let rewards = &self.config.rewards;
+        let points = FruitKind::Normal.points() as f32;
+
+        events
+            .iter()
+            .fold(rewards.step, |reward, event| match event {
+                Event::FruitEaten(fruit) => {
+                    reward + rewards.fruit * fruit.kind.points() as f32 / points
+                }
+                Event::GameOver => reward + rewards.death,
+                Event::Won => reward + rewards.win,
+                _ => reward,
+            })
+    }
+
+    fn observe(&self) -> Observation {
+        let data = match self.config.encoding {
+            Encoding::Grid => self.grid(),
+            Encoding::Rays => self.rays(),
+        };
+
+        Observation {
+            shape: self.observation_shape(),
+            data,
+        }
+    }
+
+    fn grid(&self) -> Vec<f32> {
+        let board = self.state.get_board();
+        let (width, height) = (board.width as usize, board.height as usize);
+        let plane = width * height;
+        let mut data = vec![0.0; GRID_PLANES * plane];
+        let mut set = |layer: usize, pos: &Position| {
+            if board.contains(pos) {
+                data[layer * plane + pos.y as usize * width + pos.x as usize] = 1.0;
+            }
+        };
+
+        let snake = self.state.get_snake();
+        set(0, snake.get_head_pos());
+        for (i, player) in self.state.get_players().iter().enumerate() {
+            // Other snakes' heads are as deadly as their bodies
+            let skip = if i == 0 { 1 } else { 0 };
+            for cell in player.get_snake().cells().skip(skip) {
+                set(1, cell);
+            }
+        }
+        if let Some(fruit) = self.state.get_fruit() {
+            set(2, &fruit.pos);
+        }
+        for wall in board.walls.iter() {
+            set(3, wall);
+        }
+
+        data
+    }
+
+    fn rays(&self) -> Vec<f32> {
+        let board = self.state.get_board();
+        let snake = self.state.get_snake();
+        let head = snake.get_head_pos();
+        let fruit = self.state.get_fruit().map(|fruit| &fruit.pos);
+        let heading = ACTIONS
+            .iter()
+            .position(|dir| *dir == snake.get_dir())
+            .unwrap_or(0);
+        // On a wrapping board a ray ends when it would come back around
+        let max_distance = board.width.max(board.height) as usize;
+
+        let mut data = Vec::with_capacity(RAY_COUNT * RAY_FEATURES + ACTIONS.len());
+        for ray in 0..RAY_COUNT {
+            // Every other ray is diagonal, between the two directions
+            // around it
+            let turn = heading * 2 + ray;
+            let first = ACTIONS[turn / 2 % ACTIONS.len()];
+            let second = ACTIONS[turn.div_ceil(2) % ACTIONS.len()];
+
+            let (mut wall, mut body, mut food) = (0.0, 0.0, 0.0);
+            let mut pos = head.clone();
+            for distance in 1..=max_distance {
+                pos = board.neighbor(&pos, first);
+                if second != first {
+                    pos = board.neighbor(&pos, second);
+                }
+                let inverse = 1.0 / distance as f32;
+
+                if !board.contains(&pos) || board.is_wall(&pos) {
+                    wall = inverse;
+                    break;
+                }
+                if body == 0.0
+                    && self
+                        .state
+                        .get_players()
+                        .iter()
+                        .any(|player| player.get_snake().occupies(&pos))
+                {
+                    body = inverse;
+                }
+                if food == 0.0 && fruit == Some(&pos) {
+                    food = inverse;
+                }
+            }
+            data.extend([wall, body, food].iter());
+        }
+        data.extend(
+            ACTIONS
+                .iter()
+                .map(|dir| (*dir == snake.get_dir()) as u8 as f32),
+        );
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, Topology};
+
+    /// A snake of length 3 heading right from the middle of a walled board.
+    fn config(width: u32, height: u32) -> EnvConfig {
+        let mut rules = Rules::new(Board::new(width, height, Topology::Walled));
+        rules.start = Some((
+            Position {
+                x: width as i32 / 2,
+                y: height as i32 / 2,
+            },
+            Direction::Right,
+        ));
+        EnvConfig::new(rules)
+    }
+
+    #[test]
+    fn same_seed_same_episode() {
+        let actions = [Direction::Up, Direction::Left, Direction::Down];
+        let episode = |seed| {
+            let mut env = Env::new(config(12, 8));
+            let mut steps = vec![(env.reset(seed), 0.0, false)];
+            for tick in 0..40 {
+                let (observation, reward, done, _) = env.step(actions[tick / 3 % actions.len()]);
+                steps.push((observation, reward, done));
+            }
+            steps
+        };
+
+        assert_eq!(episode(3), episode(3));
+    }
+
+    #[test]
+    fn dying_ends_the_episode() {
+        let mut env = Env::new(config(12, 8));
+        env.reset(1);
+        let rewards = env.config.rewards.clone();
+
+        // Five cells to the right edge, the sixth step is off the board
+        let (reward, done, info) = loop {
+            let (_, reward, done, info) = env.step(Direction::Right);
+            if done {
+                break (reward, done, info);
+            }
+        };
+        assert!(done);
+        assert_eq!(info.status, GameStatus::GameOver);
+        assert!(!info.truncated);
+        assert_eq!(reward, rewards.step + rewards.death);
+
+        let (_, reward, done, info) = env.step(Direction::Up);
+        assert_eq!((reward, done), (0.0, true));
+        assert!(info.events.is_empty());
+    }
+
+    #[test]
+    fn episodes_are_cut_off_at_max_steps() {
+        let mut config = config(12, 8);
+        config.max_steps = Some(2);
+        let mut env = Env::new(config);
+        env.reset(1);
+
+        assert!(!env.step(Direction::Up).2);
+        let (_, reward, done, info) = env.step(Direction::Right);
+        assert!(done && info.truncated);
+        assert_eq!(info.status, GameStatus::Playing);
+        assert!(reward < 0.0);
+    }
+
+    #[test]
+    fn observation_shapes() {
+        let mut env = Env::new(config(12, 8));
+        let grid = env.reset(1);
+        assert_eq!(grid.shape, vec![4, 8, 12]);
+        assert_eq!(grid.data.len(), 4 * 8 * 12);
+        // One head, the rest of the snake and one fruit
+        let plane = |layer: usize| &grid.data[layer * 96..(layer + 1) * 96];
+        assert_eq!(plane(0).iter().sum::<f32>(), 1.0);
+        assert_eq!(plane(0)[4 * 12 + 6], 1.0);
+        assert_eq!(plane(1).iter().sum::<f32>(), 2.0);
+        assert_eq!(plane(2).iter().sum::<f32>(), 1.0);
+        assert_eq!(plane(3).iter().sum::<f32>(), 0.0);
+
+        let mut config = config(12, 8);
+        config.encoding = Encoding::Rays;
+        let mut env = Env::new(config);
+        let rays = env.reset(1);
+        assert_eq!(rays.shape, vec![28]);
+        assert_eq!(rays.data.len(), 28);
+        // Heading right
+        assert_eq!(&rays.data[24..], &[0.0, 1.0, 0.0, 0.0]);
+        // The first ray looks ahead, off the board six cells on
+        assert_eq!(rays.data[0], 1.0 / 6.0);
+    }
+}
@@ -0,0 +1,416 @@
+//! Bots running as a separate process, written in any language.
+//!
+//! Every tick the game writes the board as one line of JSON to the bot's
+//! stdin and reads its move as one line of JSON from its stdout:
+//!
+//! ```text
+//! > {"version":1,"type":"move","tick":12,"you":0,
+//!    "board":{"width":25,"height":25,"wrap":false,"walls":[],"portals":[]},
+//!    "snakes":[{"body":[{"x":4,"y":7},{"x":4,"y":6}],"direction":"down","alive":true,"score":30}],
+//!    "fruit":{"x":9,"y":2,"kind":"golden","ticks_left":41}}
+//! < {"move":"left"}
+//! ```
+//!
+//! `you` is the index of the bot's snake in `snakes`, every body starts
+//! with the head. `fruit` is `null` once the board is full and `ticks_left`
+//! is `null` for fruit that stays until eaten. `portals` lists pairs of
+//! cells, `[{"x":1,"y":1},{"x":8,"y":8}]`. When the game ends, or is
+//! restarted before that, the bot gets `{"version":1,"type":"end"}`, and
+//! its stdin is closed when the game is over for good.
+//!
+//! A move is one of `up`, `down`, `left` and `right`. If the bot doesn't
+//! answer within the timeout, or answers with anything else, it either
+//! keeps going straight or forfeits: it gets no more requests and its snake
+//! goes straight until it hits something. A bot that stops reading its
+//! stdin always forfeits. `version` only changes when the format changes in
+//! a way existing bots would misread.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+use crate::board::Topology;
+use crate::controller::{BoardView, Controller};
+use crate::physics::{Direction, Position};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_MOVE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Time for the first move, which includes starting up the bot
+const FIRST_MOVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Lines waiting for the bot to read them, past that it counts as stuck
+const WRITE_QUEUE: usize = 4;
+
+/// What happens when a bot is too slow or makes no sense.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OnTimeout {
+    /// Keep going straight and ask again on the next tick
+    Straight,
+    /// Stop asking the bot, its snake goes straight from then on
+    Forfeit,
+}
+
+impl FromStr for OnTimeout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "straight" => Ok(OnTimeout::Straight),
+            "forfeit" => Ok(OnTimeout::Forfeit),
+            _ => Err(format!(
+                "unknown timeout policy '{}', expected straight or forfeit",
+                s
+            )),
+        }
+    }
+}
+
+fn direction_name(dir: Direction) -> &'static str {
+    match dir {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+#[derive(Serialize)]
+struct Cell {
+    x: i32,
+    y: i32,
+}
+
+impl From<&Position> for Cell {
+    fn from(pos: &Position) -> Self {
+        Self { x: pos.x, y: pos.y }
+    }
+}
+
+#[derive(Serialize)]
+struct BoardMessage {
+    width: u32,
+    height: u32,
+    wrap: bool,
+    walls: Vec<Cell>,
+    portals: Vec<[Cell; 2]>,
+}
+
+#[derive(Serialize)]
+struct SnakeMessage {
+    body: Vec<Cell>,
+    direction: &'static str,
+    alive: bool,
+    score: u32,
+}
+
+#[derive(Serialize)]
+struct FruitMessage {
+    x: i32,
+    y: i32,
+    kind: &'static str,
+    ticks_left: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct MoveRequest {
+    version: u32,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    tick: u64,
+    you: usize,
+    board: BoardMessage,
+    snakes: Vec<SnakeMessage>,
+    fruit: Option<FruitMessage>,
+}
+
+#[derive(Serialize)]
+struct EndMessage {
+    version: u32,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[derive(Deserialize)]
+struct MoveReply {
+    #[serde(rename = "move")]
+    direction: String,
+}
+
+impl MoveRequest {
+    fn new(view: &BoardView) -> Self {
+        let board = view.get_board();
+        let mut walls: Vec<&Position> = board.walls.iter().collect();
+        walls.sort_by_key(|pos| (pos.y, pos.x));
+        let mut portals: Vec<(&Position, &Position)> = board
+            .portals
+            .iter()
+            .filter(|(a, b)| (a.y, a.x) < (b.y, b.x))
+            .collect();
+        portals.sort_by_key(|(a, _)| (a.y, a.x));
+
+        Self {
+            version: PROTOCOL_VERSION,
+            kind: "move",
+            tick: view.get_tick(),
+            you: view.get_player(),
+            board: BoardMessage {
+                width: board.width,
+                height: board.height,
+                wrap: board.topology == Topology::Wrap,
+                walls: walls.into_iter().map(Cell::from).collect(),
+                portals: portals
+                    .into_iter()
+                    .map(|(a, b)| [Cell::from(a), Cell::from(b)])
+                    .collect(),
+            },
+            snakes: view
+                .get_players()
+                .iter()
+                .map(|player| SnakeMessage {
+                    body: player.get_snake().cells().map(Cell::from).collect(),
+                    direction: direction_name(player.get_snake().get_dir()),
+                    alive: player.is_alive(),
+                    score: player.get_score(),
+                })
+                .collect(),
+            fruit: view.get_fruit().map(|fruit| FruitMessage {
+                x: fruit.pos.x,
+                y: fruit.pos.y,
+                kind: fruit.kind.name(),
+                ticks_left: fruit.ticks_left,
+            }),
+        }
+    }
+}
+
+fn parse_move(line: &str) -> Result<Direction, String> {
+    let reply: MoveReply =
+        serde_json::from_str(line).map_err(|err| format!("invalid reply '{}': {}", line, err))?;
+
+    match reply.direction.as_str() {
+        "up" => Ok(Direction::Up),
+        "down" => Ok(Direction::Down),
+        "left" => Ok(Direction::Left),
+        "right" => Ok(Direction::Right),
+        other => Err(format!("unknown move '{}'", other)),
+    }
+}
+
+pub struct ExternalBot {
+    child: Child,
+    // Lines for the bot's stdin, written on a separate thread so a bot that
+    // doesn't read can't block the game. `None` once the bot forfeited or
+    // its stdin broke
+    writer: Option<SyncSender<String>>,
+    // Lines the bot printed, read on a separate thread so a silent bot
+    // can't block the game
+    lines: Receiver<String>,
+    timeout: Duration,
+    on_timeout: OnTimeout,
+    moves: u64,
+    // The bot got the end message of the current game
+    ended: bool,
+}
+
+impl ExternalBot {
+    /// Start the bot. `command` is the program and its arguments separated
+    /// by spaces, there is no shell quoting.
+    pub fn spawn(command: &str, timeout: Duration, on_timeout: OnTimeout) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+
+        let mut writer = None;
+        if let Some(mut stdin) = stdin {
+            let (sender, queue) = mpsc::sync_channel::<String>(WRITE_QUEUE);
+            thread::spawn(move || {
+                for line in queue {
+                    if writeln!(stdin, "{}", line)
+                        .and_then(|_| stdin.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            writer = Some(sender);
+        }
+
+        let (sender, lines) = mpsc::channel();
+        if let Some(stdout) = stdout {
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let sent = line.map(|line| sender.send(line).is_ok());
+                    if !matches!(sent, Ok(true)) {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
+            child,
+            writer,
+            lines,
+            timeout,
+            on_timeout,
+            moves: 0,
+            ended: false,
+        })
+    }
+
+    /// Queue `message` for the bot. A bot that is too far behind reading
+    /// its input, or whose input broke, is gone from then on.
+    fn send(&mut self, message: &impl Serialize) -> io::Result<()> {
+        let writer = match &self.writer {
+            Some(writer) => writer,
+            None => return Err(io::Error::new(io::ErrorKind::BrokenPipe, "bot is gone")),
+        };
+        let line = serde_json::to_string(message).map_err(io::Error::other)?;
+
+        writer.try_send(line).map_err(|err| {
+            self.writer = None;
+            match err {
+                TrySendError::Full(_) => {
+                    io::Error::new(io::ErrorKind::WouldBlock, "bot is not reading its input")
+                }
+                TrySendError::Disconnected(_) => {
+                    io::Error::new(io::ErrorKind::BrokenPipe, "bot closed its input")
+                }
+            }
+        })
+    }
+
+    fn request_move(&mut self, view: &BoardView) -> Result<Direction, String> {
+        // A reply that came in after its timeout belongs to an old tick
+        while self.lines.try_recv().is_ok() {}
+
+        self.send(&MoveRequest::new(view))
+            .map_err(|err| format!("could not send the board: {}", err))?;
+
+        let timeout = if self.moves == 0 {
+            FIRST_MOVE_TIMEOUT.max(self.timeout)
+        } else {
+            self.timeout
+        };
+        self.moves += 1;
+
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => parse_move(&line),
+            Err(RecvTimeoutError::Timeout) => {
+                Err(format!("no move within {} ms", timeout.as_millis()))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err("bot exited".to_string()),
+        }
+    }
+}
+
+impl Controller for ExternalBot {
+    fn next_direction(&mut self, view: &BoardView) -> Direction {
+        let straight = view.get_snake().get_dir();
+        if self.writer.is_none() {
+            return straight;
+        }
+
+        match self.request_move(view) {
+            Ok(dir) => dir,
+            Err(err) => {
+                eprintln!("Bot of player {}: {}", view.get_player() + 1, err);
+                if self.on_timeout == OnTimeout::Forfeit || self.writer.is_none() {
+                    eprintln!("Bot of player {} forfeits", view.get_player() + 1);
+                    self.writer = None;
+                }
+                straight
+            }
+        }
+    }
+
+    fn end(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        let end = EndMessage {
+            version: PROTOCOL_VERSION,
+            kind: "end",
+        };
+        // A bot that is gone stays gone
+        let _ = self.send(&end);
+    }
+
+    fn reset(&mut self) {
+        // A game restarted before it ended ends now
+        self.end();
+        self.ended = false;
+        self.moves = 0;
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        // Closing stdin, once the writer is done with it, tells the bot the
+        // game is over. Give it a moment to exit on its own
+        self.writer = None;
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ends_every_game_once() {
+        // `cat` sends back everything it gets
+        let mut bot = ExternalBot::spawn("cat", DEFAULT_MOVE_TIMEOUT, OnTimeout::Straight).unwrap();
+        let end = r#"{"version":1,"type":"end"}"#;
+        let next_line = |bot: &ExternalBot| bot.lines.recv_timeout(FIRST_MOVE_TIMEOUT).ok();
+
+        bot.end();
+        bot.end();
+        bot.reset();
+        assert_eq!(next_line(&bot).as_deref(), Some(end));
+
+        // Restarting a game that is still going ends it as well
+        bot.reset();
+        assert_eq!(next_line(&bot).as_deref(), Some(end));
+        assert!(bot.lines.try_recv().is_err());
+    }
+
+    #[test]
+    fn a_bot_that_does_not_read_forfeits() {
+        let mut bot =
+            ExternalBot::spawn("sleep 10", DEFAULT_MOVE_TIMEOUT, OnTimeout::Straight).unwrap();
+        // More than fits in the pipe, the writer gets stuck on the first
+        let line = "x".repeat(1 << 20);
+
+        let sent = (0..WRITE_QUEUE + 2)
+            .map(|_| bot.send(&line))
+            .collect::<Vec<_>>();
+        let err = sent
+            .into_iter()
+            .find_map(Result::err)
+            .expect("a full queue");
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(bot.writer.is_none());
+    }
+}
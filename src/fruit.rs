@@ -0,0 +1,232 @@
+//! The different kinds of fruit and how often each of them spawns.
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::physics::Position;
+
+/// Ticks a golden, poison or speed fruit stays on the board before it is
+/// replaced by a new one. Normal fruit stays until it is eaten.
+pub const SPECIAL_FRUIT_TICKS: u32 = 60;
+/// Segments a poison fruit takes off the tail
+pub const POISON_SHRINK: usize = 2;
+/// Ticks a speed fruit keeps the snake fast
+pub const SPEED_BOOST_TICKS: u32 = 40;
+/// Tick rate multiplier while a speed fruit is in effect
+pub const SPEED_BOOST_FACTOR: f64 = 1.5;
+/// Largest accepted spawn weight, so the sum of all weights can't overflow
+const MAX_WEIGHT: u32 = 1_000_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FruitKind {
+    Normal,
+    /// Worth five normal fruits
+    Golden,
+    /// Shrinks the snake instead of growing it
+    Poison,
+    /// Makes the snake faster for a while
+    Speed,
+}
+
+impl FruitKind {
+    pub const ALL: [FruitKind; 4] = [
+        FruitKind::Normal,
+        FruitKind::Golden,
+        FruitKind::Poison,
+        FruitKind::Speed,
+    ];
+
+    /// Points for eating this kind of fruit.
+    pub fn points(self) -> u32 {
+        match self {
+            FruitKind::Normal | FruitKind::Speed => 10,
+            FruitKind::Golden => 50,
+            FruitKind::Poison => 0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FruitKind::Normal => "normal",
+            FruitKind::Golden => "golden",
+            FruitKind::Poison => "poison",
+            FruitKind::Speed => "speed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fruit {
+    pub pos: Position,
+    pub kind: FruitKind,
+    /// Ticks until the fruit disappears, `None` if it stays until eaten
+    pub ticks_left: Option<u32>,
+}
+
+impl Fruit {
+    pub fn new(pos: Position, kind: FruitKind) -> Self {
+        let ticks_left = match kind {
+            FruitKind::Normal => None,
+            _ => Some(SPECIAL_FRUIT_TICKS),
+        };
+
+        Self {
+            pos,
+            kind,
+            ticks_left,
+        }
+    }
+}
+
+/// Relative chance of each kind being picked when a new fruit spawns. Can be
+/// set in the `[fruit]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FruitWeights {
+    pub normal: u32,
+    pub golden: u32,
+    pub poison: u32,
+    pub speed: u32,
+}
+
+impl Default for FruitWeights {
+    fn default() -> Self {
+        Self {
+            normal: 80,
+            golden: 8,
+            poison: 6,
+            speed: 6,
+        }
+    }
+}
+
+impl FruitWeights {
+    /// Only normal fruit, like the original game.
+    pub fn classic() -> Self {
+        Self {
+            normal: 1,
+            golden: 0,
+            poison: 0,
+            speed: 0,
+        }
+    }
+
+    pub fn get(&self, kind: FruitKind) -> u32 {
+        match kind {
+            FruitKind::Normal => self.normal,
+            FruitKind::Golden => self.golden,
+            FruitKind::Poison => self.poison,
+            FruitKind::Speed => self.speed,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        FruitKind::ALL.iter().map(|kind| self.get(*kind)).sum()
+    }
+
+    /// Pick a kind at random according to the weights. If only one kind
+    /// can spawn, the RNG is left alone, so games with classic weights play
+    /// out the same as before there were fruit kinds.
+    pub fn pick(&self, rng: &mut impl Rng) -> FruitKind {
+        let mut possible = FruitKind::ALL.iter().filter(|kind| self.get(**kind) > 0);
+        match (possible.next(), possible.next()) {
+            (None, _) => return FruitKind::Normal,
+            (Some(kind), None) => return *kind,
+            _ => {}
+        }
+
+        let mut roll = rng.gen_range(0..self.total());
+        for kind in FruitKind::ALL.iter() {
+            let weight = self.get(*kind);
+            if roll < weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+
+        FruitKind::Normal
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for kind in FruitKind::ALL.iter() {
+            if self.get(*kind) > MAX_WEIGHT {
+                return Err(format!(
+                    "fruit.{} must be at most {}, got {}",
+                    kind.name(),
+                    MAX_WEIGHT,
+                    self.get(*kind)
+                ));
+            }
+        }
+
+        if self.total() == 0 {
+            return Err("fruit weights must not all be 0".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn picks_by_weight() {
+        let weights = FruitWeights {
+            normal: 1,
+            golden: 0,
+            poison: 3,
+            speed: 0,
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let picks: Vec<FruitKind> = (0..4000).map(|_| weights.pick(&mut rng)).collect();
+        let count = |kind| picks.iter().filter(|pick| **pick == kind).count();
+
+        assert_eq!(count(FruitKind::Normal) + count(FruitKind::Poison), 4000);
+        assert!((900..1100).contains(&count(FruitKind::Normal)));
+    }
+
+    #[test]
+    fn a_single_kind_leaves_the_rng_alone() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let untouched = rng.clone();
+
+        assert_eq!(FruitWeights::classic().pick(&mut rng), FruitKind::Normal);
+        let golden = FruitWeights {
+            golden: 5,
+            ..FruitWeights::classic()
+        };
+        assert_eq!(golden.get(FruitKind::Golden), 5);
+        assert_eq!(rng, untouched);
+    }
+
+    #[test]
+    fn validates_weights() {
+        assert!(FruitWeights::default().validate().is_ok());
+        let none = FruitWeights {
+            normal: 0,
+            ..FruitWeights::classic()
+        };
+        assert!(none.validate().is_err());
+        let huge = FruitWeights {
+            golden: MAX_WEIGHT + 1,
+            ..FruitWeights::default()
+        };
+        assert!(huge.validate().is_err());
+    }
+
+    #[test]
+    fn only_special_fruit_expires() {
+        let pos = Position { x: 0, y: 0 };
+        assert_eq!(Fruit::new(pos.clone(), FruitKind::Normal).ticks_left, None);
+        for kind in [FruitKind::Golden, FruitKind::Poison, FruitKind::Speed] {
+            assert_eq!(
+                Fruit::new(pos.clone(), kind).ticks_left,
+                Some(SPECIAL_FRUIT_TICKS)
+            );
+        }
+    }
+}
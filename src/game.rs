@@ -1,70 +1,146 @@
-use piston_window::*;
-use rand::Rng;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::colors;
+use crate::board::Topology;
+use crate::colors::Palette;
+use crate::controller::{self, BoardView, Controller};
 use crate::draw::*;
-use crate::physics::{Direction, Position};
-use crate::snake::Snake;
-
-const FPS: f64 = 10.0;
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum GameStatus {
-    Playing,
-    GameOver,
-}
+use crate::fruit::SPEED_BOOST_FACTOR;
+use crate::input::InputQueue;
+use crate::keys::Action;
+use crate::netplay::{ConnectionStatus, Session};
+use crate::physics::Direction;
+use crate::render::Renderer;
+use crate::replay::Replay;
+use crate::sim::{self, Event, GameState, GameStatus, Rules};
+use crate::spectate::{Feed, Spectator};
+use crate::speed::SpeedCurve;
+use crate::versus::Match;
+
+const PLAYBACK_SPEEDS: [f64; 4] = [0.5, 1.0, 2.0, 4.0];
+/// Ticks before a special fruit disappears during which it blinks
+const FRUIT_BLINK_TICKS: u32 = 15;
+/// Opacity of a snake that lost a versus round, relative to its color
+const DEAD_SNAKE_ALPHA: f32 = 0.35;
+
+/// A controller with a lock of its own, so the tick thread can ask it
+/// without holding on to the rest of the game.
+type SharedController = Arc<Mutex<Box<dyn Controller>>>;
 
 fn fps_as_duration(fps: f64) -> Duration {
     Duration::from_secs_f64(1.0 / fps)
 }
 
-fn calc_random_pos(width: u32, height: u32) -> Position {
-    let mut rng = rand::thread_rng();
-
-    Position {
-        x: rng.gen_range(0..width as i32),
-        y: rng.gen_range(0..height as i32),
-    }
-}
-
-struct GameState {
-    snake: Snake,
-    fruit: Position,
-    size: (u32, u32),
-    score: u32,
-    status: GameStatus,
+struct SharedState {
+    sim: GameState,
     paused: bool,
-    pending_direction: Option<Direction>,
+    // Keyboard turns, one per player
+    input_queues: Vec<InputQueue>,
+    // What drives each player, the keyboard if `None`
+    controllers: Vec<Option<SharedController>>,
     should_stop_thread: bool,
-    apple_eaten_at: Option<Position>,
+    events: Vec<Event>,
+    // Inputs of the current game, or the inputs being played back
+    replay: Replay,
+    playback: bool,
+    speed_curve: SpeedCurve,
+    speed: f64,
+    step_requested: bool,
+    // The connection to the other player of an online match
+    net: Option<Session>,
+    // The game being watched, streamed by another instance
+    feed: Option<Spectator>,
+    // The other player of an online match started a new round, or the
+    // game being watched did
+    restarted: bool,
 }
 
 pub struct Game {
-    state: Arc<Mutex<GameState>>,
+    state: Arc<Mutex<SharedState>>,
     update_thread: Option<thread::JoinHandle<()>>,
+    // Seconds since the game was created, for animations
+    time: f64,
+    // Rounds won so far, only in versus
+    versus: Option<Match>,
 }
 
 impl Game {
-    pub fn new(width: u32, height: u32) -> Self {
-        let state = Arc::new(Mutex::new(GameState {
-            snake: Snake::new(calc_random_pos(width, height)),
-            fruit: calc_random_pos(width, height),
-            size: (width, height),
-            score: 0,
-            status: GameStatus::Playing,
+    pub fn new(rules: Rules, seed: u64) -> Self {
+        Self::with_replay(Replay::new(seed, rules), false)
+    }
+
+    /// Create a game that feeds the recorded inputs of `replay` into the
+    /// tick loop instead of listening to the keyboard.
+    pub fn from_replay(replay: Replay) -> Self {
+        Self::with_replay(replay, true)
+    }
+
+    fn with_replay(replay: Replay, playback: bool) -> Self {
+        let players = replay.rules.players.max(1);
+        let versus = if players > 1 {
+            Some(Match::default())
+        } else {
+            None
+        };
+        let state = Arc::new(Mutex::new(SharedState {
+            sim: GameState::new(replay.rules.clone(), replay.seed),
             paused: true,
-            pending_direction: None,
+            input_queues: vec![InputQueue::default(); players],
+            controllers: (0..players).map(|_| None).collect(),
             should_stop_thread: false,
-            apple_eaten_at: None,
+            events: Vec::new(),
+            replay,
+            playback,
+            speed_curve: SpeedCurve::default(),
+            speed: 1.0,
+            step_requested: false,
+            net: None,
+            feed: None,
+            restarted: false,
         }));
 
         Self {
             state,
             update_thread: None,
+            time: 0.0,
+            versus,
+        }
+    }
+
+    /// A versus match against another instance over the network, see
+    /// `netplay`. The local snake listens to both sets of keys.
+    pub fn online(session: Session) -> Self {
+        let mut game = Self::with_replay(session.get_replay().clone(), false);
+        game.versus = Some(Match::new(session.get_best_of()));
+        {
+            let mut state = game.state.lock().unwrap();
+            state.sim = session.get_state().clone();
+            state.net = Some(session);
         }
+        game
+    }
+
+    /// Watch a game streamed by another instance, see `spectate`. `replay`
+    /// is the game so far, which is caught up with at once.
+    pub fn spectate(spectator: Spectator, replay: Replay) -> Self {
+        let mut game = Self::played_through(replay);
+        if let Some(best_of) = spectator.get_best_of() {
+            game.set_best_of(best_of);
+        }
+        game.state.lock().unwrap().feed = Some(spectator);
+        game
+    }
+
+    /// The game as it is after every tick of `replay`, e.g. to draw it
+    /// without playing. Once started it goes on from there.
+    pub fn played_through(replay: Replay) -> Self {
+        let game = Self::with_replay(Replay::new(replay.seed, replay.rules.clone()), false);
+        Self::catch_up(&mut game.state.lock().unwrap(), replay);
+        game
     }
 
     pub fn start(&mut self) {
@@ -77,90 +153,282 @@ impl Game {
         // Start the game logic thread
         let state_clone = Arc::clone(&self.state);
         self.update_thread = Some(thread::spawn(move || {
-            let tick_duration = fps_as_duration(FPS);
             let mut last_update = Instant::now();
 
             loop {
-                let now = Instant::now();
-                let elapsed = now.duration_since(last_update);
+                // Sleep briefly to avoid busy-waiting
+                thread::sleep(Duration::from_millis(1));
 
-                if elapsed >= tick_duration {
-                    last_update = now;
+                let mut state = state_clone.lock().unwrap();
 
-                    let mut state = state_clone.lock().unwrap();
+                if state.should_stop_thread {
+                    break;
+                }
 
-                    if state.should_stop_thread {
-                        break;
-                    }
+                // Inputs of the other player arrive between ticks
+                if state.net.is_some() {
+                    Self::poll_net(&mut state);
+                }
 
-                    if state.status == GameStatus::GameOver {
-                        // Keep the thread alive but don't update game logic
-                        continue;
+                // A watched game ticks whenever its inputs arrive
+                if state.feed.is_some() {
+                    Self::poll_feed(&mut state);
+                    continue;
+                }
+
+                let now = Instant::now();
+                // The tick rate follows the length of the snake and speed
+                // fruit, so look it up again for every tick
+                let tick_rate = Self::tick_rate(&state);
+                let tick_duration = fps_as_duration(tick_rate * state.speed);
+
+                if now.duration_since(last_update) < tick_duration && !state.step_requested {
+                    continue;
+                }
+                last_update = now;
+
+                if state.paused && !state.step_requested {
+                    continue;
+                }
+                state.step_requested = false;
+
+                if let Some(net) = &state.net {
+                    let player = net.get_player();
+                    let bot = state.controllers[player]
+                        .clone()
+                        .filter(|_| net.can_advance());
+                    let mut decided = None;
+                    if let Some(bot) = bot {
+                        // Like below, the bot decides on a copy of the game
+                        let sim = net.get_state().clone();
+                        drop(state);
+                        let view = BoardView::new(&sim, player);
+                        decided = Some(bot.lock().unwrap().next_direction(&view));
+                        state = state_clone.lock().unwrap();
+                        if state.should_stop_thread {
+                            break;
+                        }
                     }
+                    Self::tick_net(&mut state, decided);
+                    continue;
+                }
+
+                if state.sim.get_status() != GameStatus::Playing {
+                    continue;
+                }
 
-                    if state.paused {
+                // The rules themselves live in `sim`, this thread only
+                // decides when a tick happens and which input it gets.
+                let inputs: Vec<Option<Direction>> = if state.playback {
+                    let tick = state.sim.get_tick();
+                    if tick >= state.replay.ticks() {
+                        // The recording ended without the game ending
+                        state.paused = true;
                         continue;
                     }
-
-                    // Apply pending direction change
-                    if let Some(dir) = state.pending_direction.take() {
-                        state.snake.set_dir(dir);
+                    state.replay.inputs_at(tick).to_vec()
+                } else {
+                    let bots = state.controllers.clone();
+                    let mut decided = vec![None; bots.len()];
+                    if bots.iter().any(Option::is_some) {
+                        // Bots may take a while to answer, e.g. a program
+                        // over a pipe, so they decide on a copy of the game
+                        // while drawing and key presses go on
+                        let sim = state.sim.clone();
+                        drop(state);
+                        decided = Self::decide_bots(&bots, &sim);
+                        state = state_clone.lock().unwrap();
+                        if state.should_stop_thread {
+                            break;
+                        }
                     }
 
-                    // Check for wall collision before updating
-                    if state.snake.will_hit_wall(state.size.0, state.size.1) {
-                        state.status = GameStatus::GameOver;
-                        continue;
+                    let state = &mut *state;
+                    let sim = &state.sim;
+                    let inputs: Vec<_> = state
+                        .input_queues
+                        .iter_mut()
+                        .zip(decided)
+                        .enumerate()
+                        .map(|(player, (queue, decided))| {
+                            decided.unwrap_or_else(|| controller::decide(queue, sim, player))
+                        })
+                        .collect();
+                    state.replay.record(&inputs);
+                    inputs
+                };
+
+                let events = sim::step_all(&mut state.sim, &inputs);
+                state.events.extend(events);
+                if !state.playback && state.sim.get_status() != GameStatus::Playing {
+                    for controller in state.controllers.iter().flatten() {
+                        controller.lock().unwrap().end();
                     }
+                }
+            }
+        }));
+    }
 
-                    if !state.snake.is_tail_overlapping() && !state.snake.will_tail_overlapp() {
-                        let snake_head_pos = state.snake.get_head_pos().clone();
-                        let did_eat_fruit = snake_head_pos == state.fruit;
+    /// The input of every player driven by one of `bots` on `sim`, `None`
+    /// for the players on the keyboard.
+    fn decide_bots(
+        bots: &[Option<SharedController>],
+        sim: &GameState,
+    ) -> Vec<Option<Option<Direction>>> {
+        bots.iter()
+            .enumerate()
+            .map(|(player, bot)| {
+                bot.as_ref()
+                    .map(|bot| controller::decide(bot.lock().unwrap().as_mut(), sim, player))
+            })
+            .collect()
+    }
 
-                        let (width, height) = state.size;
-                        state.snake.update(width, height);
+    fn poll_net(state: &mut SharedState) {
+        let net = match &mut state.net {
+            Some(net) => net,
+            None => return,
+        };
 
-                        if did_eat_fruit {
-                            state.snake.grow();
-                            state.score = (state.snake.get_len() * 10) as u32;
-                            state.apple_eaten_at = Some(state.fruit.clone());
-                            state.fruit = calc_random_pos(width, height);
-                        }
-                    } else {
-                        state.status = GameStatus::GameOver;
-                    }
-                }
+        let events = net.poll();
+        // The confirmed end of a round
+        if !events.is_empty() {
+            for controller in state.controllers.iter().flatten() {
+                controller.lock().unwrap().end();
+            }
+        }
+        if net.take_restarted() {
+            for queue in state.input_queues.iter_mut() {
+                queue.clear();
+            }
+            for controller in state.controllers.iter().flatten() {
+                controller.lock().unwrap().reset();
+            }
+            state.restarted = true;
+        }
+        // A late input may have changed the past
+        state.sim = net.get_state().clone();
+        state.events.extend(events);
+    }
 
-                // Sleep briefly to avoid busy-waiting
-                thread::sleep(Duration::from_millis(1));
+    /// Play the next predicted tick of an online game with the local
+    /// player's input, `decided` already if a bot drives the snake.
+    fn tick_net(state: &mut SharedState, decided: Option<Direction>) {
+        let net = match &mut state.net {
+            Some(net) => net,
+            None => return,
+        };
+        if !net.can_advance() {
+            return;
+        }
+
+        let player = net.get_player();
+        // Turns on their way are what the queue and bot are checked
+        // against, not the direction the snake has now
+        let input = match decided {
+            Some(dir) => net.input_for(dir),
+            None => state.input_queues[player].pop(),
+        };
+        let events = net.tick(input);
+        state.sim = net.get_state().clone();
+        state.events.extend(events);
+    }
+
+    /// Make `replay` the current game, played through to its last tick
+    /// without any events.
+    fn catch_up(state: &mut SharedState, replay: Replay) {
+        let mut sim = GameState::new(replay.rules.clone(), replay.seed);
+        for tick in 0..replay.ticks() {
+            sim::step_all(&mut sim, replay.inputs_at(tick));
+        }
+        state.sim = sim;
+        state.replay = replay;
+        state.events.clear();
+    }
+
+    fn poll_feed(state: &mut SharedState) {
+        let feed = match &mut state.feed {
+            Some(spectator) => spectator.poll(),
+            None => return,
+        };
+
+        for item in feed {
+            match item {
+                Feed::Game(replay) => {
+                    Self::catch_up(state, *replay);
+                    state.restarted = true;
+                }
+                Feed::Tick(inputs) => {
+                    state.replay.record(&inputs);
+                    let events = sim::step_all(&mut state.sim, &inputs);
+                    state.events.extend(events);
+                }
+                Feed::Ended(_) => {}
             }
-        }));
+        }
     }
 
-    pub fn restart(&mut self) {
-        // Stop the current thread
+    /// Stop the tick thread and wait for it to finish.
+    fn stop(&mut self) {
         {
             let mut state = self.state.lock().unwrap();
             state.should_stop_thread = true;
         }
 
-        // Wait for the thread to finish
         if let Some(handle) = self.update_thread.take() {
             let _ = handle.join();
         }
+    }
+
+    pub fn restart(&mut self) {
+        // The game being watched restarts on its own
+        if self.is_spectating() {
+            return;
+        }
+
+        // An online round only ends once both sides agree on it
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(net) = &mut state.net {
+                if !net.restart() {
+                    return;
+                }
+            }
+        }
+
+        self.stop();
+
+        // The next round of a versus match, or a new match once it is over
+        if let Some(versus) = &mut self.versus {
+            if versus.is_over() {
+                versus.reset();
+            }
+        }
 
         // Reset the game state
         {
             let mut state = self.state.lock().unwrap();
-            let (width, height) = state.size;
-            state.snake = Snake::new(calc_random_pos(width, height));
-            state.fruit = calc_random_pos(width, height);
-            state.score = 0;
-            state.status = GameStatus::Playing;
+
+            // A playback restarts the same recording, a live game gets a
+            // fresh seed.
+            if !state.playback && state.net.is_none() {
+                state.replay = Replay::new(rand::random(), state.replay.rules.clone());
+            }
+
+            state.sim = match &state.net {
+                Some(net) => net.get_state().clone(),
+                None => GameState::new(state.replay.rules.clone(), state.replay.seed),
+            };
             state.paused = false;
-            state.pending_direction = None;
+            for queue in state.input_queues.iter_mut() {
+                queue.clear();
+            }
+            // The tick thread has stopped, so no bot is busy deciding
+            for controller in state.controllers.iter().flatten() {
+                controller.lock().unwrap().reset();
+            }
             state.should_stop_thread = false;
-            state.apple_eaten_at = None;
+            state.events.clear();
         }
 
         // Start the game again
@@ -172,33 +440,208 @@ impl Game {
         state.paused = true;
     }
 
+    /// How the tick rate grows with the snake.
+    pub fn set_speed_curve(&mut self, speed_curve: SpeedCurve) {
+        let mut state = self.state.lock().unwrap();
+        state.speed_curve = speed_curve;
+    }
+
+    /// Ticks per second at the current length of the snake, including a
+    /// speed fruit boost but not the playback speed.
+    pub fn get_tick_rate(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        Self::tick_rate(&state)
+    }
+
+    fn tick_rate(state: &SharedState) -> f64 {
+        // In versus the longest snake sets the pace for both
+        let length = state
+            .sim
+            .get_players()
+            .iter()
+            .map(|player| player.get_snake().get_len())
+            .max()
+            .unwrap_or(0);
+        let rate = state.speed_curve.tick_rate(length);
+        if state.sim.is_boosted() {
+            rate * SPEED_BOOST_FACTOR
+        } else {
+            rate
+        }
+    }
+
+    /// Let `controller` drive the snake of `player` instead of the
+    /// keyboard. Ignored during playback and for players not in the game.
+    pub fn set_controller(&mut self, player: usize, controller: Box<dyn Controller>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(slot) = state.controllers.get_mut(player) {
+            *slot = Some(Arc::new(Mutex::new(controller)));
+        }
+    }
+
+    /// How many turns can be buffered between ticks.
+    pub fn set_input_queue_depth(&mut self, depth: usize) {
+        let mut state = self.state.lock().unwrap();
+        for queue in state.input_queues.iter_mut() {
+            queue.set_depth(depth);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.paused
+    }
+
+    pub fn is_playback(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.playback
+    }
+
+    /// Tick rate multiplier, only changed during playback.
+    pub fn get_speed(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        state.speed
+    }
+
+    /// Write the inputs of the game played so far to a new file in `dir`.
+    pub fn save_replay(&self, dir: &Path) -> io::Result<PathBuf> {
+        let state = self.state.lock().unwrap();
+        fs::create_dir_all(dir)?;
+
+        let (replay, tick) = Self::played(&state);
+        let path = dir.join(format!("rsnake-{}-{}.replay", replay.seed, tick));
+        replay.save(&path)?;
+
+        Ok(path)
+    }
+
+    /// The replay and the ticks played of it. Online only the confirmed
+    /// inputs are final.
+    fn played(state: &SharedState) -> (&Replay, u64) {
+        match &state.net {
+            Some(net) => (net.get_replay(), net.get_confirmed().get_tick()),
+            None => (&state.replay, state.sim.get_tick()),
+        }
+    }
+
+    /// The inputs of the game up to the current tick, for spectators.
+    pub fn get_played(&self) -> Replay {
+        let state = self.state.lock().unwrap();
+        let (replay, tick) = Self::played(&state);
+        let mut played = replay.clone();
+        played.truncate(tick);
+        played
+    }
+
+    /// Seed and tick of `get_played`, without copying its inputs.
+    pub fn get_played_tick(&self) -> (u64, u64) {
+        let state = self.state.lock().unwrap();
+        let (replay, tick) = Self::played(&state);
+        (replay.seed, tick)
+    }
+
+    /// Whether this is a game streamed by another instance.
+    pub fn is_spectating(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.feed.is_some()
+    }
+
+    /// Whether any snake is driven by a controller, e.g. a bot, instead of
+    /// the keyboard.
+    pub fn has_controllers(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.controllers.iter().any(Option::is_some)
+    }
+
+    /// Why the stream of a watched game ended, `None` while it goes on.
+    pub fn get_stream_ended(&self) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .feed
+            .as_ref()
+            .and_then(|spectator| spectator.get_ended().map(str::to_string))
+    }
+
     pub fn get_status(&self) -> GameStatus {
         let state = self.state.lock().unwrap();
-        state.status
+        state.sim.get_status()
     }
 
-    pub fn draw(&self, ctx: Context, g: &mut G2d) {
+    /// Draw the board, `r` is in cells (see `board_transform`).
+    pub fn draw(&self, r: &mut dyn Renderer, palette: &Palette) {
         let state = self.state.lock().unwrap();
-        draw_block(&ctx, g, colors::FRUIT, &state.fruit);
-        state.snake.draw(&ctx, g);
+        let board = state.sim.get_board();
+        for wall in board.walls.iter() {
+            draw_block(r, palette.wall, wall);
+        }
+        for (pos, partner) in board.portals.iter() {
+            // Both cells of a pair get the same phase so they pulse together
+            let pair_phase = (pos.x + pos.y + partner.x + partner.y) as f64;
+            draw_portal(
+                r,
+                palette.portal,
+                palette.background,
+                pos,
+                self.time + pair_phase,
+            );
+        }
+        if let Some(fruit) = state.sim.get_fruit() {
+            // Blink when a special fruit is about to disappear
+            let blink = matches!(fruit.ticks_left, Some(ticks) if ticks <= FRUIT_BLINK_TICKS && ticks % 2 == 0);
+            if !blink {
+                draw_fruit(r, palette.fruit_color(fruit.kind), fruit);
+            }
+        }
+        if let [player] = state.sim.get_players() {
+            player.get_snake().draw(r, palette);
+        } else {
+            for (player, color) in state
+                .sim
+                .get_players()
+                .iter()
+                .zip([palette.snake, palette.snake2].iter())
+            {
+                // The loser of a round stays on the board, faded out
+                let mut color = *color;
+                if !player.is_alive() {
+                    color[3] *= DEAD_SNAKE_ALPHA;
+                }
+                player
+                    .get_snake()
+                    .draw_colored(r, color, palette.background);
+            }
+        }
 
-        if state.status == GameStatus::GameOver {
-            draw_overlay(&ctx, g, colors::OVERLAY, state.size)
+        match state.sim.get_status() {
+            GameStatus::GameOver => draw_overlay(r, palette.overlay, state.sim.get_size()),
+            GameStatus::Won | GameStatus::Cleared => {
+                draw_overlay(r, palette.won_overlay, state.sim.get_size())
+            }
+            GameStatus::Playing => {}
         }
     }
 
-    pub fn update(&mut self, _delta_time: f64) {
-        // Game logic is now handled in a separate thread
-        // This method is kept for API compatibility but does nothing
+    /// Play one tick with the input of every player, recorded like a tick
+    /// of the tick thread. For a game that isn't started, e.g. to draw
+    /// every tick of a replay.
+    pub fn step(&mut self, inputs: &[Option<Direction>]) {
+        let mut state = self.state.lock().unwrap();
+        state.replay.record(inputs);
+        let events = sim::step_all(&mut state.sim, inputs);
+        state.events.extend(events);
     }
 
-    pub fn key_down(&mut self, key: keyboard::Key) {
-        use keyboard::Key;
+    pub fn update(&mut self, delta_time: f64) {
+        // Game logic is handled in a separate thread, this only advances
+        // the animations
+        self.time += delta_time;
+    }
 
+    pub fn key_down(&mut self, action: Action) {
         // Check for restart key first (works even when game is over)
-        if key == Key::R {
+        if action == Action::Restart {
             let status = self.get_status();
-            if status == GameStatus::GameOver {
+            if status != GameStatus::Playing {
                 self.restart();
                 return;
             }
@@ -206,31 +649,175 @@ impl Game {
 
         let mut state = self.state.lock().unwrap();
 
+        if state.playback {
+            Self::playback_key_down(&mut state, action);
+            return;
+        }
+
+        // Spectators only watch
+        if state.feed.is_some() {
+            return;
+        }
+
         // Don't process movement keys if game is over
-        if state.status == GameStatus::GameOver {
+        if state.sim.get_status() != GameStatus::Playing {
             return;
         }
 
-        let dir = match key {
-            Key::A | Key::Left => Some(Direction::Left),
-            Key::W | Key::Up => Some(Direction::Up),
-            Key::D | Key::Right => Some(Direction::Right),
-            Key::S | Key::Down => Some(Direction::Down),
+        let mut turn = match action {
+            Action::Left => Some((0, Direction::Left)),
+            Action::Up => Some((0, Direction::Up)),
+            Action::Right => Some((0, Direction::Right)),
+            Action::Down => Some((0, Direction::Down)),
+            Action::P2Left => Some((1, Direction::Left)),
+            Action::P2Up => Some((1, Direction::Up)),
+            Action::P2Right => Some((1, Direction::Right)),
+            Action::P2Down => Some((1, Direction::Down)),
             _ => None,
         };
 
-        if let Some(d) = dir {
-            state.pending_direction = Some(d);
+        // Online both sets of keys steer the local snake
+        if let Some(net) = &state.net {
+            turn = turn.map(|(_, d)| (net.get_player(), d));
+        }
+
+        if let Some((player, d)) = turn {
+            // Snakes driven by a bot don't listen to the keyboard
+            if matches!(state.controllers.get(player), Some(Some(_))) {
+                return;
+            }
+            let current = match &state.net {
+                Some(net) => net.get_heading(),
+                None => state
+                    .sim
+                    .get_players()
+                    .get(player)
+                    .map(|p| p.get_snake().get_dir()),
+            };
+            let current = match current {
+                Some(current) => current,
+                None => return,
+            };
+            state.input_queues[player].push(d, current);
+        }
+    }
+
+    fn playback_key_down(state: &mut SharedState, action: Action) {
+        let current = PLAYBACK_SPEEDS
+            .iter()
+            .position(|speed| *speed == state.speed)
+            .unwrap_or(1);
+
+        match action {
+            Action::Pause => state.paused = !state.paused,
+            Action::Step => {
+                state.paused = true;
+                state.step_requested = true;
+            }
+            Action::Slower => state.speed = PLAYBACK_SPEEDS[current.saturating_sub(1)],
+            Action::Faster => {
+                state.speed = PLAYBACK_SPEEDS[(current + 1).min(PLAYBACK_SPEEDS.len() - 1)]
+            }
+            _ => {}
         }
     }
 
+    pub fn get_size(&self) -> (u32, u32) {
+        let state = self.state.lock().unwrap();
+        state.sim.get_size()
+    }
+
+    pub fn get_topology(&self) -> Topology {
+        let state = self.state.lock().unwrap();
+        state.sim.get_board().topology
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.replay.seed
+    }
+
     pub fn get_score(&self) -> u32 {
         let state = self.state.lock().unwrap();
-        state.score
+        state.sim.get_score()
     }
 
-    pub fn take_apple_eaten(&mut self) -> Option<Position> {
+    pub fn get_fruits_eaten(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+        state.sim.get_fruits_eaten()
+    }
+
+    /// Length of the snake including its head.
+    pub fn get_length(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.sim.get_snake().get_body_len()
+    }
+
+    /// Scores of all players, a single one outside of versus.
+    pub fn get_scores(&self) -> Vec<u32> {
+        let state = self.state.lock().unwrap();
+        state
+            .sim
+            .get_players()
+            .iter()
+            .map(|player| player.get_score())
+            .collect()
+    }
+
+    /// Who won the versus round that just ended, `None` for a draw.
+    pub fn get_round_winner(&self) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state.sim.get_winner()
+    }
+
+    /// The versus match score, `None` outside of versus.
+    pub fn get_match(&self) -> Option<&Match> {
+        self.versus.as_ref()
+    }
+
+    /// Play versus rounds until one player won the majority of `best_of`.
+    pub fn set_best_of(&mut self, best_of: u32) {
+        if let Some(versus) = &mut self.versus {
+            *versus = Match::new(best_of);
+        }
+    }
+
+    /// The local player, the connection status and the ping of an online
+    /// match, `None` for a local game.
+    pub fn get_connection(&self) -> Option<(usize, ConnectionStatus, Option<Duration>)> {
+        let state = self.state.lock().unwrap();
+        state
+            .net
+            .as_ref()
+            .map(|net| (net.get_player(), net.get_status().clone(), net.get_ping()))
+    }
+
+    /// Drain the events produced by the tick thread since the last call.
+    /// The end of a versus round is added to the match score here.
+    pub fn take_events(&mut self) -> Vec<Event> {
         let mut state = self.state.lock().unwrap();
-        state.apple_eaten_at.take()
+        let events = std::mem::take(&mut state.events);
+
+        if let Some(versus) = &mut self.versus {
+            if events.contains(&Event::GameOver) || events.contains(&Event::Won) {
+                // Online the next round may already have started
+                let winner = match &state.net {
+                    Some(net) => net.get_round_winner(),
+                    None => state.sim.get_winner(),
+                };
+                versus.record(winner);
+            }
+            if std::mem::take(&mut state.restarted) && versus.is_over() {
+                versus.reset();
+            }
+        }
+
+        events
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.stop();
     }
 }
@@ -0,0 +1,351 @@
+//! The best scores of every game mode and board size, saved to
+//! `$XDG_DATA_HOME/rsnake/highscores.toml`:
+//!
+//! ```toml
+//! last_name = "MARA"
+//!
+//! [[tables]]
+//! mode = "classic"
+//! width = 25
+//! height = 25
+//!
+//! [[tables.entries]]
+//! name = "MARA"
+//! score = 420
+//! length = 45
+//! seed = 1234
+//! ```
+//!
+//! A score only competes with scores of the same mode on a board of the
+//! same size. Saving copies the old file to `highscores.toml.bak`, writes a
+//! new one and renames it over the old one, so a crash leaves either table
+//! whole. A file that can't be parsed is moved aside to
+//! `highscores.toml.corrupt` and the backup is used instead.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TOP: usize = 10;
+/// Characters in a player name
+pub const MAX_NAME_LEN: usize = 12;
+/// Name of a score entered without one
+const NO_NAME: &str = "???";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    /// Length of the snake when the game ended
+    pub length: usize,
+    /// Seed of the game, to find its replay
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Table {
+    mode: String,
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+/// Everything in the file, with the best `top` scores of every table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    /// Name entered last, offered again for the next score
+    #[serde(default)]
+    pub last_name: String,
+    #[serde(default)]
+    tables: Vec<Table>,
+    #[serde(skip)]
+    top: usize,
+}
+
+/// `path` with `suffix` added to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// `name` cut down to what fits on the score table, `???` if empty.
+pub fn clean_name(name: &str) -> String {
+    let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+    if name.is_empty() {
+        NO_NAME.to_string()
+    } else {
+        name
+    }
+}
+
+impl HighScores {
+    /// An empty table keeping the best `top` scores.
+    pub fn new(top: usize) -> Self {
+        Self {
+            last_name: String::new(),
+            tables: Vec::new(),
+            top,
+        }
+    }
+
+    /// `highscores.toml` in the user's data directory, if there is one.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("rsnake").join("highscores.toml"))
+    }
+
+    /// The scores at `path`, or none if there is no file. A file that
+    /// can't be parsed is an `InvalidData` error.
+    fn read(path: &Path) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+    }
+
+    /// Load the saved scores, keeping the best `top` of every table. A
+    /// damaged file is moved aside and the backup of it used instead, and
+    /// if there is none either the scores start from scratch. `Err` if the
+    /// file is there but can't be read, which leaves it alone.
+    pub fn load(path: &Path, top: usize) -> io::Result<Self> {
+        let backup = sibling(path, ".bak");
+        let loaded = match Self::read(path) {
+            Ok(Some(scores)) => Some(scores),
+            // Moved aside as damaged before, without a save since
+            Ok(None) => Self::read(&backup).ok().flatten(),
+            Err(err) if err.kind() != ErrorKind::InvalidData => return Err(err),
+            Err(err) => {
+                let corrupt = sibling(path, ".corrupt");
+                eprintln!(
+                    "Could not parse high scores {}: {}, moved to {}",
+                    path.display(),
+                    err,
+                    corrupt.display()
+                );
+                if let Err(err) = fs::rename(path, &corrupt) {
+                    eprintln!("Could not move {}: {}", path.display(), err);
+                }
+                let restored = Self::read(&backup).ok().flatten();
+                if restored.is_some() {
+                    eprintln!("Restored high scores from {}", backup.display());
+                }
+                restored
+            }
+        };
+
+        let mut scores = loaded.unwrap_or_else(|| Self::new(top));
+        scores.top = top;
+        scores.tidy();
+        Ok(scores)
+    }
+
+    /// Save the scores without ever leaving a half written file at `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+
+        let temp = sibling(path, ".tmp");
+        let mut file = File::create(&temp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        // Copied rather than moved, so there is a file at `path` throughout
+        if path.exists() {
+            fs::copy(path, sibling(path, ".bak"))?;
+        }
+        fs::rename(&temp, path)
+    }
+
+    /// Sort every table, keep its best `top` scores, clean up the names and
+    /// drop empty tables, whatever the file said.
+    fn tidy(&mut self) {
+        let top = self.top;
+        for table in self.tables.iter_mut() {
+            // Stable, so of equal scores the earlier one stays ahead
+            table.entries.sort_by_key(|entry| Reverse(entry.score));
+            table.entries.truncate(top);
+            for entry in table.entries.iter_mut() {
+                entry.name = clean_name(&entry.name);
+            }
+        }
+        self.tables.retain(|table| !table.entries.is_empty());
+        self.last_name = self.last_name.chars().take(MAX_NAME_LEN).collect();
+    }
+
+    fn table(&self, mode: &str, (width, height): (u32, u32)) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.mode == mode && table.width == width && table.height == height)
+    }
+
+    /// The best scores of `mode` on a board of `size`, best first.
+    pub fn get_entries(&self, mode: &str, size: (u32, u32)) -> &[Entry] {
+        self.table(mode, size)
+            .map_or(&[], |table| table.entries.as_slice())
+    }
+
+    /// Where `score` would go in the table of `mode` on a board of `size`,
+    /// `None` if it doesn't make it. Zero never does.
+    pub fn rank(&self, mode: &str, size: (u32, u32), score: u32) -> Option<usize> {
+        let entries = self.get_entries(mode, size);
+        let rank = entries
+            .iter()
+            .position(|entry| entry.score < score)
+            .unwrap_or(entries.len());
+        if score > 0 && rank < self.top {
+            Some(rank)
+        } else {
+            None
+        }
+    }
+
+    /// Add `entry` to the table of `mode` on a board of `size`, pushing out
+    /// the lowest score if it is full. Returns its place, `None` if it
+    /// didn't make it.
+    pub fn insert(&mut self, mode: &str, size: (u32, u32), mut entry: Entry) -> Option<usize> {
+        let rank = self.rank(mode, size, entry.score)?;
+        entry.name = clean_name(&entry.name);
+
+        if self.table(mode, size).is_none() {
+            self.tables.push(Table {
+                mode: mode.to_string(),
+                width: size.0,
+                height: size.1,
+                entries: Vec::new(),
+            });
+        }
+        let top = self.top;
+        let table = self
+            .tables
+            .iter_mut()
+            .find(|table| table.mode == mode && (table.width, table.height) == size)?;
+        table.entries.insert(rank, entry);
+        table.entries.truncate(top);
+
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    const MODE: &str = "classic";
+    const SIZE: (u32, u32) = (25, 25);
+
+    /// An empty directory of its own for every test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsnake-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(name: &str, score: u32) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            length: 3,
+            seed: score as u64,
+        }
+    }
+
+    fn names(scores: &HighScores) -> Vec<&str> {
+        scores
+            .get_entries(MODE, SIZE)
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_best_scores_in_order() {
+        let mut scores = HighScores::new(3);
+        assert_eq!(scores.insert(MODE, SIZE, entry("A", 10)), Some(0));
+        assert_eq!(scores.insert(MODE, SIZE, entry("B", 30)), Some(0));
+        // Below the equal score that was there first
+        assert_eq!(scores.insert(MODE, SIZE, entry("C", 10)), Some(2));
+        assert_eq!(scores.insert(MODE, SIZE, entry("D", 20)), Some(1));
+        assert_eq!(scores.insert(MODE, SIZE, entry("E", 5)), None);
+        assert_eq!(scores.insert(MODE, SIZE, entry("F", 0)), None);
+
+        assert_eq!(names(&scores), ["B", "D", "A"]);
+        assert!(scores.get_entries(MODE, (11, 11)).is_empty());
+        assert!(scores.get_entries("wrap", SIZE).is_empty());
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let path = temp_dir("save").join("highscores.toml");
+        let mut scores = HighScores::load(&path, DEFAULT_TOP).unwrap();
+        assert!(names(&scores).is_empty());
+
+        scores.last_name = "A".to_string();
+        scores.insert(MODE, SIZE, entry("A", 10));
+        scores.save(&path).unwrap();
+        assert!(!sibling(&path, ".bak").exists());
+
+        let loaded = HighScores::load(&path, DEFAULT_TOP).unwrap();
+        assert_eq!(loaded, scores);
+    }
+
+    #[test]
+    fn restores_a_corrupt_file_from_the_backup() {
+        let path = temp_dir("corrupt").join("highscores.toml");
+        let mut scores = HighScores::new(DEFAULT_TOP);
+        scores.insert(MODE, SIZE, entry("A", 10));
+        scores.save(&path).unwrap();
+        scores.insert(MODE, SIZE, entry("B", 20));
+        scores.save(&path).unwrap();
+
+        fs::write(&path, "[[tables]\nmode = ").unwrap();
+        let loaded = HighScores::load(&path, DEFAULT_TOP).unwrap();
+
+        // The backup is from before the last save
+        assert_eq!(names(&loaded), ["A"]);
+        assert!(!path.exists());
+        let corrupt = fs::read_to_string(sibling(&path, ".corrupt")).unwrap();
+        assert_eq!(corrupt, "[[tables]\nmode = ");
+
+        // Until the next save the backup is all there is
+        let again = HighScores::load(&path, DEFAULT_TOP).unwrap();
+        assert_eq!(names(&again), ["A"]);
+    }
+
+    #[test]
+    fn leaves_a_file_it_cannot_read() {
+        let path = temp_dir("unreadable").join("highscores.toml");
+        fs::create_dir(&path).unwrap();
+
+        assert!(HighScores::load(&path, DEFAULT_TOP).is_err());
+        assert!(path.is_dir());
+        assert!(!sibling(&path, ".corrupt").exists());
+    }
+
+    #[test]
+    fn tidies_what_it_loads() {
+        let path = temp_dir("tidy").join("highscores.toml");
+        let text = "[[tables]]\nmode = \"classic\"\nwidth = 25\nheight = 25\n\n\
+                    [[tables.entries]]\nname = \"  \"\nscore = 1\nlength = 3\nseed = 1\n\n\
+                    [[tables.entries]]\nname = \"B\"\nscore = 3\nlength = 3\nseed = 2\n\n\
+                    [[tables.entries]]\nname = \"C\"\nscore = 2\nlength = 3\nseed = 3\n";
+        fs::write(&path, text).unwrap();
+
+        let scores = HighScores::load(&path, 2).unwrap();
+        assert_eq!(names(&scores), ["B", "C"]);
+
+        let scores = HighScores::load(&path, DEFAULT_TOP).unwrap();
+        assert_eq!(names(&scores), ["B", "C", NO_NAME]);
+    }
+}
@@ -0,0 +1,118 @@
+//! Buffered direction input.
+//!
+//! Players often press two keys within a single tick (e.g. Up then Left to
+//! make a U-turn). Instead of keeping only the last key, inputs are queued
+//! and the tick loop takes one turn per tick.
+
+use std::collections::VecDeque;
+
+use crate::physics::Direction;
+
+pub const DEFAULT_INPUT_QUEUE_DEPTH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct InputQueue {
+    turns: VecDeque<Direction>,
+    depth: usize,
+}
+
+impl InputQueue {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            turns: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+
+    /// Queue a turn. `current` is the direction the snake is moving in right
+    /// now, or online will be once the turns already sent are played; the
+    /// turn is checked against the direction the snake will have once all
+    /// queued turns are applied, so reversals and duplicates are dropped.
+    /// Returns whether the turn was queued.
+    pub fn push(&mut self, dir: Direction, current: Direction) -> bool {
+        let heading = self.turns.back().copied().unwrap_or(current);
+
+        if self.turns.len() >= self.depth || dir == heading || dir == heading.opposite() {
+            return false;
+        }
+
+        self.turns.push_back(dir);
+        true
+    }
+
+    /// Take the turn to apply on this tick.
+    pub fn pop(&mut self) -> Option<Direction> {
+        self.turns.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Change the depth, dropping the newest turns if it shrinks.
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        self.turns.truncate(depth);
+    }
+}
+
+impl Default for InputQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_INPUT_QUEUE_DEPTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Direction::*;
+
+    #[test]
+    fn queues_turns_in_order() {
+        let mut queue = InputQueue::new(3);
+        assert!(queue.push(Up, Right));
+        assert!(queue.push(Left, Right));
+
+        assert_eq!(queue.pop(), Some(Up));
+        assert_eq!(queue.pop(), Some(Left));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn drops_reversals_and_repeats_of_the_last_turn() {
+        let mut queue = InputQueue::new(3);
+        assert!(!queue.push(Left, Right));
+        assert!(!queue.push(Right, Right));
+
+        assert!(queue.push(Up, Right));
+        // Checked against the queued turn, not the current direction
+        assert!(!queue.push(Down, Right));
+        assert!(!queue.push(Up, Right));
+        assert!(queue.push(Left, Right));
+
+        assert_eq!(queue.pop(), Some(Up));
+        assert_eq!(queue.pop(), Some(Left));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn holds_at_most_depth_turns() {
+        let mut queue = InputQueue::new(2);
+        assert!(queue.push(Up, Right));
+        assert!(queue.push(Left, Right));
+        assert!(!queue.push(Down, Right));
+
+        queue.set_depth(1);
+        assert_eq!(queue.get_depth(), 1);
+        assert_eq!(queue.pop(), Some(Up));
+        assert_eq!(queue.pop(), None);
+
+        queue.push(Up, Right);
+        queue.clear();
+        assert_eq!(queue.pop(), None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Resolving the key names from the config file to piston keys.
+
+use piston_window::keyboard::Key;
+
+use crate::config::KeyBindings;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Restart,
+    Mute,
+    Screenshot,
+    HighScores,
+    Pause,
+    Step,
+    Slower,
+    Faster,
+    Confirm,
+    Back,
+    P2Up,
+    P2Down,
+    P2Left,
+    P2Right,
+}
+
+impl Action {
+    /// Whether the action turns the first player's snake.
+    pub fn is_p1_move(self) -> bool {
+        matches!(
+            self,
+            Action::Up | Action::Down | Action::Left | Action::Right
+        )
+    }
+
+    /// Whether the action turns the second player's snake in versus.
+    pub fn is_p2_move(self) -> bool {
+        matches!(
+            self,
+            Action::P2Up | Action::P2Down | Action::P2Left | Action::P2Right
+        )
+    }
+}
+
+/// Parse a key name such as `"W"`, `"7"`, `"Up"`, `"Space"` or `"F5"`.
+pub fn parse_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "A" => Key::A,
+        "B" => Key::B,
+        "C" => Key::C,
+        "D" => Key::D,
+        "E" => Key::E,
+        "F" => Key::F,
+        "G" => Key::G,
+        "H" => Key::H,
+        "I" => Key::I,
+        "J" => Key::J,
+        "K" => Key::K,
+        "L" => Key::L,
+        "M" => Key::M,
+        "N" => Key::N,
+        "O" => Key::O,
+        "P" => Key::P,
+        "Q" => Key::Q,
+        "R" => Key::R,
+        "S" => Key::S,
+        "T" => Key::T,
+        "U" => Key::U,
+        "V" => Key::V,
+        "W" => Key::W,
+        "X" => Key::X,
+        "Y" => Key::Y,
+        "Z" => Key::Z,
+        "0" => Key::D0,
+        "1" => Key::D1,
+        "2" => Key::D2,
+        "3" => Key::D3,
+        "4" => Key::D4,
+        "5" => Key::D5,
+        "6" => Key::D6,
+        "7" => Key::D7,
+        "8" => Key::D8,
+        "9" => Key::D9,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Space" => Key::Space,
+        "Return" => Key::Return,
+        "Escape" => Key::Escape,
+        "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace,
+        "Minus" => Key::Minus,
+        "Equals" => Key::Equals,
+        "Period" => Key::Period,
+        "Comma" => Key::Comma,
+        "Slash" => Key::Slash,
+        "Semicolon" => Key::Semicolon,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// The key bindings from the config, resolved to piston keys.
+#[derive(Debug, Clone)]
+pub struct Controls {
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Controls {
+    pub fn new(keys: &KeyBindings) -> Result<Self, String> {
+        let actions = [
+            (Action::Up, "up", &keys.up),
+            (Action::Down, "down", &keys.down),
+            (Action::Left, "left", &keys.left),
+            (Action::Right, "right", &keys.right),
+            (Action::Restart, "restart", &keys.restart),
+            (Action::Mute, "mute", &keys.mute),
+            (Action::Screenshot, "screenshot", &keys.screenshot),
+            (Action::HighScores, "high_scores", &keys.high_scores),
+            (Action::Pause, "pause", &keys.pause),
+            (Action::Step, "step", &keys.step),
+            (Action::Slower, "slower", &keys.slower),
+            (Action::Faster, "faster", &keys.faster),
+            (Action::Confirm, "confirm", &keys.confirm),
+            (Action::Back, "back", &keys.back),
+            (Action::P2Up, "p2_up", &keys.p2_up),
+            (Action::P2Down, "p2_down", &keys.p2_down),
+            (Action::P2Left, "p2_left", &keys.p2_left),
+            (Action::P2Right, "p2_right", &keys.p2_right),
+        ];
+        let mut bindings = Vec::new();
+
+        for (action, name, key_names) in actions.iter() {
+            for key_name in key_names.iter() {
+                let key = parse_key(key_name)
+                    .ok_or_else(|| format!("unknown key '{}' for keys.{}", key_name, name))?;
+                bindings.push((key, *action));
+            }
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// All actions bound to `key`. A key may be bound to more than one,
+    /// e.g. `Space` for pausing a replay and something else in a live game.
+    pub fn actions(&self, key: Key) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    /// Actions bound to `key` in a versus game. The arrow keys turn the
+    /// first snake in a normal game, so a key bound to the second player
+    /// only turns the second snake here.
+    pub fn versus_actions(&self, key: Key) -> impl Iterator<Item = Action> + '_ {
+        let p2 = self.actions(key).any(Action::is_p2_move);
+        self.actions(key)
+            .filter(move |action| !(p2 && action.is_p1_move()))
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self::new(&KeyBindings::default()).unwrap()
+    }
+}
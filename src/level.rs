@@ -0,0 +1,493 @@
+//! Obstacle maps loaded from plain text level files.
+//!
+//! Levels live in `assets/levels/` as `<name>.level`. A level is a grid of
+//! one character per cell, optionally preceded by header lines:
+//!
+//! ```text
+//! ; Lines starting with ';' are comments
+//! name The Box
+//! topology walled
+//! ##########
+//! #........#
+//! #..v.....#
+//! #...**...#
+//! #...**...#
+//! ##########
+//! ```
+//!
+//! | Character | Cell |
+//! |-----------|------|
+//! | `.` | Empty |
+//! | `#` | Wall |
+//! | `*` | Empty, fruit spawns here |
+//! | `^` `>` `v` `<` | The snake's head and the direction it starts in |
+//! | `0` to `9` | Portal, the two cells with the same digit are a pair |
+//!
+//! There must be exactly one snake head. If there are any `*` cells, fruit
+//! only spawns on those while one of them is free. The tail starts out
+//! behind the head, opposite to its direction, and must not run into a wall
+//! or off the board. A snake moving into a portal comes out of its partner
+//! with the same heading.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::board::{Board, Topology};
+use crate::physics::{Direction, Position};
+use crate::snake::DEFAULT_START_LENGTH;
+
+pub const LEVEL_DIR: &str = "levels";
+pub const LEVEL_EXTENSION: &str = "level";
+
+/// Cells across and down a board can have
+pub const MIN_SIZE: usize = 5;
+pub const MAX_SIZE: usize = 100;
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    /// A malformed level file, `line` and `column` start at 1
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "{}", err),
+            LevelError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<io::Error> for LevelError {
+    fn from(err: io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+fn syntax_error(line: usize, column: usize, message: String) -> LevelError {
+    LevelError::Syntax {
+        line,
+        column,
+        message,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    /// Size, topology and walls
+    pub board: Board,
+    pub start: Position,
+    pub start_dir: Direction,
+    /// Cells fruit prefers to spawn on, empty to spawn anywhere
+    pub fruit_zones: Vec<Position>,
+}
+
+fn head_char(dir: Direction) -> char {
+    match dir {
+        Direction::Up => '^',
+        Direction::Right => '>',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+    }
+}
+
+/// Path of the level called `name` in the asset directory.
+pub fn level_path(assets: &Path, name: &str) -> PathBuf {
+    assets
+        .join(LEVEL_DIR)
+        .join(name)
+        .with_extension(LEVEL_EXTENSION)
+}
+
+impl Level {
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let mut name = String::new();
+        let mut topology = Topology::Walled;
+        // Grid rows with their line numbers
+        let mut rows: Vec<(usize, &str)> = Vec::new();
+        let mut last_line = 0;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim_end();
+            last_line = line_no;
+
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            // The only letter in the grid is `v`, anything else starting
+            // with a letter is a header line
+            let is_header = line.starts_with(|c: char| c.is_ascii_alphabetic() && c != 'v');
+            if rows.is_empty() && is_header {
+                let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+                let value_column = key.len() + 2;
+                match key {
+                    "name" => name = value.trim().to_string(),
+                    "topology" => {
+                        topology = match value.trim() {
+                            "walled" => Topology::Walled,
+                            "wrap" => Topology::Wrap,
+                            other => {
+                                return Err(syntax_error(
+                                    line_no,
+                                    value_column,
+                                    format!(
+                                        "unknown topology '{}', expected walled or wrap",
+                                        other
+                                    ),
+                                ))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(syntax_error(
+                            line_no,
+                            1,
+                            format!("unknown header '{}', expected name or topology", key),
+                        ))
+                    }
+                }
+                continue;
+            }
+
+            rows.push((line_no, line));
+        }
+
+        let (first_line, first_row) = *rows
+            .first()
+            .ok_or_else(|| syntax_error(last_line + 1, 1, "level has no grid".to_string()))?;
+        let width = first_row.chars().count();
+        let height = rows.len();
+
+        if !(MIN_SIZE..=MAX_SIZE).contains(&width) || !(MIN_SIZE..=MAX_SIZE).contains(&height) {
+            return Err(syntax_error(
+                first_line,
+                1,
+                format!(
+                    "level is {}x{} cells, both sides must be between {} and {}",
+                    width, height, MIN_SIZE, MAX_SIZE
+                ),
+            ));
+        }
+
+        let mut walls = HashSet::new();
+        let mut fruit_zones = Vec::new();
+        let mut start: Option<(Position, Direction, usize, usize)> = None;
+        // Cells of every portal digit with their line and column
+        let mut portals: BTreeMap<char, Vec<(Position, usize, usize)>> = BTreeMap::new();
+
+        for (y, (line_no, row)) in rows.iter().enumerate() {
+            let row_width = row.chars().count();
+            if row_width != width {
+                return Err(syntax_error(
+                    *line_no,
+                    row_width.min(width) + 1,
+                    format!("row is {} cells wide, expected {}", row_width, width),
+                ));
+            }
+
+            for (x, c) in row.chars().enumerate() {
+                let pos = Position {
+                    x: x as i32,
+                    y: y as i32,
+                };
+                let dir = match c {
+                    '.' => None,
+                    '#' => {
+                        walls.insert(pos.clone());
+                        None
+                    }
+                    '*' => {
+                        fruit_zones.push(pos.clone());
+                        None
+                    }
+                    '^' => Some(Direction::Up),
+                    '>' => Some(Direction::Right),
+                    'v' => Some(Direction::Down),
+                    '<' => Some(Direction::Left),
+                    '0'..='9' => {
+                        let cells = portals.entry(c).or_default();
+                        if cells.len() == 2 {
+                            return Err(syntax_error(
+                                *line_no,
+                                x + 1,
+                                format!("portal '{}' appears more than twice", c),
+                            ));
+                        }
+                        cells.push((pos.clone(), *line_no, x + 1));
+                        None
+                    }
+                    _ => {
+                        return Err(syntax_error(
+                            *line_no,
+                            x + 1,
+                            format!("unexpected character '{}'", c),
+                        ))
+                    }
+                };
+
+                if let Some(dir) = dir {
+                    if let Some((_, _, first_line, first_column)) = start {
+                        return Err(syntax_error(
+                            *line_no,
+                            x + 1,
+                            format!(
+                                "second snake head, the first one is at line {}, column {}",
+                                first_line, first_column
+                            ),
+                        ));
+                    }
+                    start = Some((pos, dir, *line_no, x + 1));
+                }
+            }
+        }
+
+        let (start, start_dir, start_line, start_column) = start.ok_or_else(|| {
+            syntax_error(
+                first_line,
+                1,
+                "level has no snake head (one of ^ > v <)".to_string(),
+            )
+        })?;
+
+        let mut board = Board::new(width as u32, height as u32, topology);
+        board.walls = walls;
+        for (digit, cells) in portals {
+            if let [(a, _, _), (b, _, _)] = &cells[..] {
+                board.add_portal(a.clone(), b.clone());
+            } else {
+                let (_, line, column) = cells[0];
+                return Err(syntax_error(
+                    line,
+                    column,
+                    format!("portal '{}' has no partner", digit),
+                ));
+            }
+        }
+
+        let level = Self {
+            name,
+            board,
+            start,
+            start_dir,
+            fruit_zones,
+        };
+        level
+            .check_start(DEFAULT_START_LENGTH)
+            .map_err(|message| syntax_error(start_line, start_column, message))?;
+
+        Ok(level)
+    }
+
+    /// Check that a snake of `length` fits behind the head, laid out the
+    /// way `Snake::new` does.
+    pub fn check_start(&self, length: usize) -> Result<(), String> {
+        let board = &self.board;
+        let mut cells = HashSet::new();
+        let mut segment = self.start.clone();
+        cells.insert(segment.clone());
+
+        for _ in 1..length {
+            segment = board.neighbor(&segment, self.start_dir.opposite());
+            let problem = if !board.contains(&segment) {
+                "runs off the board"
+            } else if board.is_wall(&segment) {
+                "runs into a wall"
+            } else if !cells.insert(segment.clone()) {
+                "runs into itself"
+            } else {
+                continue;
+            };
+            return Err(format!(
+                "the tail of a snake {} cells long {}",
+                length, problem
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Load a level file. A level without a `name` header is named after
+    /// the file.
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let mut level = Self::parse(&fs::read_to_string(path)?)?;
+
+        if level.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                level.name = stem.to_string_lossy().into_owned();
+            }
+        }
+
+        Ok(level)
+    }
+
+    /// The level in the same format `parse` reads. A fruit zone under the
+    /// snake's head is lost.
+    pub fn serialize(&self) -> String {
+        let board = &self.board;
+        let zones: HashSet<&Position> = self.fruit_zones.iter().collect();
+
+        // Number the portal pairs 1 to 9, then 0, in the order they appear
+        let mut pairs: Vec<(&Position, &Position)> = board
+            .portals
+            .iter()
+            .filter(|(a, b)| (a.y, a.x) < (b.y, b.x))
+            .collect();
+        pairs.sort_by_key(|(a, _)| (a.y, a.x));
+        let portal_digit = |pos: &Position| {
+            pairs
+                .iter()
+                .position(|(a, b)| *a == pos || *b == pos)
+                .and_then(|i| std::char::from_digit((i as u32 + 1) % 10, 10))
+        };
+
+        let mut out = String::new();
+
+        if !self.name.is_empty() {
+            out.push_str(&format!("name {}\n", self.name));
+        }
+        if board.topology == Topology::Wrap {
+            out.push_str("topology wrap\n");
+        }
+
+        for y in 0..board.height as i32 {
+            for x in 0..board.width as i32 {
+                let pos = Position { x, y };
+                let c = if pos == self.start {
+                    head_char(self.start_dir)
+                } else if let Some(digit) = portal_digit(&pos) {
+                    digit
+                } else if board.is_wall(&pos) {
+                    '#'
+                } else if zones.contains(&pos) {
+                    '*'
+                } else {
+                    '.'
+                };
+                out.push(c);
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: &str = "name Box\n#######\n#.....#\n#.....#\n#..v..#\n#..*..#\n#1...1#\n#######\n";
+
+    /// Line, column and message of the error parsing `text`.
+    fn error(text: &str) -> (usize, usize, String) {
+        match Level::parse(text) {
+            Err(LevelError::Syntax {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_and_serializes() {
+        let level = Level::parse(BOX).unwrap();
+        assert_eq!(level.name, "Box");
+        assert_eq!((level.board.width, level.board.height), (7, 7));
+        assert_eq!(level.start, Position { x: 3, y: 3 });
+        assert_eq!(level.start_dir, Direction::Down);
+        assert_eq!(level.fruit_zones, vec![Position { x: 3, y: 4 }]);
+        assert!(level.board.is_wall(&Position { x: 0, y: 0 }));
+        assert!(level.board.is_portal(&Position { x: 1, y: 5 }));
+
+        assert_eq!(Level::parse(&level.serialize()).unwrap(), level);
+    }
+
+    #[test]
+    fn bad_headers() {
+        let (line, column, message) = error("; comment\nsize 5\n.....");
+        assert_eq!((line, column), (2, 1));
+        assert!(message.contains("unknown header 'size'"), "{}", message);
+
+        let (line, column, message) = error("topology torus\n.....");
+        assert_eq!((line, column), (1, 10));
+        assert!(message.contains("unknown topology 'torus'"), "{}", message);
+
+        let (line, _, message) = error("name Empty\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("no grid"), "{}", message);
+    }
+
+    #[test]
+    fn bad_grids() {
+        let (_, _, message) = error("....\n..>.\n....\n....");
+        assert!(message.contains("4x4"), "{}", message);
+
+        let (line, column, message) = error(".....\n..>.\n.....\n.....\n.....");
+        assert_eq!((line, column), (2, 5));
+        assert!(message.contains("4 cells wide"), "{}", message);
+
+        let (line, column, message) = error(".....\n..>..\n..x..\n.....\n.....");
+        assert_eq!((line, column), (3, 3));
+        assert!(message.contains("'x'"), "{}", message);
+    }
+
+    #[test]
+    fn bad_snakes() {
+        let (line, _, message) = error(".....\n.....\n.....\n.....\n.....");
+        assert_eq!(line, 1);
+        assert!(message.contains("no snake head"), "{}", message);
+
+        let (line, column, message) = error(".....\n..>..\n.....\n..<..\n.....");
+        assert_eq!((line, column), (4, 3));
+        assert!(message.contains("line 2, column 3"), "{}", message);
+
+        // The tail of a snake going right starts to its left
+        let (line, column, message) = error(".....\n.#>..\n.....\n.....\n.....");
+        assert_eq!((line, column), (2, 3));
+        assert!(message.contains("wall"), "{}", message);
+        let (_, _, message) = error(".....\n.>...\n.....\n.....\n.....");
+        assert!(message.contains("off the board"), "{}", message);
+    }
+
+    #[test]
+    fn bad_portals() {
+        let (line, column, message) = error("1...1\n..>..\n.....\n.....\n..1..");
+        assert_eq!((line, column), (5, 3));
+        assert!(message.contains("more than twice"), "{}", message);
+
+        let (line, column, message) = error(".....\n..>..\n.....\n...2.\n.....");
+        assert_eq!((line, column), (4, 4));
+        assert!(message.contains("no partner"), "{}", message);
+    }
+
+    #[test]
+    fn longer_snakes_have_to_fit() {
+        let level = Level::parse(BOX).unwrap();
+        // Two cells up to the wall above the head
+        assert!(level.check_start(3).is_ok());
+        assert!(level.check_start(4).is_err());
+
+        let wrap = Level::parse("topology wrap\n>....\n.....\n.....\n.....\n.....").unwrap();
+        assert!(wrap.check_start(5).is_ok());
+        assert!(wrap.check_start(6).is_err());
+    }
+}
@@ -0,0 +1,580 @@
+//! Moves between the screens of the campaign: level select, playing a
+//! level and the transition after a level was cleared.
+//!
+//! Outside of the campaign the manager just holds a single game. With high
+//! scores kept, a single game that ends with one moves on to entering the
+//! player's name and then the leaderboard.
+
+use std::path::PathBuf;
+
+use crate::campaign::{Campaign, Goal, Progress};
+use crate::colors::Palette;
+use crate::config::KeyBindings;
+use crate::draw::draw_overlay;
+use crate::fruit::FruitWeights;
+use crate::game::Game;
+use crate::highscores::{self, Entry, HighScores, MAX_NAME_LEN};
+use crate::keys::Action;
+use crate::render::Renderer;
+use crate::sim::{Event, GameStatus, Rules};
+use crate::speed::SpeedCurve;
+
+const MENU_OVERLAY: [f32; 4] = [0.0, 0.0, 0.0, 0.7];
+const LOCKED_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const LINE_HEIGHT: f64 = 30.0;
+const SCORE_LINE_HEIGHT: f64 = 24.0;
+/// Characters allowed in names besides letters and digits
+const NAME_SYMBOLS: &str = " -_.";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Screen {
+    /// Choosing a level, the game shows a preview of it
+    Select,
+    Playing,
+    /// The level was cleared, waiting to move on to the next one
+    Cleared,
+    /// The game ended with a high score, the player types their name
+    EnterName,
+    /// The best scores of the mode being played
+    HighScores,
+}
+
+/// How new campaign games are set up, besides their level.
+#[derive(Debug, Clone)]
+pub struct GameSetup {
+    pub start_length: usize,
+    pub fruit_weights: FruitWeights,
+    pub speed_curve: SpeedCurve,
+    pub input_depth: usize,
+}
+
+struct CampaignState {
+    campaign: Campaign,
+    progress: Progress,
+    // Where progress is saved, not saved at all if `None`
+    progress_path: Option<PathBuf>,
+    setup: GameSetup,
+    // Level selected or being played
+    current: usize,
+}
+
+struct ScoreBoard {
+    scores: HighScores,
+    // Where the scores are saved, not saved at all if `None`
+    path: Option<PathBuf>,
+    // Game mode the scores of the game go under
+    mode: String,
+    // Name being typed
+    name: String,
+    // Place of the score just entered, to highlight it
+    placed: Option<usize>,
+}
+
+pub struct LevelManager {
+    game: Game,
+    screen: Screen,
+    campaign: Option<CampaignState>,
+    scores: Option<ScoreBoard>,
+    level_changed: bool,
+}
+
+impl LevelManager {
+    /// A single game outside of the campaign.
+    pub fn single(game: Game) -> Self {
+        Self {
+            game,
+            screen: Screen::Playing,
+            campaign: None,
+            scores: None,
+            level_changed: false,
+        }
+    }
+
+    /// Start on the level select screen, with the first level that hasn't
+    /// been cleared yet selected.
+    pub fn campaign(
+        campaign: Campaign,
+        progress: Progress,
+        progress_path: Option<PathBuf>,
+        setup: GameSetup,
+    ) -> Self {
+        let current = progress.cleared.min(campaign.len() - 1);
+        let state = CampaignState {
+            campaign,
+            progress,
+            progress_path,
+            setup,
+            current,
+        };
+        let game = Self::create_game(&state);
+
+        Self {
+            game,
+            screen: Screen::Select,
+            campaign: Some(state),
+            scores: None,
+            level_changed: false,
+        }
+    }
+
+    /// Add the scores of single games to `scores` under `mode`, saving
+    /// them to `path` after every new one.
+    pub fn keep_high_scores(&mut self, scores: HighScores, path: Option<PathBuf>, mode: String) {
+        self.scores = Some(ScoreBoard {
+            scores,
+            path,
+            mode,
+            name: String::new(),
+            placed: None,
+        });
+    }
+
+    /// Whether the scores of the game go into the high scores. Campaign
+    /// levels end at their goal, and only games played alone on the
+    /// keyboard count.
+    pub fn keeps_high_scores(&self) -> bool {
+        let game = &self.game;
+        self.scores.is_some()
+            && self.campaign.is_none()
+            && !game.is_playback()
+            && !game.is_spectating()
+            && game.get_connection().is_none()
+            && game.get_match().is_none()
+            && !game.has_controllers()
+    }
+
+    /// Where the score of the game goes in the high scores, `None` if it
+    /// doesn't make it.
+    fn score_rank(&self) -> Option<usize> {
+        let board = self.scores.as_ref().filter(|_| self.keeps_high_scores())?;
+        board
+            .scores
+            .rank(&board.mode, self.game.get_size(), self.game.get_score())
+    }
+
+    /// Add the score of the game under the name typed, save the high
+    /// scores and show them.
+    fn enter_score(&mut self) {
+        let board = match &mut self.scores {
+            Some(board) => board,
+            None => return,
+        };
+        let entry = Entry {
+            name: highscores::clean_name(&board.name),
+            score: self.game.get_score(),
+            length: self.game.get_length(),
+            seed: self.game.get_seed(),
+        };
+        board.placed = board
+            .scores
+            .insert(&board.mode, self.game.get_size(), entry);
+        board.scores.last_name = board.name.trim().to_string();
+        if let Some(path) = &board.path {
+            if let Err(err) = board.scores.save(path) {
+                eprintln!("Could not save high scores to {}: {}", path.display(), err);
+            }
+        }
+        self.screen = Screen::HighScores;
+    }
+
+    /// Whether a name is being typed, so keys go to `type_text` instead of
+    /// doing what they are bound to.
+    pub fn is_typing(&self) -> bool {
+        self.screen == Screen::EnterName
+    }
+
+    /// Add `text` typed on the keyboard to the name being entered, letters
+    /// in upper case.
+    pub fn type_text(&mut self, text: &str) {
+        let board = match &mut self.scores {
+            Some(board) if self.screen == Screen::EnterName => board,
+            _ => return,
+        };
+        let typed = text
+            .chars()
+            .filter(|ch| ch.is_ascii_alphanumeric() || NAME_SYMBOLS.contains(*ch));
+        for ch in typed {
+            if board.name.chars().count() < MAX_NAME_LEN {
+                board.name.push(ch.to_ascii_uppercase());
+            }
+        }
+    }
+
+    /// Remove the last character of the name being entered.
+    pub fn erase_text(&mut self) {
+        if let Some(board) = &mut self.scores {
+            if self.screen == Screen::EnterName {
+                board.name.pop();
+            }
+        }
+    }
+
+    fn create_game(state: &CampaignState) -> Game {
+        let stage = &state.campaign.stages[state.current];
+        let mut rules = Rules::from_level(&stage.level);
+        rules.start_length = state.setup.start_length;
+        rules.fruit_weights = state.setup.fruit_weights.clone();
+        rules.goal = Some(stage.goal);
+
+        let mut game = Game::new(rules, rand::random());
+        game.set_speed_curve(state.setup.speed_curve.clone());
+        game.set_input_queue_depth(state.setup.input_depth);
+        game
+    }
+
+    /// Replace the game with one for the current campaign level.
+    fn load_current(&mut self) {
+        if let Some(state) = &self.campaign {
+            self.game = Self::create_game(state);
+            self.level_changed = true;
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    pub fn get_screen(&self) -> Screen {
+        self.screen
+    }
+
+    pub fn is_campaign(&self) -> bool {
+        self.campaign.is_some()
+    }
+
+    /// Name of the level being played or selected.
+    pub fn level_name(&self) -> Option<&str> {
+        self.campaign
+            .as_ref()
+            .map(|state| state.campaign.stages[state.current].level.name.as_str())
+    }
+
+    /// Whether the game was replaced by one on a different level since the
+    /// last call. The frontend then has to rebuild everything that depends
+    /// on the board.
+    pub fn take_level_changed(&mut self) -> bool {
+        std::mem::take(&mut self.level_changed)
+    }
+
+    /// Start the game, unless there is a level to choose first.
+    pub fn start(&mut self) {
+        if self.screen == Screen::Playing {
+            self.game.start();
+        }
+    }
+
+    pub fn key_down(&mut self, action: Action) {
+        match self.screen {
+            Screen::EnterName => {
+                match action {
+                    Action::Confirm => self.enter_score(),
+                    Action::Back => self.screen = Screen::Playing,
+                    _ => {}
+                }
+                return;
+            }
+            Screen::HighScores => {
+                match action {
+                    Action::Confirm | Action::Back | Action::HighScores => {
+                        self.screen = Screen::Playing;
+                    }
+                    Action::Restart => {
+                        self.screen = Screen::Playing;
+                        self.game.key_down(action);
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            Screen::Playing if action == Action::HighScores => {
+                if self.keeps_high_scores() && self.game.get_status() != GameStatus::Playing {
+                    if let Some(board) = &mut self.scores {
+                        board.placed = None;
+                    }
+                    self.screen = Screen::HighScores;
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let state = match &mut self.campaign {
+            Some(state) => state,
+            None => {
+                self.game.key_down(action);
+                return;
+            }
+        };
+
+        match self.screen {
+            Screen::Select => match action {
+                Action::Up if state.current > 0 => {
+                    state.current -= 1;
+                    self.load_current();
+                }
+                Action::Down if state.current + 1 < state.campaign.len() => {
+                    state.current += 1;
+                    self.load_current();
+                }
+                Action::Confirm if state.progress.is_unlocked(state.current) => {
+                    self.screen = Screen::Playing;
+                    self.game.start();
+                }
+                _ => {}
+            },
+            Screen::Playing => match action {
+                Action::Back => {
+                    self.screen = Screen::Select;
+                    self.load_current();
+                }
+                _ => self.game.key_down(action),
+            },
+            Screen::Cleared => match action {
+                Action::Confirm if state.current + 1 < state.campaign.len() => {
+                    state.current += 1;
+                    self.load_current();
+                    self.screen = Screen::Playing;
+                    self.game.start();
+                }
+                Action::Confirm | Action::Back => {
+                    self.screen = Screen::Select;
+                    self.load_current();
+                }
+                _ => {}
+            },
+            Screen::EnterName | Screen::HighScores => {}
+        }
+    }
+
+    /// Drain the events of the game, moving on to the transition screen
+    /// and saving progress when a level was cleared, or to entering a name
+    /// when the game ended with a high score.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let events = self.game.take_events();
+
+        if let Some(state) = &mut self.campaign {
+            if events.contains(&Event::LevelCleared) {
+                self.screen = Screen::Cleared;
+                if state.progress.clear(state.current) {
+                    if let Some(path) = &state.progress_path {
+                        if let Err(err) = state.progress.save(path) {
+                            eprintln!("Could not save progress to {}: {}", path.display(), err);
+                        }
+                    }
+                }
+            }
+        }
+
+        let ended = events.contains(&Event::GameOver) || events.contains(&Event::Won);
+        if ended && self.score_rank().is_some() {
+            if let Some(board) = &mut self.scores {
+                board.name = board.scores.last_name.clone();
+                board.placed = None;
+            }
+            self.screen = Screen::EnterName;
+        }
+
+        events
+    }
+
+    /// The goal of the current level and how far along it the player is.
+    pub fn goal_progress(&self) -> Option<String> {
+        let state = self.campaign.as_ref()?;
+        let goal = state.campaign.stages[state.current].goal;
+
+        let (done, total) = match goal {
+            Goal::Fruits(n) => (self.game.get_fruits_eaten() as usize, n as usize),
+            Goal::Length(n) => (self.game.get_length(), n),
+            Goal::Score(n) => (self.game.get_score() as usize, n as usize),
+        };
+
+        Some(format!("{}  {}/{}", goal, done.min(total), total))
+    }
+
+    /// Draw the level select, transition or high score screens on top of
+    /// the game, `r` is in pixels.
+    pub fn draw_screen(
+        &self,
+        r: &mut dyn Renderer,
+        palette: &Palette,
+        keys: &KeyBindings,
+        window_size: (f64, f64),
+    ) {
+        if self.screen == Screen::Playing {
+            return;
+        }
+        draw_overlay(
+            r,
+            MENU_OVERLAY,
+            (window_size.0 as u32, window_size.1 as u32),
+        );
+
+        if let Some(board) = &self.scores {
+            match self.screen {
+                Screen::EnterName => {
+                    self.draw_name_entry(r, board, palette, keys, window_size);
+                    return;
+                }
+                Screen::HighScores => {
+                    self.draw_high_scores(r, board, palette, keys, window_size);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let state = match &self.campaign {
+            Some(state) => state,
+            None => return,
+        };
+        let mut draw_text = |text: &str, size: u32, color: [f32; 4], x: f64, y: f64| {
+            r.text(color, size, text, x, y);
+        };
+
+        match self.screen {
+            Screen::Playing | Screen::EnterName | Screen::HighScores => {}
+            Screen::Select => {
+                draw_text("SELECT LEVEL", 32, palette.score, 40.0, 80.0);
+
+                for (i, stage) in state.campaign.stages.iter().enumerate() {
+                    let unlocked = state.progress.is_unlocked(i);
+                    let marker = if i == state.current { ">" } else { " " };
+                    let line = if unlocked {
+                        format!(
+                            "{} {}. {}  - {}",
+                            marker,
+                            i + 1,
+                            stage.level.name,
+                            stage.goal
+                        )
+                    } else {
+                        format!("{} {}. LOCKED", marker, i + 1)
+                    };
+                    let color = if unlocked {
+                        palette.score
+                    } else {
+                        LOCKED_COLOR
+                    };
+                    draw_text(&line, 16, color, 40.0, 130.0 + i as f64 * LINE_HEIGHT);
+                }
+
+                let help = format!(
+                    "[{}/{}] Choose  [{}] Play",
+                    first_key(&keys.up),
+                    first_key(&keys.down),
+                    first_key(&keys.confirm)
+                );
+                draw_text(&help, 14, palette.score, 40.0, window_size.1 - 30.0);
+            }
+            Screen::Cleared => {
+                let next = state.campaign.stages.get(state.current + 1);
+                let headline = if next.is_some() {
+                    "LEVEL CLEARED"
+                } else {
+                    "CAMPAIGN COMPLETE"
+                };
+                draw_text(
+                    headline,
+                    32,
+                    palette.score,
+                    40.0,
+                    window_size.1 / 2.0 - 40.0,
+                );
+
+                let detail = match next {
+                    Some(stage) => format!("Next: {} - {}", stage.level.name, stage.goal),
+                    None => "Every level cleared!".to_string(),
+                };
+                draw_text(&detail, 16, palette.score, 40.0, window_size.1 / 2.0);
+                draw_text(
+                    &format!("Press {} to continue", first_key(&keys.confirm)),
+                    16,
+                    palette.score,
+                    40.0,
+                    window_size.1 / 2.0 + 40.0,
+                );
+            }
+        }
+    }
+
+    fn draw_name_entry(
+        &self,
+        r: &mut dyn Renderer,
+        board: &ScoreBoard,
+        palette: &Palette,
+        keys: &KeyBindings,
+        window_size: (f64, f64),
+    ) {
+        let middle = window_size.1 / 2.0;
+        r.text(palette.score, 32, "NEW HIGH SCORE", 40.0, middle - 80.0);
+
+        let score = self.game.get_score();
+        let detail = match self.score_rank() {
+            Some(rank) => format!("{} points  #{}", score, rank + 1),
+            None => format!("{} points", score),
+        };
+        r.text(palette.score, 16, &detail, 40.0, middle - 40.0);
+        let name = format!("NAME: {}_", board.name);
+        r.text(palette.golden_fruit, 24, &name, 40.0, middle + 10.0);
+
+        let help = format!(
+            "[{}] Save  [{}] Skip",
+            first_key(&keys.confirm),
+            first_key(&keys.back)
+        );
+        r.text(palette.score, 14, &help, 40.0, window_size.1 - 30.0);
+    }
+
+    fn draw_high_scores(
+        &self,
+        r: &mut dyn Renderer,
+        board: &ScoreBoard,
+        palette: &Palette,
+        keys: &KeyBindings,
+        window_size: (f64, f64),
+    ) {
+        let (width, height) = self.game.get_size();
+        r.text(palette.score, 32, "HIGH SCORES", 40.0, 80.0);
+        let mode = format!("{} {}x{}", board.mode.to_uppercase(), width, height);
+        r.text(palette.score, 16, &mode, 40.0, 110.0);
+
+        let entries = board.scores.get_entries(&board.mode, (width, height));
+        if entries.is_empty() {
+            r.text(palette.score, 16, "No scores yet", 40.0, 160.0);
+        }
+
+        // As many lines as fit, scrolled down to a new score far below
+        let fits = ((window_size.1 - 220.0) / SCORE_LINE_HEIGHT).max(1.0) as usize;
+        let first = board
+            .placed
+            .map_or(0, |placed| (placed + 1).saturating_sub(fits));
+        for (i, entry) in entries.iter().enumerate().skip(first).take(fits) {
+            let color = if board.placed == Some(i) {
+                palette.golden_fruit
+            } else {
+                palette.score
+            };
+            let y = 160.0 + (i - first) as f64 * SCORE_LINE_HEIGHT;
+            r.text(color, 16, &format!("{}. {}", i + 1, entry.name), 40.0, y);
+            let score = entry.score.to_string();
+            let score_width = r.text_width(16, &score);
+            r.text(color, 16, &score, window_size.0 - 40.0 - score_width, y);
+        }
+
+        let help = format!(
+            "[{}] Play again  [{}] Back",
+            first_key(&keys.restart),
+            first_key(&keys.back)
+        );
+        r.text(palette.score, 14, &help, 40.0, window_size.1 - 30.0);
+    }
+}
+
+/// Name of the first key bound to an action, for help texts.
+fn first_key(names: &[String]) -> String {
+    names.first().cloned().unwrap_or_else(|| "-".to_string())
+}
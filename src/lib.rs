@@ -0,0 +1,39 @@
+pub mod arena;
+pub mod battlesnake;
+pub mod board;
+pub mod bots;
+pub mod campaign;
+pub mod colors;
+pub mod config;
+pub mod controller;
+pub mod draw;
+pub mod env;
+pub mod external;
+pub mod fruit;
+pub mod highscores;
+pub mod input;
+pub mod level;
+pub mod netplay;
+pub mod particles;
+pub mod physics;
+pub mod render;
+pub mod replay;
+pub mod sim;
+pub mod snake;
+pub mod software;
+pub mod spectate;
+pub mod speed;
+pub mod versus;
+
+#[cfg(feature = "images")]
+pub mod clip;
+#[cfg(feature = "gui")]
+pub mod game;
+#[cfg(feature = "gui")]
+pub mod keys;
+#[cfg(feature = "gui")]
+pub mod level_manager;
+#[cfg(feature = "gui")]
+pub mod sound;
+#[cfg(feature = "gui")]
+pub mod terminal;
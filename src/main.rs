@@ -13,47 +13,1205 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
-mod colors;
-mod draw;
-mod game;
-mod particles;
-mod physics;
-mod snake;
-
-use draw::{blocks_in_pixels, Background};
-use game::{Game, GameStatus};
-use particles::ParticleSystem;
+mod cli;
+
+use cli::Args;
 use piston_window::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use rodio::{Decoder, OutputStream, Sink, Source};
-use std::fs::File;
-use std::io::BufReader;
+use rsnake::battlesnake::{ArenaGame, Server};
+use rsnake::board::Topology;
+use rsnake::campaign::{Campaign, Progress};
+use rsnake::clip::GifWriter;
+use rsnake::colors::{Color, Palette};
+use rsnake::config::{Config, ParticleSettings};
+use rsnake::controller::{self, Controller};
+use rsnake::draw::{blocks_in_pixels, board_transform, Background};
+use rsnake::game::Game;
+use rsnake::highscores::HighScores;
+use rsnake::keys::{Action, Controls};
+use rsnake::level::{level_path, Level};
+use rsnake::level_manager::{GameSetup, LevelManager};
+use rsnake::netplay::{self, ConnectionStatus, Session};
+use rsnake::particles::ParticleSystem;
+use rsnake::render::{PistonRenderer, Renderer, Transform};
+use rsnake::replay::Replay;
+use rsnake::sim::{Event, GameStatus, Rules};
+use rsnake::software::{Font, Pixmap, SoftwareRenderer};
+use rsnake::sound::play_fruit_sound;
+use rsnake::spectate::{Broadcaster, Spectator};
+use rsnake::terminal::{self, Canvas, Input, Screen};
+use rsnake::versus::Match;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const WINDOW_TITLE: &str = "rsnake";
+const REPLAY_DIR: &str = "replays";
+const SCREENSHOT_DIR: &str = "screenshots";
+const FONT_FILE: &str = "retro-gaming.ttf";
+/// Seconds the last frame of a GIF stays on before it loops
+const GIF_END_HOLD: f64 = 2.0;
+/// Frame time in the terminal, which doesn't need to be smooth
+const TERMINAL_FRAME: Duration = Duration::from_millis(33);
+/// Time between drawing the whole terminal, not just what changed
+const TERMINAL_REDRAW: Duration = Duration::from_secs(1);
+/// Rows of text above and below the board in the terminal
+const TERMINAL_TEXT_ROWS: (usize, usize) = (2, 1);
+
+/// Everything drawn that depends on the board the game is played on.
+struct BoardView {
+    width: u32,
+    height: u32,
+    background: Background,
+    particle_system: ParticleSystem,
+}
+
+impl BoardView {
+    fn new(game: &Game, palette: &Palette, particles: &ParticleSettings, block_size: f64) -> Self {
+        let (width, height) = game.get_size();
+        let background = Background::new(
+            width,
+            height,
+            palette,
+            &mut ChaCha8Rng::seed_from_u64(game.get_seed()),
+        );
+        let mut particle_system = ParticleSystem::new(particles.clone(), block_size);
+        if game.get_topology() == Topology::Wrap {
+            particle_system.set_wrap(width, height);
+        }
+
+        Self {
+            width,
+            height,
+            background,
+            particle_system,
+        }
+    }
+
+    /// Window size in pixels.
+    fn window_size(&self, block_size: f64) -> [u32; 2] {
+        [
+            blocks_in_pixels(self.width, block_size),
+            blocks_in_pixels(self.height, block_size),
+        ]
+    }
+
+    /// Particle bursts for eating fruit and going through portals.
+    fn spawn_particles(&mut self, event: &Event, palette: &Palette) {
+        match event {
+            Event::FruitEaten(fruit) => self.particle_system.spawn_at(
+                fruit.pos.x,
+                fruit.pos.y,
+                palette.fruit_color(fruit.kind),
+            ),
+            Event::Teleported { from, to } => {
+                self.particle_system
+                    .spawn_at(from.x, from.y, palette.portal);
+                self.particle_system.spawn_at(to.x, to.y, palette.portal);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn window_title(level_name: Option<&str>) -> String {
+    match level_name {
+        Some(name) => format!("{} - {}", WINDOW_TITLE, name),
+        None => WINDOW_TITLE.to_string(),
+    }
+}
+
+/// The asset directory from the command line, or the first `assets`
+/// directory found around the working directory. A game without a window
+/// only needs it for levels given by name.
+fn find_assets(args: &Args) -> PathBuf {
+    match &args.assets {
+        Some(dir) => dir.clone(),
+        None => find_folder::Search::ParentsThenKids(3, 3)
+            .for_folder("assets")
+            .unwrap_or_else(|_| {
+                eprintln!("Could not find the assets directory, pass it with --assets");
+                process::exit(1);
+            }),
+    }
+}
+
+/// Play a game between bots without a window, print how it ended and save
+/// its replay.
+fn run_headless(args: &Args, rules: Rules, bots: Vec<Option<Box<dyn Controller>>>) {
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut replay = Replay::new(seed, rules);
+    let mut controllers: Vec<_> = bots.into_iter().flatten().collect();
+    let state = controller::play(&mut replay, &mut controllers, args.max_ticks);
+
+    let result = match state.get_status() {
+        GameStatus::Playing => "stopped".to_string(),
+        _ if state.get_players().len() > 1 => match state.get_winner() {
+            Some(winner) => format!("P{} wins", winner + 1),
+            None => "draw".to_string(),
+        },
+        GameStatus::Won => "won".to_string(),
+        GameStatus::GameOver | GameStatus::Cleared => "game over".to_string(),
+    };
+    let scores: Vec<String> = state
+        .get_players()
+        .iter()
+        .map(|player| {
+            format!(
+                "score {} length {}",
+                player.get_score(),
+                player.get_snake().get_body_len()
+            )
+        })
+        .collect();
+    println!(
+        "seed {}: {} after {} ticks, {}",
+        seed,
+        result,
+        state.get_tick(),
+        scores.join(" / ")
+    );
+
+    let path = Path::new(REPLAY_DIR).join(format!("rsnake-{}-{}.replay", seed, state.get_tick()));
+    match fs::create_dir_all(REPLAY_DIR).and_then(|_| replay.save(&path)) {
+        Ok(()) => println!("Replay saved to {}", path.display()),
+        Err(err) => eprintln!("Could not save replay: {}", err),
+    }
+}
+
+/// Host or join the online match asked for on the command line, `None`
+/// for a local game. Exits if there is no connection.
+fn online_session(args: &Args, rules: Rules) -> Option<Session> {
+    let session = if args.host {
+        println!("Waiting for a player on port {}...", args.port);
+        let seed = args.seed.unwrap_or_else(rand::random);
+        Session::host(args.port, rules, seed, args.best_of, args.input_delay)
+    } else if let Some(addr) = args.connect_addr() {
+        Session::connect(&addr)
+    } else {
+        return None;
+    };
+
+    match session {
+        Ok(session) => {
+            println!("Connected, playing P{}", session.get_player() + 1);
+            Some(session)
+        }
+        Err(err) => {
+            eprintln!("Could not start the online match: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Connect to the game to watch asked for on the command line, `None` to
+/// play. Exits if there is no connection.
+fn watched_game(args: &Args) -> Option<Game> {
+    let addr = args.watch_addr()?;
+    match Spectator::connect(&addr) {
+        Ok((spectator, replay)) => {
+            println!("Watching {}", addr);
+            Some(Game::spectate(spectator, replay))
+        }
+        Err(err) => {
+            eprintln!("Could not watch {}: {}", addr, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Let spectators in if asked to on the command line. Exits if the port
+/// is taken.
+fn spectate_broadcaster(args: &Args) -> Option<Broadcaster> {
+    if !args.spectate {
+        return None;
+    }
+
+    let port = args.spectate_port;
+    let broadcaster = Broadcaster::bind(port).unwrap_or_else(|err| {
+        eprintln!("Could not let spectators in on port {}: {}", port, err);
+        process::exit(1);
+    });
+    println!("Spectators can watch on port {}", port);
+    Some(broadcaster)
+}
+
+/// The game asked for on the command line other than the campaign: one
+/// to watch, an online match or a local game, new or played back.
+fn single_game(
+    args: &Args,
+    config: &Config,
+    level: Option<&Level>,
+    session: Option<Session>,
+    bots: Vec<Option<Box<dyn Controller>>>,
+) -> Game {
+    if let Some(game) = watched_game(args) {
+        return game;
+    }
+
+    let speed_curve = args.speed_curve(&config.speed);
+    let input_depth = args.input_buffer.unwrap_or(config.game.input_buffer);
+
+    if let Some(session) = session {
+        let player = session.get_player();
+        let mut game = Game::online(session);
+        game.set_speed_curve(speed_curve);
+        game.set_input_queue_depth(input_depth);
+        if let Some(bot) = bots.into_iter().next().flatten() {
+            game.set_controller(player, bot);
+        }
+        return game;
+    }
+
+    let mut game = match &args.replay {
+        Some(path) => match Replay::load(path) {
+            Ok(replay) => Game::from_replay(replay),
+            Err(err) => {
+                eprintln!("Could not load replay {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        },
+        None => Game::new(
+            args.rules(&config.fruit, level),
+            args.seed.unwrap_or_else(rand::random),
+        ),
+    };
+    game.set_speed_curve(speed_curve);
+    game.set_input_queue_depth(input_depth);
+    game.set_best_of(args.best_of);
+    for (player, bot) in bots.into_iter().enumerate() {
+        if let Some(bot) = bot {
+            game.set_controller(player, bot);
+        }
+    }
+    game
+}
+
+/// The score and the current speed, in versus the score of both players
+/// and the match instead.
+fn score_line(game: &Game) -> String {
+    match game.get_match() {
+        Some(versus) => {
+            let scores = game.get_scores();
+            let wins = versus.get_wins();
+            format!(
+                "P1 {}  P2 {}  MATCH {}-{}",
+                scores[0], scores[1], wins[0], wins[1]
+            )
+        }
+        None => format!("{}  SPEED {:.1}", game.get_score(), game.get_tick_rate()),
+    }
+}
+
+/// Online the own snake and the state of the connection, when watching
+/// whether the game still goes on.
+fn status_line(game: &Game) -> Option<String> {
+    if let Some((player, status, ping)) = game.get_connection() {
+        return Some(match (status, ping) {
+            (ConnectionStatus::Connected, Some(ping)) => {
+                format!("ONLINE P{}  PING {} MS", player + 1, ping.as_millis())
+            }
+            (ConnectionStatus::Connected, None) => format!("ONLINE P{}", player + 1),
+            (ConnectionStatus::Waiting, _) => {
+                format!("ONLINE P{}  WAITING FOR P{}", player + 1, 2 - player)
+            }
+            (ConnectionStatus::Disconnected(_), _) => "DISCONNECTED".to_string(),
+        });
+    }
+
+    if !game.is_spectating() {
+        return None;
+    }
+    Some(match game.get_stream_ended() {
+        Some(_) => "STREAM ENDED".to_string(),
+        None => "WATCHING".to_string(),
+    })
+}
+
+/// State and speed of a replay being played back.
+fn playback_line(game: &Game) -> Option<String> {
+    if !game.is_playback() {
+        return None;
+    }
+    let state = if game.is_paused() { "PAUSED" } else { "REPLAY" };
+    Some(format!(
+        "{} {}x  [SPACE] [.] [-/+]",
+        state,
+        game.get_speed()
+    ))
+}
+
+/// "GAME OVER" or who won once the game has ended.
+fn headline(game: &Game) -> Option<&'static str> {
+    match (game.get_status(), game.get_match()) {
+        (GameStatus::Playing, _) | (GameStatus::Cleared, _) => None,
+        (_, Some(_)) => match game.get_round_winner() {
+            Some(0) => Some("P1 WINS!"),
+            Some(_) => Some("P2 WINS!"),
+            None => Some("DRAW"),
+        },
+        (GameStatus::GameOver, None) => Some("GAME OVER"),
+        (GameStatus::Won, None) => Some("YOU WIN!"),
+    }
+}
+
+/// How to go on below the headline, in versus with the match score.
+fn restart_line(game: &Game, restart_key: &str) -> String {
+    match game.get_match() {
+        Some(versus) => match versus.get_winner() {
+            Some(winner) => format!(
+                "P{} takes the match! {} for a new one",
+                winner + 1,
+                restart_key
+            ),
+            None => format!(
+                "Best of {}  {} for the next round",
+                versus.get_best_of(),
+                restart_key
+            ),
+        },
+        None => format!("Press {} to Restart", restart_key),
+    }
+}
+
+/// Mode the high scores of a single game go under.
+fn score_mode(args: &Args, level: Option<&Level>) -> String {
+    match level {
+        Some(level) => format!("level {}", level.name),
+        None if args.wrap => "wrap".to_string(),
+        None => "classic".to_string(),
+    }
+}
+
+/// Everything the window shows: the board, the HUD and the level screens
+/// on top. `r` is in pixels. `music_muted` is `None` without music, which
+/// leaves out the mute button.
+fn draw_frame(
+    r: &mut dyn Renderer,
+    manager: &LevelManager,
+    view: &BoardView,
+    config: &Config,
+    music_muted: Option<bool>,
+) {
+    let palette = &config.colors;
+    let block_size = config.block_size;
+    let restart_key = config.keys.restart.first().map_or("-", String::as_str);
+    let main = manager.game();
+    let pixels = r.get_transform();
+    let [window_width, window_height] = view.window_size(block_size);
+    let (window_width, window_height) = (window_width as f64, window_height as f64);
+
+    r.set_transform(board_transform(pixels, block_size));
+    view.background.draw(r);
+    r.set_transform(pixels);
+
+    r.text(palette.score, 20, &score_line(main), 0.0, 20.0);
+    if let Some(line) = status_line(main) {
+        r.text(palette.score, 14, &line, 0.0, 40.0);
+    }
+
+    // Draw mute button in top right
+    if let Some(muted) = music_muted {
+        let mute_key = config.keys.mute.first().map_or("-", String::as_str);
+        let mute_state = if muted { "OFF" } else { "ON" };
+        let mute_text = format!("[{}] {}", mute_key, mute_state);
+        r.text(palette.score, 20, &mute_text, window_width - 100.0, 20.0);
+    }
+
+    // Draw the game elements (snake, fruit, etc.)
+    r.set_transform(board_transform(pixels, block_size));
+    main.draw(r, palette);
+    r.set_transform(pixels);
+
+    // Draw particles
+    view.particle_system.draw(r);
+
+    // Draw playback state at the bottom
+    if let Some(replay_text) = playback_line(main) {
+        r.text(palette.score, 14, &replay_text, 5.0, window_height - 8.0);
+    }
+
+    // Draw the goal of the campaign level at the bottom
+    if let Some(goal) = manager.goal_progress() {
+        r.text(palette.score, 14, &goal, 5.0, window_height - 8.0);
+    }
+
+    // Draw game over or win text if the game has ended
+    if let Some(headline) = headline(main) {
+        let half_width = r.text_width(32, headline) / 2.0;
+        r.text(
+            palette.score,
+            32,
+            headline,
+            window_width / 2.0 - half_width,
+            window_height / 2.0 - 20.0,
+        );
+
+        // Draw "Press R to Restart" text
+        let restart_text = restart_line(main, restart_key);
+        let half_width = r.text_width(16, &restart_text) / 2.0;
+        r.text(
+            palette.score,
+            16,
+            &restart_text,
+            window_width / 2.0 - half_width,
+            window_height / 2.0 + 20.0,
+        );
+
+        if manager.keeps_high_scores() {
+            let scores_key = config.keys.high_scores.first().map_or("-", String::as_str);
+            let scores_text = format!("[{}] High Scores", scores_key);
+            let half_width = r.text_width(14, &scores_text) / 2.0;
+            r.text(
+                palette.score,
+                14,
+                &scores_text,
+                window_width / 2.0 - half_width,
+                window_height / 2.0 + 45.0,
+            );
+        }
+    }
+
+    // Level select and transition screens go on top of everything
+    manager.draw_screen(r, palette, &config.keys, (window_width, window_height));
+}
+
+/// The HUD font, exiting if it can't be read.
+fn load_font(path: &Path) -> Font {
+    Font::load(path).unwrap_or_else(|err| {
+        eprintln!("Could not load font {}: {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+/// Open a window, exiting with `hint` if it can't be opened. Without a
+/// display winit panics instead of returning an error, so the panic is
+/// caught and reported the same way.
+fn open_window(settings: WindowSettings, hint: &str) -> PistonWindow {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let built = panic::catch_unwind(AssertUnwindSafe(|| settings.build::<PistonWindow>()));
+    panic::set_hook(hook);
+
+    let err = match built {
+        Ok(Ok(window)) => return window,
+        Ok(Err(err)) => err.to_string(),
+        Err(panic) => match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => panic
+                .downcast_ref::<&str>()
+                .map_or("no display", |message| *message)
+                .to_string(),
+        },
+    };
+    eprintln!("Could not open a window: {}", err);
+    eprintln!("{}", hint);
+    process::exit(1);
+}
+
+/// The HUD font for drawing in `window`, exiting if it can't be read.
+fn load_glyphs(window: &mut PistonWindow, path: &Path) -> Glyphs {
+    let context = TextureContext {
+        factory: window.factory.clone(),
+        encoder: window.factory.create_command_buffer().into(),
+    };
+    Glyphs::new(path, context, TextureSettings::new()).unwrap_or_else(|err| {
+        eprintln!("Could not load font {}: {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+/// Draw what the window shows on the CPU, see `draw_frame`, `scale`
+/// times the size of the window.
+fn render_frame(
+    font: &Font,
+    manager: &LevelManager,
+    view: &BoardView,
+    config: &Config,
+    music_muted: Option<bool>,
+    scale: f64,
+) -> Pixmap {
+    let [width, height] = view.window_size(config.block_size);
+    let size = |pixels: u32| (pixels as f64 * scale).round().max(1.0) as usize;
+    let pixmap = Pixmap::new(size(width), size(height), config.colors.background);
+    let mut renderer = SoftwareRenderer::new(pixmap, font);
+    renderer.set_transform(Transform::default().scale(scale, scale));
+    draw_frame(&mut renderer, manager, view, config, music_muted);
+    renderer.into_pixmap()
+}
+
+/// Save what the window shows as a PNG in `dir`, named after the seed and
+/// the tick like a replay.
+fn save_screenshot(
+    dir: &Path,
+    font: &Font,
+    manager: &LevelManager,
+    view: &BoardView,
+    config: &Config,
+    music_muted: bool,
+) -> io::Result<PathBuf> {
+    let game = manager.game();
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "rsnake-{}-{}.png",
+        game.get_seed(),
+        game.get_played().ticks()
+    ));
+    render_frame(font, manager, view, config, Some(music_muted), 1.0).save_png(&path)?;
+
+    Ok(path)
+}
+
+/// The game to draw without a window: the `--replay` up to `--max-ticks`,
+/// or a new game the bots play that far. Without a bot for every snake
+/// the new game has no ticks.
+fn offscreen_replay(
+    args: &Args,
+    config: &Config,
+    level: Option<&Level>,
+    bots: Vec<Option<Box<dyn Controller>>>,
+) -> Replay {
+    if let Some(path) = &args.replay {
+        let mut replay = Replay::load(path).unwrap_or_else(|err| {
+            eprintln!("Could not load replay {}: {}", path.display(), err);
+            process::exit(1);
+        });
+        replay.truncate(args.max_ticks);
+        return replay;
+    }
+
+    let rules = args.rules(&config.fruit, level);
+    let players = rules.players;
+    let mut replay = Replay::new(args.seed.unwrap_or_else(rand::random), rules);
+    let mut controllers: Vec<_> = bots.into_iter().take(players).flatten().collect();
+    if controllers.len() == players {
+        controller::play(&mut replay, &mut controllers, args.max_ticks);
+    }
+    replay
+}
+
+/// A game from `replay` to draw without a window, set up like one from
+/// the command line.
+fn offscreen_game(args: &Args, config: &Config, replay: Replay) -> LevelManager {
+    let mut game = Game::played_through(replay);
+    game.set_speed_curve(args.speed_curve(&config.speed));
+    game.set_best_of(args.best_of);
+    LevelManager::single(game)
+}
+
+/// Draw the last tick of the game to draw without a window into a PNG at
+/// `path`, see `offscreen_replay`.
+fn run_render_frame(args: &Args, config: &Config, replay: Replay, path: &Path) {
+    let manager = offscreen_game(args, config, replay);
+    let view = BoardView::new(
+        manager.game(),
+        &config.colors,
+        &config.particles,
+        config.block_size,
+    );
+    let font = load_font(&find_assets(args).join(FONT_FILE));
+
+    let frame = render_frame(&font, &manager, &view, config, None, 1.0);
+    match frame.save_png(path) {
+        Ok(()) => println!("Frame saved to {}", path.display()),
+        Err(err) => {
+            eprintln!("Could not save frame {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Write every tick of the game to draw without a window, see
+/// `offscreen_replay`, as an animated GIF at `path`. Frames last as long
+/// as the ticks they show did in the window.
+fn run_render_gif(args: &Args, config: &Config, replay: Replay, path: &Path) {
+    let palette = &config.colors;
+    let font = load_font(&find_assets(args).join(FONT_FILE));
+    let scale = args.gif_scale;
+    let new_view = |manager: &LevelManager| {
+        BoardView::new(
+            manager.game(),
+            palette,
+            &config.particles,
+            config.block_size,
+        )
+    };
+
+    // A global palette is learned from the first and the last frame, which
+    // between them have most colors of the game
+    let mut manager = offscreen_game(args, config, Replay::new(replay.seed, replay.rules.clone()));
+    let mut view = new_view(&manager);
+    let first = render_frame(&font, &manager, &view, config, None, scale);
+    let last = {
+        let manager = offscreen_game(args, config, replay.clone());
+        render_frame(&font, &manager, &new_view(&manager), config, None, scale)
+    };
+    let mut writer = GifWriter::create(path, first.get_size(), args.gif_palette, &[first, last])
+        .unwrap_or_else(|err| {
+            eprintln!("Could not write GIF {}: {}", path.display(), err);
+            process::exit(1);
+        });
+
+    let frame_every = args.frame_skip + 1;
+    let mut frames = 0;
+    let mut frame = render_frame(&font, &manager, &view, config, None, scale);
+    // Seconds the last frame drawn is shown for so far
+    let mut duration = 0.0;
+    let mut written = Ok(());
+    for tick in 0..replay.ticks() {
+        let dt = 1.0 / manager.game().get_tick_rate();
+        manager.game_mut().step(replay.inputs_at(tick));
+        manager.game_mut().update(dt);
+        view.particle_system.update(dt);
+        for event in manager.take_events() {
+            if !args.no_particles {
+                view.spawn_particles(&event, palette);
+            }
+        }
+        duration += dt;
+
+        if (tick + 1) % frame_every == 0 || tick + 1 == replay.ticks() {
+            written = writer.add_frame(&frame, duration);
+            if written.is_err() {
+                break;
+            }
+            frames += 1;
+            frame = render_frame(&font, &manager, &view, config, None, scale);
+            duration = 0.0;
+        }
+    }
+
+    // The end stays on for a moment before the GIF starts over
+    let result = written
+        .and_then(|_| writer.add_frame(&frame, GIF_END_HOLD))
+        .and_then(|_| writer.finish());
+    match result {
+        Ok(()) => println!("{} frames saved to {}", frames + 1, path.display()),
+        Err(err) => {
+            eprintln!("Could not write GIF {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Play in the terminal instead of a window, until Ctrl-C.
+fn run_terminal(args: &Args, config: &Config, controls: &Controls, mut game: Game) {
+    let palette = &config.colors;
+    let restart_key = config.keys.restart.first().map_or("-", String::as_str);
+    let (above, below) = TERMINAL_TEXT_ROWS;
+
+    let mut broadcaster = spectate_broadcaster(args);
+
+    let (mut width, mut height) = game.get_size();
+    let new_background = |game: &Game, (width, height)| {
+        Background::new(
+            width,
+            height,
+            palette,
+            &mut ChaCha8Rng::seed_from_u64(game.get_seed()),
+        )
+    };
+    let mut background = new_background(&game, (width, height));
+    let canvas = Canvas::new(width, height, above, below, palette.background);
+    let (columns, rows) = canvas.size();
+    let mut screen = Screen::open(columns, rows).unwrap_or_else(|err| {
+        eprintln!("Could not play in the terminal: {}", err);
+        process::exit(1);
+    });
+
+    game.start();
+    let mut message = String::new();
+    let mut last_frame = Instant::now();
+    let mut last_redraw = Instant::now();
+
+    'frames: loop {
+        let inputs = terminal::read_input(TERMINAL_FRAME.saturating_sub(last_frame.elapsed()))
+            .unwrap_or_else(|err| {
+                message = format!("Could not read the keyboard: {}", err);
+                vec![Input::Quit]
+            });
+        for input in inputs {
+            let key = match input {
+                Input::Key(key) => key,
+                Input::Quit => break 'frames,
+                Input::Resize => {
+                    screen.invalidate();
+                    continue;
+                }
+            };
+            let actions: Vec<Action> = if game.get_match().is_some() {
+                controls.versus_actions(key).collect()
+            } else {
+                controls.actions(key).collect()
+            };
+            for action in actions {
+                game.key_down(action);
+            }
+        }
+        if last_frame.elapsed() < TERMINAL_FRAME {
+            continue;
+        }
+        let dt = last_frame.elapsed().as_secs_f64();
+        last_frame = Instant::now();
+
+        for event in game.take_events() {
+            let ended = matches!(event, Event::GameOver | Event::Won | Event::LevelCleared);
+            if ended && !game.is_playback() && !game.is_spectating() {
+                message = match game.save_replay(Path::new(REPLAY_DIR)) {
+                    Ok(path) => format!("Replay saved to {}", path.display()),
+                    Err(err) => format!("Could not save replay: {}", err),
+                };
+            }
+        }
+        if let Some(broadcaster) = &mut broadcaster {
+            let best_of = game.get_match().map(Match::get_best_of);
+            broadcaster.sync(game.get_played_tick(), best_of, || game.get_played());
+        }
+        if let Some((_, ConnectionStatus::Disconnected(reason), _)) = game.get_connection() {
+            message = format!("Disconnected: {}", reason);
+        }
+        if let Some(reason) = game.get_stream_ended() {
+            message = format!("Stream ended: {}", reason);
+        }
+
+        // The next game being watched may be on another board
+        if game.get_size() != (width, height) {
+            (width, height) = game.get_size();
+            background = new_background(&game, (width, height));
+            let (columns, rows) =
+                Canvas::new(width, height, above, below, palette.background).size();
+            if let Err(err) = screen.resize(columns, rows) {
+                message = format!("Could not play in the terminal: {}", err);
+                break;
+            }
+        }
+        // Bots and the network may print over the board, so now and then
+        // everything is drawn again
+        if last_redraw.elapsed() >= TERMINAL_REDRAW {
+            screen.invalidate();
+            last_redraw = Instant::now();
+        }
+
+        let mut canvas = Canvas::new(width, height, above, below, palette.background);
+        canvas.set_transform(canvas.board_transform());
+        background.draw(&mut canvas);
+        game.draw(&mut canvas, palette);
+        canvas.set_transform(Transform::default());
+
+        let (columns, _) = canvas.size();
+        canvas.print(0, 0, &score_line(&game), palette.score);
+        canvas.print(columns.saturating_sub(7), 0, "^C QUIT", palette.score);
+        if let Some(line) = status_line(&game) {
+            canvas.print(0, 1, &line, palette.score);
+        }
+        if let Some(headline) = headline(&game) {
+            let middle = canvas.board_row(height / 2);
+            canvas.print_centered(middle - 1, headline, palette.score);
+            canvas.print_centered(middle + 1, &restart_line(&game, restart_key), palette.score);
+        }
+        let bottom = playback_line(&game).unwrap_or_else(|| message.clone());
+        canvas.print(0, canvas.board_row(height), &bottom, palette.score);
+
+        if let Err(err) = screen.present(&canvas) {
+            message = format!("Could not draw: {}", err);
+            break;
+        }
+        game.update(dt);
+    }
+
+    drop(screen);
+    if !message.is_empty() {
+        println!("{}", message);
+    }
+}
+
+/// Play an online match with a bot without a window, printing every round
+/// and saving its replay.
+fn run_headless_online(args: &Args, mut session: Session, mut bot: Box<dyn Controller>) {
+    let mut versus = Match::new(session.get_best_of());
+    netplay::play(&mut session, bot.as_mut(), args.max_ticks, |session| {
+        versus.record(session.get_round_winner());
+        let state = session.get_confirmed();
+        let result = match session.get_round_winner() {
+            Some(winner) => format!("P{} wins", winner + 1),
+            None => "draw".to_string(),
+        };
+        let wins = versus.get_wins();
+        println!(
+            "round {}: {} after {} ticks, match {}-{}",
+            versus.get_rounds(),
+            result,
+            state.get_tick(),
+            wins[0],
+            wins[1]
+        );
 
-const WINDOW_TITLE: &'static str = "rsnake";
-const WIDTH: u32 = 25;
-const HEIGHT: u32 = 25;
+        let replay = session.get_replay();
+        let path = Path::new(REPLAY_DIR).join(format!(
+            "rsnake-{}-{}.replay",
+            replay.seed,
+            state.get_tick()
+        ));
+        match fs::create_dir_all(REPLAY_DIR).and_then(|_| replay.save(&path)) {
+            Ok(()) => println!("Replay saved to {}", path.display()),
+            Err(err) => eprintln!("Could not save replay: {}", err),
+        }
+
+        !versus.is_over()
+    });
+
+    match (versus.get_winner(), session.get_status()) {
+        (Some(winner), _) => println!("P{} takes the match", winner + 1),
+        (None, ConnectionStatus::Disconnected(reason)) => println!("Disconnected: {}", reason),
+        (None, _) => println!("stopped after {} ticks", session.get_confirmed().get_tick()),
+    }
+    println!("{} rollbacks", session.get_rollbacks());
+}
+
+/// Connect to the Battlesnake servers on the command line and set up a
+/// game between them, exiting if one can't be reached.
+fn arena_game(args: &Args) -> ArenaGame {
+    let timeout = args.arena_timeout();
+    let servers = args
+        .battlesnake
+        .iter()
+        .map(|url| {
+            Server::connect(url, None, timeout).unwrap_or_else(|err| {
+                eprintln!("Could not reach Battlesnake server {}", err);
+                process::exit(1);
+            })
+        })
+        .collect();
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    ArenaGame::new(servers, args.arena_rules(), seed, timeout)
+}
+
+/// Watch a game between Battlesnake servers. The servers are asked for
+/// their moves on a separate thread, at most one turn per tick.
+fn run_arena(args: &Args, config: &Config, controls: &Controls, mut game: ArenaGame) {
+    let assets = find_assets(args);
+    let palette = &config.colors;
+    let block_size = config.block_size;
+    let restart_key = config.keys.restart.first().map_or("-", String::as_str);
+    let turn_time = Duration::from_secs_f64(1.0 / args.tick_rate.unwrap_or(config.speed.start));
+
+    let rules = game.get_state().get_rules().clone();
+    let (width, height) = (rules.width, rules.height);
+    let default_colors = [palette.snake, palette.snake2];
+    let colors: Vec<Color> = game
+        .get_servers()
+        .iter()
+        .enumerate()
+        .map(|(i, server)| {
+            server
+                .get_color()
+                .unwrap_or(default_colors[i % default_colors.len()])
+        })
+        .collect();
+    let names: Vec<String> = game
+        .get_servers()
+        .iter()
+        .map(|server| server.get_name().to_uppercase())
+        .collect();
+    let background = Background::new(
+        width,
+        height,
+        palette,
+        &mut ChaCha8Rng::seed_from_u64(game.get_seed()),
+    );
+
+    // Before the servers are asked for anything, in case there is no display
+    let window_size = [
+        blocks_in_pixels(width, block_size),
+        blocks_in_pixels(height, block_size),
+    ];
+    let mut window = open_window(
+        WindowSettings::new(format!("{} - Battlesnake", WINDOW_TITLE), window_size)
+            .resizable(false),
+        "Without a display, run the game with --headless",
+    );
+    let mut glyphs = load_glyphs(&mut window, &assets.join(FONT_FILE));
+
+    // The state after every turn, with the time each server took for it
+    let snapshot = |game: &ArenaGame| {
+        let latencies: Vec<Option<Duration>> =
+            game.get_servers().iter().map(Server::get_latency).collect();
+        (game.get_state().clone(), latencies)
+    };
+    let (states, state_receiver) = mpsc::channel();
+    let (restarts, restart_receiver) = mpsc::channel();
+    let mut current = snapshot(&game);
+    thread::spawn(move || loop {
+        game.start();
+        while !game.is_over() {
+            let started = Instant::now();
+            game.turn();
+            if states.send(snapshot(&game)).is_err() {
+                return;
+            }
+            if let Some(rest) = turn_time.checked_sub(started.elapsed()) {
+                thread::sleep(rest);
+            }
+        }
+        println!("seed {}: {}", game.get_seed(), game.summary());
+
+        match restart_receiver.recv() {
+            Ok(seed) => {
+                game.restart(seed);
+                if states.send(snapshot(&game)).is_err() {
+                    return;
+                }
+            }
+            Err(_) => return,
+        }
+    });
+
+    while let Some(event) = window.next() {
+        if let Some(Button::Keyboard(key)) = event.press_args() {
+            if controls
+                .actions(key)
+                .any(|action| action == Action::Restart)
+                && current.0.is_over()
+            {
+                let _ = restarts.send(rand::random());
+            }
+        }
+        while let Ok(state) = state_receiver.try_recv() {
+            current = state;
+        }
+        let (state, latencies) = &current;
+
+        window.draw_2d(&event, |ctx, g, device| {
+            clear(palette.background, g);
+            let mut renderer = PistonRenderer::new(ctx, g, &mut glyphs);
+            let r: &mut dyn Renderer = &mut renderer;
+            let pixels = r.get_transform();
+
+            r.set_transform(board_transform(pixels, block_size));
+            background.draw(r);
+            state.draw(r, palette, &colors);
+            r.set_transform(pixels);
+
+            // The turn and every snake's health and response time
+            r.text(
+                palette.score,
+                16,
+                &format!("TURN {}", state.get_turn()),
+                5.0,
+                18.0,
+            );
+            for (i, snake) in state.get_snakes().iter().enumerate() {
+                let line = match (snake.eliminated, latencies[i]) {
+                    (Some(_), _) => format!("{}  OUT", names[i]),
+                    (None, Some(latency)) => format!(
+                        "{}  HP {}  {} MS",
+                        names[i],
+                        snake.health,
+                        latency.as_millis()
+                    ),
+                    (None, None) => format!("{}  HP {}  TIMEOUT", names[i], snake.health),
+                };
+                let mut color = colors[i];
+                if !snake.is_alive() {
+                    color[3] *= 0.5;
+                }
+                r.text(color, 12, &line, 5.0, 36.0 + 14.0 * i as f64);
+            }
+
+            if state.is_over() {
+                let window_width = blocks_in_pixels(width, block_size) as f64;
+                let window_height = blocks_in_pixels(height, block_size) as f64;
+                let headline = match state.get_winner() {
+                    Some(winner) => format!("{} WINS!", names[winner]),
+                    None if names.len() > 1 => "DRAW".to_string(),
+                    None => "GAME OVER".to_string(),
+                };
+                let half_width = r.text_width(32, &headline) / 2.0;
+                r.text(
+                    palette.score,
+                    32,
+                    &headline,
+                    window_width / 2.0 - half_width,
+                    window_height / 2.0 - 20.0,
+                );
+                let restart_text = format!("Press {} for a new game", restart_key);
+                let half_width = r.text_width(16, &restart_text) / 2.0;
+                r.text(
+                    palette.score,
+                    16,
+                    &restart_text,
+                    window_width / 2.0 - half_width,
+                    window_height / 2.0 + 20.0,
+                );
+            }
+
+            glyphs.factory.encoder.flush(device);
+        });
+    }
+}
 
 fn main() {
-    let size = [blocks_in_pixels(WIDTH), blocks_in_pixels(HEIGHT)];
+    let args = Args::parse_and_validate();
+
+    let config = match &args.config {
+        Some(path) => Config::load(path),
+        None => Config::load_default(),
+    };
+    let config = config.unwrap_or_else(|err| {
+        eprintln!("Invalid config file {}", err);
+        process::exit(1);
+    });
+    let controls = Controls::new(&config.keys).unwrap_or_else(|err| {
+        eprintln!("Invalid key bindings in config file: {}", err);
+        process::exit(1);
+    });
+    let palette = &config.colors;
+    let block_size = config.block_size;
+
+    // A level is either a path to a file or the name of one in the assets
+    let level = args.level.as_ref().map(|name| {
+        let path = Path::new(name);
+        let path = if path.is_file() {
+            path.to_path_buf()
+        } else {
+            level_path(&find_assets(&args), name)
+        };
+        let level = Level::load(&path).unwrap_or_else(|err| {
+            eprintln!("Could not load level {}: {}", path.display(), err);
+            process::exit(1);
+        });
+        if let Err(err) = level.check_start(args.length) {
+            eprintln!("Level {} does not fit --length: {}", path.display(), err);
+            process::exit(1);
+        }
+        level
+    });
+
+    if !args.battlesnake.is_empty() {
+        let mut game = arena_game(&args);
+        if args.headless {
+            game.play(args.max_ticks.min(u32::MAX as u64) as u32);
+            println!("seed {}: {}", game.get_seed(), game.summary());
+        } else {
+            run_arena(&args, &config, &controls, game);
+        }
+        return;
+    }
+
+    let bots = args.bots().unwrap_or_else(|err| {
+        eprintln!("Could not start bot: {}", err);
+        process::exit(1);
+    });
 
-    let mut window: PistonWindow = WindowSettings::new(WINDOW_TITLE, size)
-        .resizable(false)
-        .build()
-        .unwrap();
+    let session = online_session(&args, args.rules(&config.fruit, level.as_ref()));
 
-    let assets = find_folder::Search::ParentsThenKids(3, 3)
-        .for_folder("assets")
-        .unwrap();
-    let ref font = assets.join("retro-gaming.ttf");
+    if args.headless {
+        match session {
+            Some(session) => {
+                // `--headless` requires a bot for the local snake
+                let bot = bots.into_iter().next().flatten();
+                if let Some(bot) = bot {
+                    run_headless_online(&args, session, bot);
+                }
+            }
+            None => run_headless(&args, args.rules(&config.fruit, level.as_ref()), bots),
+        }
+        return;
+    }
+
+    if let Some(path) = &args.render_frame {
+        let replay = offscreen_replay(&args, &config, level.as_ref(), bots);
+        run_render_frame(&args, &config, replay, path);
+        return;
+    }
+    if let Some(path) = &args.render_gif {
+        let replay = offscreen_replay(&args, &config, level.as_ref(), bots);
+        run_render_gif(&args, &config, replay, path);
+        return;
+    }
+
+    if args.terminal {
+        let game = single_game(&args, &config, level.as_ref(), session, bots);
+        run_terminal(&args, &config, &controls, game);
+        return;
+    }
+
+    let assets = find_assets(&args);
+    let online = session.is_some();
+    let watching = args.watch.is_some();
+    let mut manager = if args.campaign {
+        let campaign = Campaign::load(&assets).unwrap_or_else(|err| {
+            eprintln!("Could not load campaign {}", err);
+            process::exit(1);
+        });
+        for stage in &campaign.stages {
+            if let Err(err) = stage.level.check_start(args.length) {
+                eprintln!(
+                    "Campaign level {} does not fit --length: {}",
+                    stage.level.name, err
+                );
+                process::exit(1);
+            }
+        }
+        let progress_path = Progress::default_path();
+        let progress = progress_path
+            .as_deref()
+            .map(Progress::load)
+            .unwrap_or_default();
+        let setup = GameSetup {
+            start_length: args.length,
+            fruit_weights: config.fruit.clone(),
+            speed_curve: args.speed_curve(&config.speed),
+            input_depth: args.input_buffer.unwrap_or(config.game.input_buffer),
+        };
+        LevelManager::campaign(campaign, progress, progress_path, setup)
+    } else {
+        let mut manager =
+            LevelManager::single(single_game(&args, &config, level.as_ref(), session, bots));
+        let top = config.game.high_scores;
+        let (scores, scores_path) = match HighScores::default_path() {
+            Some(path) => match HighScores::load(&path, top) {
+                Ok(scores) => (scores, Some(path)),
+                // Saving would replace the scores that couldn't be read
+                Err(err) => {
+                    eprintln!(
+                        "Could not read high scores {}, not saving any: {}",
+                        path.display(),
+                        err
+                    );
+                    (HighScores::new(top), None)
+                }
+            },
+            None => (HighScores::new(top), None),
+        };
+        manager.keep_high_scores(scores, scores_path, score_mode(&args, level.as_ref()));
+        manager
+    };
+
+    let mut view = BoardView::new(manager.game(), palette, &config.particles, block_size);
+    let level_name = manager
+        .level_name()
+        .or_else(|| level.as_ref().map(|level| level.name.as_str()))
+        .or(if online { Some("online") } else { None })
+        .or(if watching { Some("watching") } else { None });
+    let mut window = open_window(
+        WindowSettings::new(window_title(level_name), view.window_size(block_size))
+            .resizable(false),
+        "Without a display, play in the terminal with --terminal",
+    );
+
+    let font_path = assets.join(FONT_FILE);
+    let font = load_font(&font_path);
     let _factory = window.factory.clone();
-    let mut glyphs = Glyphs::new(
-        font,
-        TextureContext {
-            factory: window.factory.clone(),
-            encoder: window.factory.create_command_buffer().into(),
-        },
-        TextureSettings::new(),
-    )
-    .unwrap();
+    let mut glyphs = load_glyphs(&mut window, &font_path);
 
     // Set up audio
     let (_stream, stream_handle) = OutputStream::try_default().unwrap();
@@ -63,118 +1221,115 @@ fn main() {
     let music_file = BufReader::new(File::open(assets.join("snakejazz.ogg")).unwrap());
     let music_source = Decoder::new(music_file).unwrap().repeat_infinite();
     music_sink.append(music_source);
-    music_sink.set_volume(0.5);
-    let mut music_muted = false;
+    let music_volume = config.audio.music_volume;
+    let mut music_muted = args.mute;
+    music_sink.set_volume(if music_muted { 0.0 } else { music_volume });
 
-    // Sound effect sink for apple
+    // Sound effect sink for fruit
     let sfx_sink = Sink::try_new(&stream_handle).unwrap();
-    sfx_sink.set_volume(0.8);
+    sfx_sink.set_volume(config.audio.sfx_volume);
 
-    let mut main: Game = Game::new(WIDTH, HEIGHT);
-    let background = Background::new(WIDTH, HEIGHT);
-    let mut particle_system = ParticleSystem::new();
-    main.start();
+    // Bound before starting, so spectators can join from the first tick
+    let mut broadcaster = spectate_broadcaster(&args);
+
+    manager.start();
+    let mut disconnect_reported = false;
 
     while let Some(event) = window.next() {
+        // Typing a name for the high scores
+        if let Some(text) = event.text_args() {
+            manager.type_text(&text);
+        }
         if let Some(Button::Keyboard(key)) = event.press_args() {
-            // Toggle music mute with M key
-            if key == Key::M {
-                music_muted = !music_muted;
-                if music_muted {
-                    music_sink.set_volume(0.0);
-                } else {
-                    music_sink.set_volume(0.5);
+            if key == Key::Backspace {
+                manager.erase_text();
+            }
+            let actions: Vec<Action> = if manager.game().get_match().is_some() {
+                controls.versus_actions(key).collect()
+            } else {
+                controls.actions(key).collect()
+            };
+            for action in actions {
+                // Toggle music mute with M key
+                if action == Action::Mute && !manager.is_typing() {
+                    music_muted = !music_muted;
+                    if music_muted {
+                        music_sink.set_volume(0.0);
+                    } else {
+                        music_sink.set_volume(music_volume);
+                    }
+                }
+                if action == Action::Screenshot {
+                    let dir = Path::new(SCREENSHOT_DIR);
+                    match save_screenshot(dir, &font, &manager, &view, &config, music_muted) {
+                        Ok(path) => println!("Screenshot saved to {}", path.display()),
+                        Err(err) => eprintln!("Could not save screenshot: {}", err),
+                    }
                 }
+                manager.key_down(action);
             }
-            main.key_down(key);
         }
 
-        // Check if apple was eaten
-        if let Some(pos) = main.take_apple_eaten() {
-            // Play sound effect
-            if let Ok(file) = File::open(assets.join("appleobtained.ogg")) {
-                let reader = BufReader::new(file);
-                if let Ok(source) = Decoder::new(reader) {
-                    sfx_sink.append(source);
+        // A new campaign level may be on a board of a different size, as
+        // may the next game being watched
+        if manager.take_level_changed() || manager.game().get_size() != (view.width, view.height) {
+            view = BoardView::new(manager.game(), palette, &config.particles, block_size);
+            window.set_size(view.window_size(block_size));
+            window.set_title(window_title(manager.level_name()));
+        }
+        // Check if fruit was eaten
+        for game_event in manager.take_events() {
+            view.spawn_particles(&game_event, palette);
+            match game_event {
+                Event::FruitEaten(fruit) => play_fruit_sound(&sfx_sink, &assets, fruit.kind),
+                Event::FruitExpired(_) | Event::Teleported { .. } => {}
+                Event::GameOver | Event::Won | Event::LevelCleared
+                    if !manager.game().is_playback() && !manager.game().is_spectating() =>
+                {
+                    match manager.game().save_replay(Path::new(REPLAY_DIR)) {
+                        Ok(path) => println!("Replay saved to {}", path.display()),
+                        Err(err) => eprintln!("Could not save replay: {}", err),
+                    }
                 }
+                Event::GameOver | Event::Won | Event::LevelCleared => {}
             }
+        }
 
-            // Spawn particles at apple position
-            particle_system.spawn_at(pos.x, pos.y, colors::FRUIT);
+        let main = manager.game();
+
+        if let Some(broadcaster) = &mut broadcaster {
+            let best_of = main.get_match().map(Match::get_best_of);
+            broadcaster.sync(main.get_played_tick(), best_of, || main.get_played());
         }
 
-        window.draw_2d(&event, |ctx, g, device| {
-            clear(colors::BACKGROUND, g);
-            background.draw(&ctx, g);
-
-            // Draw the score
-            let score_str = main.get_score().to_string();
-            text::Text::new_color(colors::SCORE, 20)
-                .draw(
-                    score_str.as_ref(),
-                    &mut glyphs,
-                    &ctx.draw_state,
-                    ctx.transform.trans(0.0, 20.0),
-                    g,
-                )
-                .unwrap();
-
-            // Draw mute button in top right
-            let window_width = blocks_in_pixels(WIDTH) as f64;
-            let mute_text = if music_muted { "[M] OFF" } else { "[M] ON" };
-            text::Text::new_color(colors::SCORE, 20)
-                .draw(
-                    mute_text,
-                    &mut glyphs,
-                    &ctx.draw_state,
-                    ctx.transform.trans(window_width - 100.0, 20.0),
-                    g,
-                )
-                .unwrap();
-
-            // Draw the game elements (snake, fruit, etc.)
-            main.draw(ctx, g);
-
-            // Draw particles
-            particle_system.draw(&ctx, g);
-
-            // Draw game over text if the game is over
-            if main.get_status() == GameStatus::GameOver {
-                let window_width = blocks_in_pixels(WIDTH) as f64;
-                let window_height = blocks_in_pixels(HEIGHT) as f64;
-
-                // Draw "GAME OVER" text
-                text::Text::new_color(colors::SCORE, 32)
-                    .draw(
-                        "GAME OVER",
-                        &mut glyphs,
-                        &ctx.draw_state,
-                        ctx.transform
-                            .trans(window_width / 2.0 - 90.0, window_height / 2.0 - 20.0),
-                        g,
-                    )
-                    .unwrap();
-
-                // Draw "Press R to Restart" text
-                text::Text::new_color(colors::SCORE, 16)
-                    .draw(
-                        "Press R to Restart",
-                        &mut glyphs,
-                        &ctx.draw_state,
-                        ctx.transform
-                            .trans(window_width / 2.0 - 80.0, window_height / 2.0 + 20.0),
-                        g,
-                    )
-                    .unwrap();
+        // Tell why an online match or the game being watched stopped, once
+        let connection = main.get_connection();
+        let stream_ended = main.get_stream_ended();
+        if let Some((_, ConnectionStatus::Disconnected(reason), _)) = &connection {
+            if !disconnect_reported {
+                eprintln!("Disconnected: {}", reason);
+                disconnect_reported = true;
+            }
+        }
+        if let Some(reason) = &stream_ended {
+            if !disconnect_reported {
+                eprintln!("Stream ended: {}", reason);
+                disconnect_reported = true;
             }
+        }
+
+        window.draw_2d(&event, |ctx, g, device| {
+            clear(palette.background, g);
+            let mut renderer = PistonRenderer::new(ctx, g, &mut glyphs);
+            draw_frame(&mut renderer, &manager, &view, &config, Some(music_muted));
 
             // Update glyphs texture context after drawing
             glyphs.factory.encoder.flush(device);
         });
 
         event.update(|arg| {
-            main.update(arg.dt);
-            particle_system.update(arg.dt);
+            manager.game_mut().update(arg.dt);
+            view.particle_system.update(arg.dt);
         });
     }
 }
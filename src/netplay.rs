@@ -0,0 +1,772 @@
+//! Versus over the network, with input delay and rollback.
+//!
+//! One instance hosts, the other connects over TCP. Both run the same
+//! deterministic `sim` and only exchange their own snake's input for every
+//! tick, as JSON lines:
+//!
+//! ```text
+//! {"type":"hello","version":1,"replay":"rsnake-replay 1\nseed 42\n...","best_of":3,"delay":2}
+//! {"type":"input","round":0,"tick":17,"input":"L"}
+//! {"type":"ping","id":3}
+//! {"type":"pong","id":3}
+//! {"type":"restart","round":1}
+//! {"type":"bye"}
+//! ```
+//!
+//! The host sends `hello` first, with the seed and rules as the header of a
+//! replay file, the match length and the input delay. Inputs use the
+//! characters of the replay format (`.` for none).
+//!
+//! An input pressed on tick `t` is played on tick `t + delay`, which gives
+//! it time to reach the other side. When the other side's input for a tick
+//! isn't there yet, the game predicts it (no turn) and carries on; once it
+//! arrives and differs, the game rolls back to the last tick both inputs
+//! were known for and plays the ticks since again with the real ones. The
+//! prediction never runs more than `MAX_ROLLBACK` ticks ahead, past that the
+//! game waits for the other side.
+//!
+//! Sounds and particles come from the predicted ticks, the end of a round
+//! only from the confirmed ones, so a round never ends on a misprediction.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::controller::{BoardView, Controller};
+use crate::physics::Direction;
+use crate::replay::{char_to_input, input_to_char, Replay};
+use crate::sim::{self, Event, GameState, GameStatus, Rules};
+use crate::versus::check_best_of;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7878;
+/// Ticks between pressing a key and the turn, at 10 ticks per second
+/// enough for a ping of about 200 ms without rollbacks
+pub const DEFAULT_INPUT_DELAY: u64 = 2;
+pub const MAX_INPUT_DELAY: u64 = 8;
+/// Ticks the prediction may run ahead of the other side's inputs
+pub const MAX_ROLLBACK: u64 = 8;
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Silence after which the other side counts as gone
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Hello {
+        version: u32,
+        replay: String,
+        best_of: u32,
+        delay: u64,
+    },
+    Input {
+        round: u32,
+        tick: u64,
+        input: char,
+    },
+    Ping {
+        id: u64,
+    },
+    Pong {
+        id: u64,
+    },
+    Restart {
+        round: u32,
+    },
+    Bye,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionStatus {
+    Connected,
+    /// The other side's inputs are too far behind to keep predicting
+    Waiting,
+    /// The other side left or the connection broke, with the reason
+    Disconnected(String),
+}
+
+pub struct Session {
+    stream: TcpStream,
+    // Messages from the other side, read on a separate thread; an error
+    // ends the connection
+    messages: Receiver<Result<Message, String>>,
+    // Index of the local player, the host is player 0
+    local: usize,
+    delay: u64,
+    best_of: u32,
+    seed: u64,
+    rules: Rules,
+    round: u32,
+    // Inputs of both players by tick, starting with `delay` empty ones
+    local_inputs: Vec<Option<Direction>>,
+    remote_inputs: Vec<Option<Direction>>,
+    // The game up to the last tick both inputs are known for, and the
+    // predicted game up to the current tick
+    confirmed: GameState,
+    predicted: GameState,
+    // The confirmed inputs of the round
+    replay: Replay,
+    status: ConnectionStatus,
+    ping: Option<Duration>,
+    // Id and time of the ping waiting for its pong
+    ping_sent: Option<(u64, Instant)>,
+    next_ping: u64,
+    last_ping: Instant,
+    last_message: Instant,
+    rollbacks: u64,
+    restarted: bool,
+    // A new round the other side started, begun on the next poll
+    pending_restart: Option<u32>,
+    // Who won the last round that ended
+    round_winner: Option<usize>,
+}
+
+fn io_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Check the match length and input delay of a hello, the same limits as
+/// on the command line.
+fn check_hello(best_of: u32, delay: u64) -> io::Result<()> {
+    check_best_of(best_of).map_err(|err| io_error(format!("best of {}: {}", best_of, err)))?;
+    if delay > MAX_INPUT_DELAY {
+        return Err(io_error(format!(
+            "input delay {} is more than {} ticks",
+            delay, MAX_INPUT_DELAY
+        )));
+    }
+
+    Ok(())
+}
+
+/// Write `message` as one line of JSON.
+pub(crate) fn send(stream: &mut TcpStream, message: &impl Serialize) -> io::Result<()> {
+    let line = serde_json::to_string(message).map_err(io::Error::other)?;
+    writeln!(stream, "{}", line)?;
+    stream.flush()
+}
+
+/// Read messages from `stream` on a new thread, one line of JSON each.
+pub(crate) fn spawn_reader<M: DeserializeOwned + Send + 'static>(
+    stream: TcpStream,
+) -> Receiver<Result<M, String>> {
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let message = match line {
+                Ok(line) => serde_json::from_str(&line)
+                    .map_err(|err| format!("invalid message '{}': {}", line, err)),
+                Err(err) => Err(err.to_string()),
+            };
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    messages
+}
+
+impl Session {
+    /// Wait for a player to connect on `port` and start a match with them.
+    /// The host plays the first snake.
+    pub fn host(port: u16, rules: Rules, seed: u64, best_of: u32, delay: u64) -> io::Result<Self> {
+        check_hello(best_of, delay)?;
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let (mut stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+
+        let mut rules = rules;
+        rules.players = 2;
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+            replay: Replay::new(seed, rules.clone()).serialize(),
+            best_of,
+            delay,
+        };
+        send(&mut stream, &hello)?;
+        let messages = spawn_reader(stream.try_clone()?);
+
+        Ok(Self::new(stream, messages, 0, rules, seed, best_of, delay))
+    }
+
+    /// Join the match hosted at `addr` (`host:port`), as the second snake.
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let messages = spawn_reader(stream.try_clone()?);
+
+        let hello = messages
+            .recv_timeout(DISCONNECT_TIMEOUT)
+            .map_err(|_| io_error("no hello from the host".to_string()))?
+            .map_err(io_error)?;
+        match hello {
+            Message::Hello {
+                version,
+                replay,
+                best_of,
+                delay,
+            } => {
+                if version != PROTOCOL_VERSION {
+                    let _ = send(&mut stream, &Message::Bye);
+                    return Err(io_error(format!(
+                        "the host speaks version {}, expected {}",
+                        version, PROTOCOL_VERSION
+                    )));
+                }
+                if let Err(err) = check_hello(best_of, delay) {
+                    let _ = send(&mut stream, &Message::Bye);
+                    return Err(err);
+                }
+                let replay = Replay::parse(&replay)?;
+                Ok(Self::new(
+                    stream,
+                    messages,
+                    1,
+                    replay.rules,
+                    replay.seed,
+                    best_of,
+                    delay,
+                ))
+            }
+            other => Err(io_error(format!("expected hello, got {:?}", other))),
+        }
+    }
+
+    fn new(
+        stream: TcpStream,
+        messages: Receiver<Result<Message, String>>,
+        local: usize,
+        rules: Rules,
+        seed: u64,
+        best_of: u32,
+        delay: u64,
+    ) -> Self {
+        let now = Instant::now();
+        let mut session = Self {
+            stream,
+            messages,
+            local,
+            delay,
+            best_of,
+            seed,
+            confirmed: GameState::new(rules.clone(), seed),
+            predicted: GameState::new(rules.clone(), seed),
+            replay: Replay::new(seed, rules.clone()),
+            rules,
+            round: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            status: ConnectionStatus::Connected,
+            ping: None,
+            ping_sent: None,
+            next_ping: 0,
+            last_ping: now,
+            last_message: now,
+            rollbacks: 0,
+            restarted: false,
+            pending_restart: None,
+            round_winner: None,
+        };
+        session.start_round(0);
+        session
+    }
+
+    /// Seed of the round, every round gets its own.
+    fn round_seed(&self, round: u32) -> u64 {
+        self.seed.wrapping_add(round as u64)
+    }
+
+    fn start_round(&mut self, round: u32) {
+        let seed = self.round_seed(round);
+        self.round = round;
+        self.confirmed = GameState::new(self.rules.clone(), seed);
+        self.predicted = self.confirmed.clone();
+        self.replay = Replay::new(seed, self.rules.clone());
+        // Nobody can turn before the first inputs arrive
+        self.local_inputs = vec![None; self.delay as usize];
+        self.remote_inputs = vec![None; self.delay as usize];
+    }
+
+    /// Index of the local player's snake.
+    pub fn get_player(&self) -> usize {
+        self.local
+    }
+
+    /// The game up to the current tick, with predicted inputs for the
+    /// other side.
+    pub fn get_state(&self) -> &GameState {
+        &self.predicted
+    }
+
+    /// The direction the local snake will be moving in once the inputs
+    /// sent but not played yet are, which new turns have to be checked
+    /// against. `None` if there is no local snake.
+    pub fn get_heading(&self) -> Option<Direction> {
+        let snake = self.predicted.get_players().get(self.local)?.get_snake();
+        let pending = self
+            .local_inputs
+            .iter()
+            .skip(self.predicted.get_tick() as usize);
+        // Played like `Snake::set_dir` will, reversals are ignored
+        let heading = pending.flatten().fold(snake.get_dir(), |heading, &dir| {
+            if dir == heading.opposite() {
+                heading
+            } else {
+                dir
+            }
+        });
+        Some(heading)
+    }
+
+    /// The input that has the local snake move in `dir`, none if it will
+    /// be heading that way already.
+    pub fn input_for(&self, dir: Direction) -> Option<Direction> {
+        if self.get_heading() == Some(dir) {
+            None
+        } else {
+            Some(dir)
+        }
+    }
+
+    /// The game up to the last tick both inputs are known for.
+    pub fn get_confirmed(&self) -> &GameState {
+        &self.confirmed
+    }
+
+    /// The confirmed inputs of the current round.
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn get_best_of(&self) -> u32 {
+        self.best_of
+    }
+
+    pub fn get_status(&self) -> &ConnectionStatus {
+        &self.status
+    }
+
+    /// Round trip time of the last ping, `None` until the first answer.
+    pub fn get_ping(&self) -> Option<Duration> {
+        self.ping
+    }
+
+    /// How often a late input changed the past.
+    pub fn get_rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !matches!(self.status, ConnectionStatus::Disconnected(_))
+    }
+
+    /// Who won the last round that ended, `None` for a draw.
+    pub fn get_round_winner(&self) -> Option<usize> {
+        self.round_winner
+    }
+
+    /// Whether the other side started a new round since the last call.
+    pub fn take_restarted(&mut self) -> bool {
+        std::mem::take(&mut self.restarted)
+    }
+
+    fn disconnect(&mut self, reason: String) {
+        if self.is_connected() {
+            self.status = ConnectionStatus::Disconnected(reason);
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        if !self.is_connected() {
+            return;
+        }
+        if let Err(err) = send(&mut self.stream, message) {
+            self.disconnect(err.to_string());
+        }
+    }
+
+    /// Handle everything the other side sent and keep the connection
+    /// alive. Returns the end of the round once it is confirmed.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if let Some(round) = self.pending_restart.take() {
+            if round > self.round {
+                self.start_round(round);
+                self.restarted = true;
+            }
+        }
+
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(Ok(message)) => message,
+                Ok(Err(err)) => {
+                    self.disconnect(err);
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnect("connection closed".to_string());
+                    break;
+                }
+            };
+            self.last_message = Instant::now();
+
+            match message {
+                Message::Input { round, tick, input } if round == self.round => {
+                    if tick != self.remote_inputs.len() as u64 {
+                        self.disconnect(format!("input for tick {} out of order", tick));
+                        break;
+                    }
+                    match char_to_input(input) {
+                        Some(input) => self.receive_input(input),
+                        None => {
+                            self.disconnect(format!("invalid input '{}'", input));
+                            break;
+                        }
+                    }
+                }
+                // Left over from a round that already ended here
+                Message::Input { .. } => {}
+                Message::Ping { id } => self.send(&Message::Pong { id }),
+                Message::Pong { id } => {
+                    if let Some((sent_id, sent)) = self.ping_sent {
+                        if id == sent_id {
+                            self.ping = Some(sent.elapsed());
+                            self.ping_sent = None;
+                        }
+                    }
+                }
+                Message::Restart { round } => {
+                    // Everything the other side needed to see the end of
+                    // the round came before this. The new round starts on
+                    // the next poll, so the caller gets to see how this one
+                    // ended.
+                    events.extend(self.confirm());
+                    if round > self.round {
+                        self.pending_restart = Some(round);
+                    }
+                    break;
+                }
+                Message::Bye => self.disconnect("the other player left".to_string()),
+                Message::Hello { .. } => {
+                    self.disconnect("unexpected hello".to_string());
+                }
+            }
+        }
+
+        events.extend(self.confirm());
+        if !self.is_connected() {
+            return events;
+        }
+        if self.last_message.elapsed() > DISCONNECT_TIMEOUT {
+            self.disconnect("connection timed out".to_string());
+            return events;
+        }
+        if self.last_ping.elapsed() >= PING_INTERVAL {
+            self.last_ping = Instant::now();
+            let id = self.next_ping;
+            self.next_ping += 1;
+            self.ping_sent = Some((id, Instant::now()));
+            self.send(&Message::Ping { id });
+        }
+
+        self.status = if self.can_advance() || self.predicted.get_status() != GameStatus::Playing {
+            ConnectionStatus::Connected
+        } else {
+            ConnectionStatus::Waiting
+        };
+
+        events
+    }
+
+    fn receive_input(&mut self, input: Option<Direction>) {
+        let tick = self.remote_inputs.len() as u64;
+        self.remote_inputs.push(input);
+
+        // The predicted game went past this tick assuming no turn
+        if tick < self.predicted.get_tick() && input.is_some() {
+            self.rollback();
+        }
+    }
+
+    /// Replay the predicted ticks from the confirmed game with the inputs
+    /// known now.
+    fn rollback(&mut self) {
+        self.rollbacks += 1;
+        let target = self.predicted.get_tick();
+        let mut state = self.confirmed.clone();
+        while state.get_tick() < target && state.get_status() == GameStatus::Playing {
+            let inputs = self.inputs_at(state.get_tick());
+            sim::step_all(&mut state, &inputs);
+        }
+        self.predicted = state;
+    }
+
+    /// Both players' inputs for `tick`, with no turn for inputs that
+    /// haven't arrived.
+    fn inputs_at(&self, tick: u64) -> Vec<Option<Direction>> {
+        let local = self.local_inputs.get(tick as usize).copied().flatten();
+        let remote = self.remote_inputs.get(tick as usize).copied().flatten();
+        if self.local == 0 {
+            vec![local, remote]
+        } else {
+            vec![remote, local]
+        }
+    }
+
+    /// Advance the confirmed game over every tick both inputs are known
+    /// for. A prediction that fell behind it jumps ahead.
+    fn confirm(&mut self) -> Vec<Event> {
+        let known = self.local_inputs.len().min(self.remote_inputs.len()) as u64;
+        let mut events = Vec::new();
+
+        while self.confirmed.get_tick() < known
+            && self.confirmed.get_status() == GameStatus::Playing
+        {
+            let inputs = self.inputs_at(self.confirmed.get_tick());
+            self.replay.record(&inputs);
+            events.extend(
+                sim::step_all(&mut self.confirmed, &inputs)
+                    .into_iter()
+                    .filter(|event| matches!(event, Event::GameOver | Event::Won)),
+            );
+        }
+        if !events.is_empty() {
+            self.round_winner = self.confirmed.get_winner();
+        }
+        if self.confirmed.get_tick() > self.predicted.get_tick() {
+            self.predicted = self.confirmed.clone();
+        }
+
+        events
+    }
+
+    /// Whether the predicted game can go on without getting too far ahead
+    /// of the other side.
+    pub fn can_advance(&self) -> bool {
+        let known = self.remote_inputs.len() as u64;
+        self.is_connected()
+            && self.predicted.get_status() == GameStatus::Playing
+            && self.predicted.get_tick() < known + MAX_ROLLBACK
+    }
+
+    /// Play the next predicted tick, with `input` from the local player
+    /// for the tick `delay` ticks from now. Returns the events of the tick
+    /// except for the end of the round, which only `poll` reports.
+    pub fn tick(&mut self, input: Option<Direction>) -> Vec<Event> {
+        if !self.can_advance() {
+            return Vec::new();
+        }
+
+        let tick = self.local_inputs.len() as u64;
+        self.local_inputs.push(input);
+        let message = Message::Input {
+            round: self.round,
+            tick,
+            input: input_to_char(input),
+        };
+        self.send(&message);
+
+        let inputs = self.inputs_at(self.predicted.get_tick());
+        sim::step_all(&mut self.predicted, &inputs)
+            .into_iter()
+            .filter(|event| !matches!(event, Event::GameOver | Event::Won))
+            .collect()
+    }
+
+    /// Start the next round on both sides, once this one is over. Returns
+    /// whether it did.
+    pub fn restart(&mut self) -> bool {
+        if self.confirmed.get_status() == GameStatus::Playing || !self.is_connected() {
+            return false;
+        }
+        let round = self.round + 1;
+        self.pending_restart = None;
+        self.start_round(round);
+        self.send(&Message::Restart { round });
+        true
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.send(&Message::Bye);
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Play rounds without a window, with `controller` steering the local
+/// snake, until the match is decided, the connection ends or a round
+/// reaches `max_ticks`. Calls `on_round` with the confirmed end of every
+/// round.
+pub fn play(
+    session: &mut Session,
+    controller: &mut dyn Controller,
+    max_ticks: u64,
+    mut on_round: impl FnMut(&Session) -> bool,
+) {
+    loop {
+        let ended = !session.poll().is_empty();
+        if session.take_restarted() {
+            controller.reset();
+        }
+        if ended {
+            controller.end();
+            if !on_round(session) {
+                return;
+            }
+            session.restart();
+            controller.reset();
+            continue;
+        }
+        if !session.is_connected() || session.get_confirmed().get_tick() >= max_ticks {
+            return;
+        }
+
+        if session.can_advance() && session.get_state().get_tick() < max_ticks {
+            let view = BoardView::new(session.get_state(), session.get_player());
+            let dir = controller.next_direction(&view);
+            session.tick(session.input_for(dir));
+        } else {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, Topology};
+    use crate::physics::Position;
+
+    const SEED: u64 = 9;
+
+    fn rules() -> Rules {
+        let mut rules = Rules::new(Board::new(25, 25, Topology::Walled));
+        rules.players = 2;
+        rules
+    }
+
+    /// A host and a guest connected over loopback.
+    fn connect(delay: u64) -> (Session, Session) {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let host = thread::spawn(move || Session::host(port, rules(), SEED, 1, delay));
+        let addr = format!("127.0.0.1:{}", port);
+        let started = Instant::now();
+        let guest = loop {
+            match Session::connect(&addr) {
+                Ok(guest) => break guest,
+                Err(err) if started.elapsed() > DISCONNECT_TIMEOUT => panic!("{}", err),
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        (host.join().unwrap().unwrap(), guest)
+    }
+
+    fn heads(state: &GameState) -> Vec<(Position, Direction)> {
+        state
+            .get_players()
+            .iter()
+            .map(|player| {
+                let snake = player.get_snake();
+                (snake.get_head_pos().clone(), snake.get_dir())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rolls_back_a_mispredicted_input() {
+        let (mut host, mut guest) = connect(1);
+        assert_eq!((host.get_player(), guest.get_player()), (0, 1));
+
+        // The host runs ahead predicting the guest goes straight
+        for _ in 0..4 {
+            host.tick(None);
+        }
+        assert_eq!(host.get_state().get_tick(), 4);
+
+        // The guest turns, played on tick 2 after the delay
+        guest.tick(None);
+        guest.tick(Some(Direction::Up));
+
+        let started = Instant::now();
+        while host.get_rollbacks() == 0 && started.elapsed() < DISCONNECT_TIMEOUT {
+            host.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(host.get_rollbacks(), 1);
+
+        let mut expected = GameState::new(rules(), SEED);
+        for tick in 0..4 {
+            let guest_input = if tick == 2 { Some(Direction::Up) } else { None };
+            sim::step_all(&mut expected, &[None, guest_input]);
+        }
+        assert_eq!(host.get_state().get_tick(), 4);
+        assert_eq!(heads(host.get_state()), heads(&expected));
+        assert_eq!(
+            host.get_state().get_players()[1].get_snake().get_dir(),
+            Direction::Up
+        );
+        // Both inputs are known up to tick 3
+        assert_eq!(host.get_confirmed().get_tick(), 3);
+    }
+
+    #[test]
+    fn turns_are_checked_against_the_inputs_on_their_way() {
+        let (mut host, _guest) = connect(2);
+        let start = host.get_state().get_snake().get_dir();
+        assert_eq!(start, Direction::Right);
+
+        host.tick(Some(Direction::Up));
+        assert_eq!(host.get_state().get_snake().get_dir(), Direction::Right);
+        assert_eq!(host.get_heading(), Some(Direction::Up));
+        assert_eq!(host.input_for(Direction::Up), None);
+        // The opposite of the direction now, but a turn after going up
+        assert_eq!(host.input_for(Direction::Left), Some(Direction::Left));
+
+        host.tick(Some(Direction::Left));
+        assert_eq!(host.get_heading(), Some(Direction::Left));
+        host.tick(None);
+        host.tick(None);
+        assert_eq!(host.get_state().get_snake().get_dir(), Direction::Left);
+    }
+
+    #[test]
+    fn refuses_matches_outside_the_limits() {
+        for (best_of, delay) in [(2, 1), (101, 1), (0, 1), (3, MAX_INPUT_DELAY + 1)] {
+            assert!(Session::host(0, rules(), SEED, best_of, delay).is_err());
+
+            // A host that doesn't check them itself
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let host = thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                let hello = Message::Hello {
+                    version: PROTOCOL_VERSION,
+                    replay: Replay::new(SEED, rules()).serialize(),
+                    best_of,
+                    delay,
+                };
+                send(&mut stream, &hello).unwrap();
+                // Wait for the bye
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line).unwrap();
+                line
+            });
+
+            let err = Session::connect(&addr.to_string()).err().expect("an error");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(host.join().unwrap().contains("bye"));
+        }
+    }
+}
@@ -1,13 +1,8 @@
-use crate::draw::BLOCK_SIZE;
-use piston_window::types::Color;
-use piston_window::{rectangle, Context, G2d};
+use crate::colors::Color;
+use crate::config::ParticleSettings;
+use crate::render::Renderer;
 use rand::Rng;
 
-const PARTICLE_COUNT: usize = 12;
-const PARTICLE_LIFETIME: f64 = 0.8;
-const PARTICLE_SPEED: f64 = 150.0;
-const PARTICLE_SIZE: f64 = 6.0;
-
 pub struct Particle {
     x: f64,
     y: f64,
@@ -18,26 +13,32 @@ pub struct Particle {
 }
 
 impl Particle {
-    pub fn new(x: f64, y: f64, color: Color) -> Self {
+    pub fn new(x: f64, y: f64, color: Color, settings: &ParticleSettings) -> Self {
         let mut rng = rand::thread_rng();
         let angle: f64 = rng.gen_range(0.0..std::f64::consts::TAU);
-        let speed: f64 = rng.gen_range(PARTICLE_SPEED * 0.5..PARTICLE_SPEED);
+        let speed: f64 = rng.gen_range(settings.speed * 0.5..settings.speed);
 
         Particle {
             x,
             y,
             vx: angle.cos() * speed,
             vy: angle.sin() * speed,
-            lifetime: PARTICLE_LIFETIME,
+            lifetime: settings.lifetime,
             color,
         }
     }
 
-    pub fn update(&mut self, dt: f64) {
+    pub fn update(&mut self, dt: f64, wrap: Option<(f64, f64)>) {
         self.x += self.vx * dt;
         self.y += self.vy * dt;
         self.lifetime -= dt;
 
+        // On a wrapping board particles fly across the edges like the snake
+        if let Some((width, height)) = wrap {
+            self.x = self.x.rem_euclid(width);
+            self.y = self.y.rem_euclid(height);
+        }
+
         // Slow down over time
         self.vx *= 0.98;
         self.vy *= 0.98;
@@ -47,57 +48,68 @@ impl Particle {
         self.lifetime > 0.0
     }
 
-    pub fn draw(&self, ctx: &Context, g: &mut G2d) {
-        let alpha = (self.lifetime / PARTICLE_LIFETIME) as f32;
+    pub fn draw(&self, r: &mut dyn Renderer, settings: &ParticleSettings) {
+        let alpha = (self.lifetime / settings.lifetime) as f32;
         let color = [self.color[0], self.color[1], self.color[2], alpha];
+        let size = settings.size;
 
-        rectangle(
+        r.fill_rect(
             color,
-            [
-                self.x - PARTICLE_SIZE / 2.0,
-                self.y - PARTICLE_SIZE / 2.0,
-                PARTICLE_SIZE,
-                PARTICLE_SIZE,
-            ],
-            ctx.transform,
-            g,
+            [self.x - size / 2.0, self.y - size / 2.0, size, size],
         );
     }
 }
 
 pub struct ParticleSystem {
     particles: Vec<Particle>,
+    settings: ParticleSettings,
+    block_size: f64,
+    wrap: Option<(f64, f64)>,
 }
 
 impl ParticleSystem {
-    pub fn new() -> Self {
+    /// Particles live in pixel space, `block_size` converts board positions.
+    pub fn new(settings: ParticleSettings, block_size: f64) -> Self {
         ParticleSystem {
             particles: Vec::new(),
+            settings,
+            block_size,
+            wrap: None,
         }
     }
 
+    /// Make particles leaving a `width` x `height` block board come back on
+    /// the opposite side.
+    pub fn set_wrap(&mut self, width: u32, height: u32) {
+        self.wrap = Some((
+            width as f64 * self.block_size,
+            height as f64 * self.block_size,
+        ));
+    }
+
     pub fn spawn_at(&mut self, grid_x: i32, grid_y: i32, color: Color) {
         // Convert grid position to pixel position (center of the block)
-        let px = grid_x as f64 * BLOCK_SIZE + BLOCK_SIZE / 2.0;
-        let py = grid_y as f64 * BLOCK_SIZE + BLOCK_SIZE / 2.0;
+        let px = (grid_x as f64 + 0.5) * self.block_size;
+        let py = (grid_y as f64 + 0.5) * self.block_size;
 
-        for _ in 0..PARTICLE_COUNT {
-            self.particles.push(Particle::new(px, py, color));
+        for _ in 0..self.settings.count {
+            self.particles
+                .push(Particle::new(px, py, color, &self.settings));
         }
     }
 
     pub fn update(&mut self, dt: f64) {
         for particle in &mut self.particles {
-            particle.update(dt);
+            particle.update(dt, self.wrap);
         }
 
         // Remove dead particles
         self.particles.retain(|p| p.is_alive());
     }
 
-    pub fn draw(&self, ctx: &Context, g: &mut G2d) {
+    pub fn draw(&self, r: &mut dyn Renderer) {
         for particle in &self.particles {
-            particle.draw(ctx, g);
+            particle.draw(r, &self.settings);
         }
     }
 }
@@ -1,11 +1,10 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
 impl Position {
-    #[allow(dead_code)]
     pub fn move_to_dir(&mut self, dir: Direction) {
         match dir {
             Direction::Up => self.y -= 1,
@@ -0,0 +1,161 @@
+//! Drawing that works the same in a window, a terminal or an image.
+//!
+//! The board and the HUD only ever fill shapes and write text through a
+//! `Renderer`, in the units of its current transform. Every frontend starts
+//! out in its own pixels and scales by the block size to draw the board in
+//! cells (see `draw::board_transform`).
+
+#[cfg(feature = "gui")]
+use piston_window::{
+    ellipse, polygon, rectangle, text, CharacterCache, Context, G2d, Glyphs, Transformed,
+};
+
+use crate::colors::Color;
+
+/// Scaling followed by a translation, all the board needs to get from
+/// cells to pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    scale: [f64; 2],
+    offset: [f64; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            scale: [1.0, 1.0],
+            offset: [0.0, 0.0],
+        }
+    }
+}
+
+impl Transform {
+    /// Move the origin by `x`, `y` in the current units.
+    pub fn trans(self, x: f64, y: f64) -> Self {
+        Self {
+            scale: self.scale,
+            offset: [
+                self.offset[0] + x * self.scale[0],
+                self.offset[1] + y * self.scale[1],
+            ],
+        }
+    }
+
+    /// Make one unit `sx` by `sy` of the current units.
+    pub fn scale(self, sx: f64, sy: f64) -> Self {
+        Self {
+            scale: [self.scale[0] * sx, self.scale[1] * sy],
+            offset: self.offset,
+        }
+    }
+
+    pub fn get_scale(&self) -> [f64; 2] {
+        self.scale
+    }
+
+    pub fn get_offset(&self) -> [f64; 2] {
+        self.offset
+    }
+
+    pub fn apply(&self, point: [f64; 2]) -> [f64; 2] {
+        [
+            point[0] * self.scale[0] + self.offset[0],
+            point[1] * self.scale[1] + self.offset[1],
+        ]
+    }
+
+    /// `rect` as `[x, y, width, height]` in the units below this transform.
+    pub fn apply_rect(&self, rect: [f64; 4]) -> [f64; 4] {
+        let [x, y] = self.apply([rect[0], rect[1]]);
+        [x, y, rect[2] * self.scale[0], rect[3] * self.scale[1]]
+    }
+}
+
+/// Something to draw on. Rectangles are `[x, y, width, height]`.
+pub trait Renderer {
+    fn get_transform(&self) -> Transform;
+
+    fn set_transform(&mut self, transform: Transform);
+
+    fn fill_rect(&mut self, color: Color, rect: [f64; 4]);
+
+    /// Fill the ellipse inside `rect`.
+    fn fill_ellipse(&mut self, color: Color, rect: [f64; 4]);
+
+    /// Fill a convex polygon.
+    fn fill_polygon(&mut self, color: Color, points: &[[f64; 2]]);
+
+    /// Write `text` in the HUD font at `size` with its baseline starting at
+    /// `x`, `y`.
+    fn text(&mut self, color: Color, size: u32, text: &str, x: f64, y: f64);
+
+    /// Width of `text` at `size`, to center it.
+    fn text_width(&mut self, size: u32, text: &str) -> f64;
+}
+
+/// The piston window, drawing with the GPU.
+#[cfg(feature = "gui")]
+pub struct PistonRenderer<'a, 'b> {
+    ctx: Context,
+    g: &'a mut G2d<'b>,
+    glyphs: &'a mut Glyphs,
+    transform: Transform,
+}
+
+#[cfg(feature = "gui")]
+impl<'a, 'b> PistonRenderer<'a, 'b> {
+    /// Draw in the pixels of `ctx`, with text in the font of `glyphs`.
+    pub fn new(ctx: Context, g: &'a mut G2d<'b>, glyphs: &'a mut Glyphs) -> Self {
+        Self {
+            ctx,
+            g,
+            glyphs,
+            transform: Transform::default(),
+        }
+    }
+
+    fn matrix(&self) -> [[f64; 3]; 2] {
+        let [x, y] = self.transform.offset;
+        let [sx, sy] = self.transform.scale;
+        self.ctx.transform.trans(x, y).scale(sx, sy)
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Renderer for PistonRenderer<'_, '_> {
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn fill_rect(&mut self, color: Color, rect: [f64; 4]) {
+        rectangle(color, rect, self.matrix(), self.g);
+    }
+
+    fn fill_ellipse(&mut self, color: Color, rect: [f64; 4]) {
+        ellipse(color, rect, self.matrix(), self.g);
+    }
+
+    fn fill_polygon(&mut self, color: Color, points: &[[f64; 2]]) {
+        polygon(color, points, self.matrix(), self.g);
+    }
+
+    fn text(&mut self, color: Color, size: u32, text: &str, x: f64, y: f64) {
+        let transform = self.matrix().trans(x, y);
+        // A glyph that fails to load is left out
+        let _ = text::Text::new_color(color, size).draw(
+            text,
+            self.glyphs,
+            &self.ctx.draw_state,
+            transform,
+            self.g,
+        );
+    }
+
+    fn text_width(&mut self, size: u32, text: &str) -> f64 {
+        self.glyphs.width(size, text).unwrap_or(0.0)
+    }
+}
@@ -0,0 +1,449 @@
+//! Recorded games.
+//!
+//! Since `sim` is deterministic, a game is fully described by its seed,
+//! rules and the direction input fed into every tick. The file format is
+//! plain text so replays can be attached to bug reports:
+//!
+//! ```text
+//! rsnake-replay 1
+//! seed 1234
+//! size 25 25
+//! topology wrap
+//! length 3
+//! fruit 80 8 6 6
+//! goal fruits 10
+//! map 5
+//! #####
+//! #.v.#
+//! #...#
+//! #.*.#
+//! #####
+//! inputs 12
+//! ...R..D..L..
+//! ```
+//!
+//! Every character after the `inputs` line is one tick: `.` for no input,
+//! or one of `U`, `D`, `L`, `R`. Line breaks between them are ignored.
+//! The `fruit` weights are for normal, golden, poison and speed fruit. The
+//! `goal` (`fruits`, `length` or `score`) is only there for campaign levels,
+//! the `map` line only for games on a level. It is followed by that many
+//! lines of the level file format (see `level.rs`).
+//!
+//! A versus game has a `players 2` line, and then every tick takes one
+//! character per player in order, so `inputs` counts characters rather than
+//! ticks. Games without the line have a single player.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::board::{Board, Topology};
+use crate::campaign::Goal;
+use crate::fruit::FruitWeights;
+use crate::level::{Level, MAX_SIZE, MIN_SIZE};
+use crate::physics::Direction;
+use crate::sim::Rules;
+use crate::snake::DEFAULT_START_LENGTH;
+
+const MAGIC: &str = "rsnake-replay";
+pub const FORMAT_VERSION: u32 = 1;
+const MAX_PLAYERS: usize = 2;
+const INPUTS_PER_LINE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    /// Input of every player for every tick, the players of a tick are next
+    /// to each other
+    pub inputs: Vec<Option<Direction>>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// `word` of the header `line` as a number, `Err` if it isn't one or is out
+/// of the range of `T`.
+fn number<T: FromStr>(word: Option<&str>, line: &str) -> io::Result<T> {
+    word.and_then(|w| w.parse::<T>().ok())
+        .ok_or_else(|| invalid(format!("bad replay header line '{}'", line)))
+}
+
+pub(crate) fn input_to_char(input: Option<Direction>) -> char {
+    match input {
+        None => '.',
+        Some(Direction::Up) => 'U',
+        Some(Direction::Down) => 'D',
+        Some(Direction::Left) => 'L',
+        Some(Direction::Right) => 'R',
+    }
+}
+
+pub(crate) fn char_to_input(c: char) -> Option<Option<Direction>> {
+    match c {
+        '.' => Some(None),
+        'U' => Some(Some(Direction::Up)),
+        'D' => Some(Some(Direction::Down)),
+        'L' => Some(Some(Direction::Left)),
+        'R' => Some(Some(Direction::Right)),
+        _ => None,
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, rules: Rules) -> Self {
+        Self {
+            seed,
+            rules,
+            inputs: Vec::new(),
+        }
+    }
+
+    fn players(&self) -> usize {
+        self.rules.players.max(1)
+    }
+
+    /// Number of ticks recorded.
+    pub fn ticks(&self) -> u64 {
+        (self.inputs.len() / self.players()) as u64
+    }
+
+    /// Inputs of all players for the given (zero based) tick, empty past
+    /// the end.
+    pub fn inputs_at(&self, tick: u64) -> &[Option<Direction>] {
+        let players = self.players();
+        let start = tick as usize * players;
+        self.inputs.get(start..start + players).unwrap_or(&[])
+    }
+
+    /// Add the inputs of all players for the next tick.
+    pub fn record(&mut self, inputs: &[Option<Direction>]) {
+        let players = self.players();
+        self.inputs
+            .extend((0..players).map(|i| inputs.get(i).copied().flatten()));
+    }
+
+    /// Drop the inputs after the first `ticks` ticks.
+    pub fn truncate(&mut self, ticks: u64) {
+        let players = self.players();
+        self.inputs.truncate(ticks as usize * players);
+    }
+
+    pub fn serialize(&self) -> String {
+        let board = &self.rules.board;
+        let weights = &self.rules.fruit_weights;
+        let topology = match board.topology {
+            Topology::Walled => "walled",
+            Topology::Wrap => "wrap",
+        };
+        let mut out = format!(
+            "{} {}\nseed {}\nsize {} {}\ntopology {}\nlength {}\nfruit {} {} {} {}\n",
+            MAGIC,
+            FORMAT_VERSION,
+            self.seed,
+            board.width,
+            board.height,
+            topology,
+            self.rules.start_length,
+            weights.normal,
+            weights.golden,
+            weights.poison,
+            weights.speed,
+        );
+
+        match self.rules.goal {
+            Some(Goal::Fruits(n)) => out.push_str(&format!("goal fruits {}\n", n)),
+            Some(Goal::Length(n)) => out.push_str(&format!("goal length {}\n", n)),
+            Some(Goal::Score(n)) => out.push_str(&format!("goal score {}\n", n)),
+            None => {}
+        }
+
+        if self.rules.players > 1 {
+            out.push_str(&format!("players {}\n", self.rules.players));
+        }
+
+        if let Some((start, start_dir)) = &self.rules.start {
+            let level = Level {
+                name: String::new(),
+                board: board.clone(),
+                start: start.clone(),
+                start_dir: *start_dir,
+                fruit_zones: self.rules.fruit_zones.clone(),
+            };
+            let map = level.serialize();
+            out.push_str(&format!("map {}\n{}", map.lines().count(), map));
+        }
+
+        out.push_str(&format!("inputs {}\n", self.inputs.len()));
+
+        for chunk in self.inputs.chunks(INPUTS_PER_LINE) {
+            out.extend(chunk.iter().map(|input| input_to_char(*input)));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines();
+
+        let header = lines.next().unwrap_or("");
+        let version = header
+            .strip_prefix(MAGIC)
+            .map(str::trim)
+            .ok_or_else(|| invalid("not an rsnake replay".to_string()))?;
+        if version != FORMAT_VERSION.to_string() {
+            return Err(invalid(format!(
+                "unsupported replay version {} (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+
+        let mut seed = None;
+        let mut size = None;
+        let mut topology = Topology::Walled;
+        let mut start_length = DEFAULT_START_LENGTH;
+        let mut fruit_weights = FruitWeights::classic();
+        let mut level = None;
+        let mut goal = None;
+        let mut players = 1;
+        let mut count: Option<usize> = None;
+
+        while let Some(line) = lines.next() {
+            let mut words = line.split_whitespace();
+
+            match words.next() {
+                Some("seed") => seed = Some(number(words.next(), line)?),
+                Some("size") => {
+                    size = Some((number(words.next(), line)?, number(words.next(), line)?))
+                }
+                Some("topology") => {
+                    topology = match words.next() {
+                        Some("walled") => Topology::Walled,
+                        Some("wrap") => Topology::Wrap,
+                        _ => return Err(invalid(format!("bad replay topology '{}'", line))),
+                    }
+                }
+                Some("length") => start_length = number(words.next(), line)?,
+                Some("fruit") => {
+                    fruit_weights = FruitWeights {
+                        normal: number(words.next(), line)?,
+                        golden: number(words.next(), line)?,
+                        poison: number(words.next(), line)?,
+                        speed: number(words.next(), line)?,
+                    }
+                }
+                Some("goal") => {
+                    goal = Some(match words.next() {
+                        Some("fruits") => Goal::Fruits(number(words.next(), line)?),
+                        Some("length") => Goal::Length(number(words.next(), line)?),
+                        Some("score") => Goal::Score(number(words.next(), line)?),
+                        _ => return Err(invalid(format!("bad replay goal '{}'", line))),
+                    })
+                }
+                Some("players") => {
+                    players = number(words.next(), line)?;
+                    if !(1..=MAX_PLAYERS).contains(&players) {
+                        return Err(invalid(format!(
+                            "replay has {} players, expected 1 to {}",
+                            players, MAX_PLAYERS
+                        )));
+                    }
+                }
+                Some("map") => {
+                    let rows = number(words.next(), line)?;
+                    let map: Vec<&str> = lines.by_ref().take(rows).collect();
+                    let parsed = Level::parse(&map.join("\n"))
+                        .map_err(|err| invalid(format!("bad replay map: {}", err)))?;
+                    level = Some(parsed);
+                }
+                Some("inputs") => {
+                    count = Some(number(words.next(), line)?);
+                    break;
+                }
+                _ => return Err(invalid(format!("unknown replay header line '{}'", line))),
+            }
+        }
+
+        let seed = seed.ok_or_else(|| invalid("replay has no seed".to_string()))?;
+        let (width, height) =
+            size.ok_or_else(|| invalid("replay has no board size".to_string()))?;
+        let count = count.ok_or_else(|| invalid("replay has no inputs".to_string()))?;
+        fruit_weights.validate().map_err(invalid)?;
+
+        // The same limits as on the command line and for levels, replays
+        // also come from the network
+        let sizes = MIN_SIZE as u32..=MAX_SIZE as u32;
+        if !sizes.contains(&width) || !sizes.contains(&height) {
+            return Err(invalid(format!(
+                "replay board is {}x{}, both sides must be between {} and {}",
+                width, height, MIN_SIZE, MAX_SIZE
+            )));
+        }
+        let cells = (width * height) as usize;
+        if !(1..=cells).contains(&start_length) {
+            return Err(invalid(format!(
+                "replay snake length {} does not fit a {}x{} board",
+                start_length, width, height
+            )));
+        }
+
+        // Not trusting `count` before the inputs are there
+        let mut inputs = Vec::with_capacity(count.min(text.len()));
+        for c in lines.flat_map(str::chars).filter(|c| !c.is_whitespace()) {
+            let input =
+                char_to_input(c).ok_or_else(|| invalid(format!("bad replay input '{}'", c)))?;
+            inputs.push(input);
+        }
+
+        if inputs.len() != count {
+            return Err(invalid(format!(
+                "replay is truncated: expected {} inputs, found {}",
+                count,
+                inputs.len()
+            )));
+        }
+        if count % players != 0 {
+            return Err(invalid(format!(
+                "replay has {} inputs, not a multiple of its {} players",
+                count, players
+            )));
+        }
+
+        let mut rules = match level {
+            Some(level) => {
+                if (level.board.width, level.board.height) != (width, height) {
+                    return Err(invalid(format!(
+                        "replay map is {}x{}, but the board is {}x{}",
+                        level.board.width, level.board.height, width, height
+                    )));
+                }
+                Rules::from_level(&level)
+            }
+            None => Rules::new(Board::new(width, height, topology)),
+        };
+        rules.board.topology = topology;
+        rules.start_length = start_length;
+        rules.fruit_weights = fruit_weights;
+        rules.goal = goal;
+        rules.players = players;
+
+        Ok(Self {
+            seed,
+            rules,
+            inputs,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let level = Level::parse("#######\n#..>.1#\n#.*...#\n#1....#\n#######").unwrap();
+        let mut rules = Rules::from_level(&level);
+        rules.start_length = 2;
+        rules.fruit_weights = FruitWeights {
+            normal: 5,
+            golden: 1,
+            poison: 2,
+            speed: 0,
+        };
+        rules.goal = Some(Goal::Score(50));
+
+        let mut replay = Replay::new(42, rules);
+        let turns = [None, Some(Direction::Down), None, Some(Direction::Left)];
+        // More than fit on one line
+        for tick in 0..INPUTS_PER_LINE * 2 + 3 {
+            replay.record(&[turns[tick % turns.len()]]);
+        }
+        replay
+    }
+
+    /// The replay with its first line replaced by `header`.
+    fn with_header(header: &str) -> String {
+        let text = replay().serialize();
+        let (_, rest) = text.split_once('\n').unwrap();
+        format!("{}\n{}", header, rest)
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = replay();
+        let parsed = Replay::parse(&replay.serialize()).unwrap();
+        assert_eq!(parsed, replay);
+        assert_eq!(parsed.inputs_at(1), &[Some(Direction::Down)]);
+        assert!(parsed.inputs_at(10_000).is_empty());
+    }
+
+    #[test]
+    fn round_trip_versus() {
+        let mut rules = Rules::new(Board::new(20, 12, Topology::Wrap));
+        rules.players = 2;
+        let mut replay = Replay::new(7, rules);
+        replay.record(&[Some(Direction::Up), None]);
+        replay.record(&[None, Some(Direction::Down)]);
+
+        let parsed = Replay::parse(&replay.serialize()).unwrap();
+        assert_eq!(parsed, replay);
+        assert_eq!(parsed.ticks(), 2);
+        assert_eq!(parsed.inputs_at(1), &[None, Some(Direction::Down)]);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let current = format!("{} {}", MAGIC, FORMAT_VERSION);
+        assert!(Replay::parse(&with_header(&current)).is_ok());
+
+        for header in [
+            format!("{} {}", MAGIC, FORMAT_VERSION + 1),
+            format!("{} one", MAGIC),
+            "some-other-file 1".to_string(),
+        ] {
+            let err = Replay::parse(&with_header(&header)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", header);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let text = replay().serialize();
+        for (line, bad) in [
+            ("length 2", "length 0"),
+            ("length 2", "length 99"),
+            ("seed 42", "seed -1"),
+            ("seed 42", "seed 42\nplayers 3"),
+            ("fruit 5 1 2 0", "fruit 0 0 0 0"),
+        ] {
+            assert!(
+                Replay::parse(&text.replacen(line, bad, 1)).is_err(),
+                "{}",
+                bad
+            );
+        }
+
+        let huge = Replay::new(1, Rules::new(Board::new(10, 10, Topology::Walled)))
+            .serialize()
+            .replace("size 10 10", "size 0 4294967295");
+        assert!(Replay::parse(&huge).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_inputs() {
+        let text = replay().serialize();
+        assert!(Replay::parse(&text[..text.len() - 2]).is_err());
+        assert!(Replay::parse(&text.replacen(".D", ".X", 1)).is_err());
+        assert!(Replay::parse(&text.replacen("seed 42\n", "", 1)).is_err());
+    }
+}
@@ -0,0 +1,733 @@
+//! Headless game rules.
+//!
+//! Everything in here is deterministic for a given seed and sequence of
+//! inputs, and does not depend on a window, audio or wall-clock time. The
+//! frontend in `game.rs` drives it from its tick thread, but it can just as
+//! well be stepped from tests, bots or a replay.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashSet;
+
+use crate::board::Board;
+use crate::campaign::Goal;
+use crate::fruit::{Fruit, FruitKind, FruitWeights, POISON_SHRINK, SPEED_BOOST_TICKS};
+use crate::level::Level;
+use crate::physics::{Direction, Position};
+use crate::snake::{Snake, DEFAULT_START_LENGTH};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameStatus {
+    Playing,
+    GameOver,
+    /// The snake covers the whole board, there is no room for more fruit
+    Won,
+    /// The goal of the level was reached
+    Cleared,
+}
+
+/// Something that happened during a single `step`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    FruitEaten(Fruit),
+    /// A special fruit disappeared without being eaten
+    FruitExpired(Fruit),
+    /// The head went into the portal at `from` and came out of `to`
+    Teleported {
+        from: Position,
+        to: Position,
+    },
+    GameOver,
+    Won,
+    LevelCleared,
+}
+
+/// Everything besides the seed and the inputs that changes how a game plays
+/// out. Replays store these next to the seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub board: Board,
+    /// Length of the snake, including its head, when the game starts
+    pub start_length: usize,
+    pub fruit_weights: FruitWeights,
+    /// Where the snake starts and which way it heads, random if `None`
+    pub start: Option<(Position, Direction)>,
+    /// Cells fruit spawns on while one of them is free, anywhere if empty
+    pub fruit_zones: Vec<Position>,
+    /// Ends the game as cleared once reached, `None` to play until the end
+    pub goal: Option<Goal>,
+    /// Number of snakes, 2 for a versus game
+    pub players: usize,
+}
+
+impl Rules {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            start_length: DEFAULT_START_LENGTH,
+            fruit_weights: FruitWeights::default(),
+            start: None,
+            fruit_zones: Vec::new(),
+            goal: None,
+            players: 1,
+        }
+    }
+
+    /// Rules for playing on `level`, with the default start length and
+    /// fruit weights.
+    pub fn from_level(level: &Level) -> Self {
+        Self {
+            start: Some((level.start.clone(), level.start_dir)),
+            fruit_zones: level.fruit_zones.clone(),
+            ..Self::new(level.board.clone())
+        }
+    }
+}
+
+fn calc_random_pos(board: &Board, rng: &mut impl Rng) -> Position {
+    Position {
+        x: rng.gen_range(0..board.width as i32),
+        y: rng.gen_range(0..board.height as i32),
+    }
+}
+
+/// Pick a random cell that is not covered by a snake or a wall,
+/// preferring the fruit zones. `None` if the snakes cover (or, once their
+/// growth has caught up, will cover) the whole board.
+fn calc_free_pos(rules: &Rules, players: &[Player], rng: &mut impl Rng) -> Option<Position> {
+    let board = &rules.board;
+    let body_len: usize = players.iter().map(|p| p.snake.get_body_len()).sum();
+    if body_len >= board.free_cells() {
+        return None;
+    }
+
+    let occupied: HashSet<&Position> = players.iter().flat_map(|p| p.snake.cells()).collect();
+    let is_free =
+        |pos: &Position| !occupied.contains(pos) && !board.is_wall(pos) && !board.is_portal(pos);
+
+    let mut free: Vec<Position> = rules
+        .fruit_zones
+        .iter()
+        .filter(|pos| is_free(pos))
+        .cloned()
+        .collect();
+    if free.is_empty() {
+        free = (0..board.height as i32)
+            .flat_map(|y| (0..board.width as i32).map(move |x| Position { x, y }))
+            .filter(is_free)
+            .collect();
+    }
+
+    if free.is_empty() {
+        return None;
+    }
+
+    Some(free[rng.gen_range(0..free.len())].clone())
+}
+
+fn spawn_fruit(rules: &Rules, players: &[Player], rng: &mut impl Rng) -> Option<Fruit> {
+    let pos = calc_free_pos(rules, players, rng)?;
+    Some(Fruit::new(pos, rules.fruit_weights.pick(rng)))
+}
+
+/// Where the snakes of a versus game start: facing each other from the
+/// left and right quarter of the middle row.
+fn versus_starts(board: &Board) -> [(Position, Direction); 2] {
+    let (width, height) = (board.width as i32, board.height as i32);
+    let y = height / 2;
+
+    [
+        (Position { x: width / 4, y }, Direction::Right),
+        (
+            Position {
+                x: width - 1 - width / 4,
+                y,
+            },
+            Direction::Left,
+        ),
+    ]
+}
+
+/// One snake and how it is doing.
+#[derive(Clone)]
+pub struct Player {
+    snake: Snake,
+    score: u32,
+    alive: bool,
+}
+
+impl Player {
+    fn new(snake: Snake) -> Self {
+        Self {
+            snake,
+            score: 0,
+            alive: true,
+        }
+    }
+
+    pub fn get_snake(&self) -> &Snake {
+        &self.snake
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+}
+
+#[derive(Clone)]
+pub struct GameState<R = ChaCha8Rng> {
+    // One for a normal game, two in versus
+    players: Vec<Player>,
+    // `None` once the game is won
+    fruit: Option<Fruit>,
+    rules: Rules,
+    // Ticks left on the speed fruit's boost
+    boost_ticks: u32,
+    fruits_eaten: u32,
+    status: GameStatus,
+    tick: u64,
+    rng: R,
+}
+
+impl GameState {
+    pub fn new(rules: Rules, seed: u64) -> Self {
+        Self::with_rng(rules, ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> GameState<R> {
+    pub fn with_rng(rules: Rules, mut rng: R) -> Self {
+        let board = &rules.board;
+        let starts: Vec<(Position, Direction)> = if rules.players > 1 {
+            versus_starts(board).to_vec()
+        } else {
+            match &rules.start {
+                Some((pos, dir)) => vec![(pos.clone(), *dir)],
+                None => vec![(calc_random_pos(board, &mut rng), Direction::Down)],
+            }
+        };
+        let players: Vec<Player> = starts
+            .into_iter()
+            .map(|(head, dir)| Player::new(Snake::new(head, dir, rules.start_length, board)))
+            .collect();
+        let fruit = spawn_fruit(&rules, &players, &mut rng);
+
+        Self {
+            players,
+            fruit,
+            rules,
+            boost_ticks: 0,
+            fruits_eaten: 0,
+            status: GameStatus::Playing,
+            tick: 0,
+            rng,
+        }
+    }
+
+    /// The snake of the first (in a normal game the only) player.
+    pub fn get_snake(&self) -> &Snake {
+        &self.players[0].snake
+    }
+
+    pub fn get_players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn get_fruit(&self) -> Option<&Fruit> {
+        self.fruit.as_ref()
+    }
+
+    /// Whether a speed fruit eaten recently still makes the snake faster.
+    pub fn is_boosted(&self) -> bool {
+        self.boost_ticks > 0
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.rules.board
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.rules.board.width, self.rules.board.height)
+    }
+
+    /// Score of the first (in a normal game the only) player.
+    pub fn get_score(&self) -> u32 {
+        self.players[0].score
+    }
+
+    pub fn get_fruits_eaten(&self) -> u32 {
+        self.fruits_eaten
+    }
+
+    pub fn get_status(&self) -> GameStatus {
+        self.status
+    }
+
+    /// The player left alive once a versus round is over, or the one with
+    /// the higher score if the board filled up. `None` for a draw, while
+    /// the round is still going and in a normal game.
+    pub fn get_winner(&self) -> Option<usize> {
+        if self.players.len() < 2 || self.status == GameStatus::Playing {
+            return None;
+        }
+
+        let alive: Vec<usize> = (0..self.players.len())
+            .filter(|i| self.players[*i].alive)
+            .collect();
+        match alive[..] {
+            [winner] => Some(winner),
+            [] => None,
+            _ => {
+                let best = alive.iter().map(|i| self.players[*i].score).max()?;
+                let leaders: Vec<&usize> = alive
+                    .iter()
+                    .filter(|i| self.players[**i].score == best)
+                    .collect();
+                match leaders[..] {
+                    [winner] => Some(*winner),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Number of ticks simulated so far.
+    pub fn get_tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// Whether the snake of player `index` dies moving into its next cell.
+/// Hitting another snake's body is fatal; in a head-on collision (both
+/// heads moving into the same cell, or swapping cells) the longer snake
+/// survives, and both die if they are the same length.
+fn will_die(players: &[Player], index: usize, board: &Board) -> bool {
+    let snake = &players[index].snake;
+    if snake.will_hit_wall(board)
+        || snake.will_hit_obstacle(board)
+        || snake.is_tail_overlapping()
+        || snake.will_tail_overlapp(board)
+    {
+        return true;
+    }
+
+    let next = board.neighbor(snake.get_head_pos(), snake.get_dir());
+    players
+        .iter()
+        .enumerate()
+        .filter(|(i, other)| *i != index && other.alive)
+        .any(|(_, other)| {
+            let other_head = other.snake.get_head_pos();
+            let other_next = board.neighbor(other_head, other.snake.get_dir());
+            // Moving into the same cell or through each other
+            let head_on =
+                other_next == next || (next == *other_head && other_next == *snake.get_head_pos());
+            if head_on {
+                snake.get_body_len() <= other.snake.get_body_len()
+            } else {
+                other.snake.occupies(&next)
+            }
+        })
+}
+
+/// Advance the game by one tick, applying `input` as the direction change
+/// for this tick (if any). Does nothing once the game is over.
+pub fn step<R: Rng>(state: &mut GameState<R>, input: Option<Direction>) -> Vec<Event> {
+    step_all(state, &[input])
+}
+
+/// Advance a game with any number of players by one tick, `inputs` holds
+/// the direction change of every player in order. Missing inputs count as
+/// no change.
+pub fn step_all<R: Rng>(state: &mut GameState<R>, inputs: &[Option<Direction>]) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    if state.status != GameStatus::Playing {
+        return events;
+    }
+
+    state.tick += 1;
+
+    for (player, input) in state.players.iter_mut().zip(inputs) {
+        if let (true, Some(dir)) = (player.alive, input) {
+            player.snake.set_dir(*dir);
+        }
+    }
+
+    // Decide who dies before anyone moves, so the order of the players
+    // doesn't matter
+    let board = &state.rules.board;
+    let dying: Vec<bool> = (0..state.players.len())
+        .map(|i| state.players[i].alive && will_die(&state.players, i, board))
+        .collect();
+    for (player, dies) in state.players.iter_mut().zip(&dying) {
+        if *dies {
+            player.alive = false;
+        }
+    }
+
+    // A normal game ends with its snake, a versus round once at most one
+    // snake is left
+    let alive = state.players.iter().filter(|p| p.alive).count();
+    if alive == 0 || (state.players.len() > 1 && alive < 2) {
+        state.status = GameStatus::GameOver;
+        events.push(Event::GameOver);
+        return events;
+    }
+
+    let mut eaten = None;
+    for (index, player) in state.players.iter_mut().enumerate() {
+        if !player.alive {
+            continue;
+        }
+
+        let head = player.snake.get_head_pos();
+        if state.fruit.as_ref().is_some_and(|f| f.pos == *head) {
+            eaten = state.fruit.take().map(|fruit| (index, fruit));
+        }
+
+        let portal = board
+            .portal_towards(head, player.snake.get_dir())
+            .map(|(from, to)| (from, to.clone()));
+
+        player.snake.update(board);
+        if let Some((from, to)) = portal {
+            events.push(Event::Teleported { from, to });
+        }
+    }
+    state.boost_ticks = state.boost_ticks.saturating_sub(1);
+
+    if let Some((index, fruit)) = eaten {
+        let player = &mut state.players[index];
+        match fruit.kind {
+            FruitKind::Poison => player.snake.shrink(POISON_SHRINK),
+            FruitKind::Speed => {
+                player.snake.grow(&mut state.rng);
+                state.boost_ticks = SPEED_BOOST_TICKS;
+            }
+            FruitKind::Normal | FruitKind::Golden => player.snake.grow(&mut state.rng),
+        }
+        player.score += fruit.kind.points();
+        state.fruits_eaten += 1;
+        events.push(Event::FruitEaten(fruit));
+
+        state.fruit = spawn_fruit(&state.rules, &state.players, &mut state.rng);
+        if state.fruit.is_none() {
+            state.status = GameStatus::Won;
+            events.push(Event::Won);
+        } else if let Some(goal) = &state.rules.goal {
+            let player = &state.players[index];
+            let length = player.snake.get_body_len();
+            if goal.is_met(state.fruits_eaten, length, player.score) {
+                state.status = GameStatus::Cleared;
+                events.push(Event::LevelCleared);
+            }
+        }
+    } else if let Some(fruit) = &mut state.fruit {
+        let expired = match &mut fruit.ticks_left {
+            Some(ticks) => {
+                *ticks = ticks.saturating_sub(1);
+                *ticks == 0
+            }
+            None => false,
+        };
+
+        if expired {
+            if let Some(fruit) = state.fruit.take() {
+                events.push(Event::FruitExpired(fruit));
+            }
+            state.fruit = spawn_fruit(&state.rules, &state.players, &mut state.rng);
+            // Like after eating, a board with no room left is won
+            if state.fruit.is_none() {
+                state.status = GameStatus::Won;
+                events.push(Event::Won);
+            }
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Topology;
+    use crate::fruit::SPECIAL_FRUIT_TICKS;
+
+    /// A game with the head of a snake `length` long heading down at
+    /// `head`, and the fruit out of its way.
+    fn game(board: Board, head: (i32, i32), length: usize) -> GameState {
+        let mut state = GameState::new(Rules::new(board), 1);
+        let head = Position {
+            x: head.0,
+            y: head.1,
+        };
+        state.players[0].snake = Snake::new(head, Direction::Down, length, &state.rules.board);
+        state.fruit = Some(Fruit::new(Position { x: 2, y: 6 }, FruitKind::Normal));
+        state
+    }
+
+    #[test]
+    fn eating_grows_and_scores() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (4, 4), 3);
+        state.fruit = Some(Fruit::new(Position { x: 4, y: 4 }, FruitKind::Normal));
+
+        let events = step(&mut state, None);
+
+        assert!(matches!(events[..], [Event::FruitEaten(_)]));
+        assert_eq!(state.get_snake().get_body_len(), 4);
+        assert_eq!(state.get_score(), FruitKind::Normal.points());
+        assert_eq!(state.get_fruits_eaten(), 1);
+        let fruit = state.get_fruit().expect("a new fruit");
+        assert!(!state.get_snake().occupies(&fruit.pos));
+    }
+
+    #[test]
+    fn hitting_a_wall_ends_the_game() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (4, 4), 3);
+        state.rules.board.walls.insert(Position { x: 4, y: 5 });
+
+        assert_eq!(step(&mut state, None), vec![Event::GameOver]);
+        assert_eq!(state.get_status(), GameStatus::GameOver);
+        // Nothing happens after the end
+        assert!(step(&mut state, None).is_empty());
+        assert_eq!(state.get_tick(), 1);
+    }
+
+    #[test]
+    fn running_into_itself_ends_the_game() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (4, 4), 5);
+
+        assert!(step(&mut state, Some(Direction::Right)).is_empty());
+        assert!(step(&mut state, Some(Direction::Up)).is_empty());
+        assert_eq!(
+            step(&mut state, Some(Direction::Left)),
+            vec![Event::GameOver]
+        );
+    }
+
+    #[test]
+    fn reversing_is_ignored() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (4, 4), 3);
+
+        assert!(step(&mut state, Some(Direction::Up)).is_empty());
+        assert_eq!(state.get_snake().get_dir(), Direction::Down);
+        assert_eq!(*state.get_snake().get_head_pos(), Position { x: 4, y: 5 });
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = |seed| {
+            let mut state = GameState::new(Rules::new(Board::new(8, 8, Topology::Wrap)), seed);
+            let turns = [Some(Direction::Left), None, Some(Direction::Up), None];
+            let mut fruit = Vec::new();
+            for tick in 0..200 {
+                step(&mut state, turns[tick / 3 % turns.len()]);
+                fruit.push(state.get_fruit().cloned());
+            }
+            (fruit, state.get_score(), state.get_status())
+        };
+
+        assert_eq!(play(5), play(5));
+        assert_ne!(play(5).0, play(6).0);
+    }
+
+    #[test]
+    fn wraps_around_the_edges() {
+        let mut state = game(Board::new(10, 10, Topology::Wrap), (3, 9), 3);
+
+        assert!(step(&mut state, None).is_empty());
+        assert_eq!(*state.get_snake().get_head_pos(), Position { x: 3, y: 0 });
+
+        assert!(step(&mut state, Some(Direction::Left)).is_empty());
+        assert!(step(&mut state, None).is_empty());
+        assert!(step(&mut state, None).is_empty());
+        assert!(step(&mut state, None).is_empty());
+        assert_eq!(*state.get_snake().get_head_pos(), Position { x: 9, y: 0 });
+        assert_eq!(state.get_status(), GameStatus::Playing);
+    }
+
+    #[test]
+    fn walls_stop_the_snake_at_the_edges() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (3, 9), 3);
+
+        assert_eq!(step(&mut state, None), vec![Event::GameOver]);
+        assert_eq!(state.get_status(), GameStatus::GameOver);
+    }
+
+    #[test]
+    fn runs_into_itself_across_the_edge() {
+        // Five long on a board four wide, the head catches up with the tail
+        let mut state = game(Board::new(4, 10, Topology::Wrap), (0, 5), 5);
+
+        assert!(step(&mut state, Some(Direction::Right)).is_empty());
+        assert!(step(&mut state, None).is_empty());
+        assert!(step(&mut state, None).is_empty());
+        assert_eq!(*state.get_snake().get_head_pos(), Position { x: 3, y: 5 });
+        assert_eq!(step(&mut state, None), vec![Event::GameOver]);
+    }
+
+    fn put_fruit(state: &mut GameState, x: i32, y: i32, kind: FruitKind) {
+        state.fruit = Some(Fruit::new(Position { x, y }, kind));
+    }
+
+    #[test]
+    fn fruit_kinds_score_and_grow_or_shrink() {
+        let mut state = game(Board::new(10, 10, Topology::Wrap), (5, 2), 3);
+
+        put_fruit(&mut state, 5, 2, FruitKind::Golden);
+        assert!(matches!(step(&mut state, None)[..], [Event::FruitEaten(_)]));
+        assert_eq!(state.get_score(), 50);
+        assert_eq!(state.get_snake().get_body_len(), 4);
+
+        put_fruit(&mut state, 5, 3, FruitKind::Speed);
+        step(&mut state, None);
+        assert_eq!(state.get_score(), 60);
+        assert_eq!(state.get_snake().get_body_len(), 5);
+        assert!(state.is_boosted());
+        for _ in 0..SPEED_BOOST_TICKS {
+            step(&mut state, None);
+        }
+        assert!(!state.is_boosted());
+
+        // Poison takes two segments off, but never below the start length
+        let head = state.get_snake().get_head_pos().clone();
+        put_fruit(&mut state, head.x, head.y, FruitKind::Poison);
+        step(&mut state, None);
+        assert_eq!(state.get_score(), 60);
+        assert_eq!(state.get_snake().get_body_len(), 3);
+    }
+
+    #[test]
+    fn poison_leaves_a_snake_of_one_alone() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (5, 2), 1);
+        state.rules.start_length = 1;
+        put_fruit(&mut state, 5, 2, FruitKind::Poison);
+
+        assert!(matches!(step(&mut state, None)[..], [Event::FruitEaten(_)]));
+        assert_eq!(state.get_snake().get_body_len(), 1);
+        assert_eq!(state.get_score(), 0);
+        assert_eq!(state.get_status(), GameStatus::Playing);
+    }
+
+    #[test]
+    fn special_fruit_expires() {
+        let mut state = game(Board::new(10, 10, Topology::Wrap), (5, 2), 3);
+        put_fruit(&mut state, 0, 0, FruitKind::Golden);
+
+        for _ in 1..SPECIAL_FRUIT_TICKS {
+            assert!(step(&mut state, None).is_empty());
+        }
+        assert_eq!(state.get_fruit().and_then(|f| f.ticks_left), Some(1));
+        let events = step(&mut state, None);
+        assert!(matches!(&events[..], [Event::FruitExpired(f)] if f.kind == FruitKind::Golden));
+        assert!(state.get_fruit().is_some());
+
+        // Normal fruit stays until it is eaten
+        put_fruit(&mut state, 0, 0, FruitKind::Normal);
+        for _ in 0..SPECIAL_FRUIT_TICKS * 2 {
+            assert!(step(&mut state, None).is_empty());
+        }
+    }
+
+    #[test]
+    fn expired_fruit_without_room_wins() {
+        let mut state = game(Board::new(5, 5, Topology::Walled), (2, 2), 3);
+        // Walls everywhere but where the snake is after its next move
+        let body = [(2, 3), (2, 2), (2, 1)];
+        for y in 0..5 {
+            for x in 0..5 {
+                if !body.contains(&(x, y)) {
+                    state.rules.board.walls.insert(Position { x, y });
+                }
+            }
+        }
+        put_fruit(&mut state, 0, 0, FruitKind::Golden);
+        if let Some(fruit) = &mut state.fruit {
+            fruit.ticks_left = Some(1);
+        }
+
+        let events = step(&mut state, None);
+
+        assert!(matches!(events[..], [Event::FruitExpired(_), Event::Won]));
+        assert_eq!(state.get_status(), GameStatus::Won);
+        assert!(state.get_fruit().is_none());
+    }
+
+    #[test]
+    fn meeting_the_goal_clears_the_level() {
+        let mut state = game(Board::new(10, 10, Topology::Walled), (5, 2), 3);
+        state.rules.goal = Some(Goal::Fruits(2));
+
+        put_fruit(&mut state, 5, 2, FruitKind::Normal);
+        step(&mut state, None);
+        assert_eq!(state.get_status(), GameStatus::Playing);
+
+        put_fruit(&mut state, 5, 3, FruitKind::Normal);
+        let events = step(&mut state, None);
+        assert_eq!(events.last(), Some(&Event::LevelCleared));
+        assert_eq!(state.get_status(), GameStatus::Cleared);
+    }
+
+    #[test]
+    fn portals_move_the_head_past_their_pair() {
+        let mut board = Board::new(10, 10, Topology::Walled);
+        board.add_portal(Position { x: 5, y: 4 }, Position { x: 1, y: 8 });
+        let mut state = game(board, (5, 2), 3);
+
+        assert!(step(&mut state, None).is_empty());
+        let events = step(&mut state, None);
+        assert_eq!(
+            events,
+            vec![Event::Teleported {
+                from: Position { x: 5, y: 4 },
+                to: Position { x: 1, y: 8 },
+            }]
+        );
+        assert_eq!(*state.get_snake().get_head_pos(), Position { x: 1, y: 9 });
+        assert_eq!(state.get_status(), GameStatus::Playing);
+    }
+
+    /// Two snakes of length 3 facing each other on the middle row, two
+    /// moves from meeting head on.
+    fn versus() -> GameState {
+        let mut rules = Rules::new(Board::new(9, 9, Topology::Walled));
+        rules.players = 2;
+        rules.fruit_weights = FruitWeights::classic();
+        let mut state = GameState::new(rules, 1);
+        put_fruit(&mut state, 0, 0, FruitKind::Normal);
+        state
+    }
+
+    #[test]
+    fn head_on_kills_both_snakes_of_the_same_length() {
+        let mut state = versus();
+
+        assert!(step_all(&mut state, &[None, None]).is_empty());
+        assert_eq!(step_all(&mut state, &[None, None]), vec![Event::GameOver]);
+        assert!(state.get_players().iter().all(|p| !p.is_alive()));
+        assert_eq!(state.get_winner(), None);
+    }
+
+    #[test]
+    fn head_on_is_won_by_the_longer_snake() {
+        let mut state = versus();
+        state.players[1]
+            .snake
+            .grow(&mut ChaCha8Rng::seed_from_u64(1));
+
+        step_all(&mut state, &[None, None]);
+        assert_eq!(step_all(&mut state, &[None, None]), vec![Event::GameOver]);
+        assert!(!state.get_players()[0].is_alive());
+        assert!(state.get_players()[1].is_alive());
+        assert_eq!(state.get_winner(), Some(1));
+    }
+}
@@ -1,121 +1,165 @@
 use std::collections::LinkedList;
 
-use piston_window::{types::Color, Context, G2d};
 use rand::Rng;
 
-use crate::colors;
+use crate::board::Board;
+use crate::colors::{Color, Palette};
 use crate::draw::*;
 use crate::physics::{Direction, Position};
+use crate::render::Renderer;
 
-const INITIAL_SNAKE_TAIL_LENGTH: usize = 2;
+/// Length of a new snake, including its head.
+pub const DEFAULT_START_LENGTH: usize = 3;
 
+#[derive(Clone)]
 pub struct Snake {
     direction: Direction,
     head: Position,
     tail: LinkedList<Position>,
-    updated_tail_pos: bool,
-    color: Color,
+    // Where the head was before the last update, so a snake without a tail
+    // knows where to grow
+    previous_head: Position,
+    start_length: usize,
+    // Random color picked when eating, `None` means the palette's color
+    color: Option<Color>,
 }
 
 impl Snake {
-    pub fn new(head: Position) -> Self {
-        let (x, y) = (head.x, head.y);
+    pub fn new(head: Position, direction: Direction, length: usize, board: &Board) -> Self {
         let mut tail = LinkedList::new();
-
-        for i in 1..=INITIAL_SNAKE_TAIL_LENGTH {
-            tail.push_back(Position { x, y: y - i as i32 });
+        let mut segment = head.clone();
+
+        // The tail starts out straight behind the head. On a walled board it
+        // may stick out of the edge, on a wrapping board it continues on
+        // the opposite side.
+        for _ in 1..length {
+            segment = board.neighbor(&segment, direction.opposite());
+            tail.push_back(segment.clone());
         }
 
         Self {
-            direction: Direction::Down,
-            head: Position { x, y },
+            direction,
+            previous_head: head.clone(),
+            head,
             tail,
-            updated_tail_pos: false,
-            color: colors::SNAKE,
+            start_length: length.max(1),
+            color: None,
         }
     }
 
-    pub fn update(&mut self, _width: u32, _height: u32) {
+    pub fn update(&mut self, board: &Board) {
         if !self.tail.is_empty() {
             self.tail.push_front(self.head.clone());
             self.tail.pop_back();
         }
 
-        match self.direction {
-            Direction::Up => self.head.y -= 1,
-            Direction::Right => self.head.x += 1,
-            Direction::Down => self.head.y += 1,
-            Direction::Left => self.head.x -= 1,
-        }
-
-        // Wall wrapping removed - collision is now checked separately
-        self.updated_tail_pos = true;
+        // Wrapping (if enabled) is handled by the board, collision with the
+        // walls is checked separately
+        let next = board.neighbor(&self.head, self.direction);
+        self.previous_head = std::mem::replace(&mut self.head, next);
     }
 
     /// Check if the snake will hit a wall on the next move
-    pub fn will_hit_wall(&self, width: u32, height: u32) -> bool {
-        let next = self.next_head_pos();
-        next.x < 0 || next.x >= width as i32 || next.y < 0 || next.y >= height as i32
+    pub fn will_hit_wall(&self, board: &Board) -> bool {
+        !board.contains(&self.next_head_pos(board))
     }
 
-    pub fn draw(&self, ctx: &Context, g: &mut G2d) {
+    /// Check if the snake will hit one of the level's obstacles on the next
+    /// move
+    pub fn will_hit_obstacle(&self, board: &Board) -> bool {
+        board.is_wall(&self.next_head_pos(board))
+    }
+
+    pub fn draw(&self, r: &mut dyn Renderer, palette: &Palette) {
+        let color = self.color.unwrap_or(palette.snake);
+        self.draw_colored(r, color, palette.background);
+    }
+
+    /// Draw the snake in a fixed color, ignoring the one picked when eating.
+    pub fn draw_colored(&self, r: &mut dyn Renderer, color: Color, eye: Color) {
         for block in self.tail.iter() {
-            draw_block(ctx, g, self.color, block);
+            draw_block(r, color, block);
         }
 
-        draw_snake_head(ctx, g, self.color, &self.head, &self.direction);
+        draw_snake_head(r, color, eye, &self.head, &self.direction);
     }
 
     pub fn set_dir(&mut self, dir: Direction) {
-        if dir == self.direction.opposite() || !self.updated_tail_pos {
+        // Only one turn is applied per tick (see `InputQueue`), so checking
+        // against the current direction is enough to stop reversals.
+        if dir == self.direction.opposite() {
             return;
         }
 
         self.direction = dir;
-        self.updated_tail_pos = false;
     }
 
-    pub fn grow(&mut self) {
-        let last = self.tail.back().cloned().unwrap_or(self.head.clone());
+    pub fn get_dir(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn grow(&mut self, rng: &mut impl Rng) {
+        let last = self
+            .tail
+            .back()
+            .cloned()
+            .unwrap_or_else(|| self.previous_head.clone());
         self.tail.push_back(last);
 
         // 🎨 randomize color
-        let mut rng = rand::thread_rng();
-        self.color = [
+        self.color = Some([
             rng.gen_range(0.2..1.0),
             rng.gen_range(0.2..1.0),
             rng.gen_range(0.2..1.0),
             1.0,
-        ];
+        ]);
+    }
+
+    /// Take up to `n` segments off the end of the tail, never going below
+    /// the starting length.
+    pub fn shrink(&mut self, n: usize) {
+        for _ in 0..n {
+            if self.get_body_len() <= self.start_length {
+                break;
+            }
+            self.tail.pop_back();
+        }
     }
 
     pub fn get_head_pos(&self) -> &Position {
         &self.head
     }
 
+    /// Every cell covered by the snake, head first.
+    pub fn cells(&self) -> impl Iterator<Item = &Position> {
+        std::iter::once(&self.head).chain(self.tail.iter())
+    }
+
+    pub fn occupies(&self, pos: &Position) -> bool {
+        self.cells().any(|cell| cell == pos)
+    }
+
     pub fn is_tail_overlapping(&self) -> bool {
         self.tail.iter().any(|pos| *pos == self.head)
     }
 
-    fn next_head_pos(&self) -> Position {
-        let mut pos = self.head.clone();
+    fn next_head_pos(&self, board: &Board) -> Position {
+        board.neighbor(&self.head, self.direction)
+    }
 
-        match self.direction {
-            Direction::Up => pos.y -= 1,
-            Direction::Left => pos.x -= 1,
-            Direction::Down => pos.y += 1,
-            Direction::Right => pos.x += 1,
-        }
+    pub fn will_tail_overlapp(&self, board: &Board) -> bool {
+        let next = self.next_head_pos(board);
 
-        pos
+        self.tail.iter().any(|pos| *pos == next)
     }
-    pub fn will_tail_overlapp(&self) -> bool {
-        let next = self.next_head_pos();
 
-        self.tail.iter().any(|pos| *pos == next)
+    /// Number of segments including the head. Right after growing this is
+    /// one more than the number of cells covered.
+    pub fn get_body_len(&self) -> usize {
+        self.tail.len() + 1
     }
 
     pub fn get_len(&self) -> usize {
-        self.tail.len() - INITIAL_SNAKE_TAIL_LENGTH
+        self.tail.len() + 1 - self.start_length
     }
 }
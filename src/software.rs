@@ -0,0 +1,291 @@
+//! Drawing into memory on the CPU, without a window or a GPU.
+//!
+//! A `Pixmap` is an RGBA buffer that shapes are filled into by sampling
+//! every pixel on a small grid, which smooths the edges. At very low
+//! resolutions, like the 2x2 pixels per cell of the terminal, smoothing
+//! only blurs the details away, so it can be turned off to sample just the
+//! center of every pixel.
+//!
+//! With the `images` feature a `SoftwareRenderer` draws through `Renderer`
+//! into a pixmap, with text in a TrueType font, and pixmaps can be saved as
+//! PNG. This is how screenshots are taken without a window.
+
+#[cfg(feature = "images")]
+use ab_glyph::{point, Font as _, FontVec, PxScale, ScaleFont};
+#[cfg(feature = "images")]
+use std::fs::{self, File};
+#[cfg(feature = "images")]
+use std::io::{self, BufWriter};
+#[cfg(feature = "images")]
+use std::path::Path;
+
+use crate::colors::Color;
+#[cfg(feature = "images")]
+use crate::render::{Renderer, Transform};
+
+/// Samples per pixel across and down when smoothing edges
+const SAMPLES: usize = 4;
+
+pub struct Pixmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    samples: usize,
+}
+
+impl Pixmap {
+    /// A `width` x `height` pixmap filled with `color`.
+    pub fn new(width: usize, height: usize, color: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color; width * height],
+            samples: SAMPLES,
+        }
+    }
+
+    /// Whether to smooth the edges of shapes, on by default.
+    pub fn set_smooth(&mut self, smooth: bool) {
+        self.samples = if smooth { SAMPLES } else { 1 };
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// The pixels row by row as 8-bit RGBA.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .iter()
+            .flat_map(|pixel| pixel.iter().map(|v| channel(*v)))
+            .collect()
+    }
+
+    /// Write the pixmap to a PNG file at `path`.
+    #[cfg(feature = "images")]
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer
+            .write_image_data(&self.to_rgba8())
+            .map_err(io::Error::other)
+    }
+
+    /// Mix `color` into the pixel at `x`, `y`, `coverage` is the part of
+    /// the pixel it covers.
+    pub fn blend(&mut self, x: usize, y: usize, color: Color, coverage: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let pixel = &mut self.pixels[y * self.width + x];
+        let alpha = color[3] * coverage;
+        for i in 0..3 {
+            pixel[i] += (color[i] - pixel[i]) * alpha;
+        }
+        pixel[3] += (1.0 - pixel[3]) * alpha;
+    }
+
+    /// Fill the pixels within `bounds` (`[x, y, width, height]`) by how many
+    /// of their samples are `inside` the shape.
+    fn fill(&mut self, color: Color, bounds: [f64; 4], inside: impl Fn(f64, f64) -> bool) {
+        let x0 = bounds[0].floor().max(0.0) as usize;
+        let y0 = bounds[1].floor().max(0.0) as usize;
+        let x1 = ((bounds[0] + bounds[2]).ceil().max(0.0) as usize).min(self.width);
+        let y1 = ((bounds[1] + bounds[3]).ceil().max(0.0) as usize).min(self.height);
+        let n = self.samples;
+        let step = 1.0 / n as f64;
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let mut hits = 0;
+                for sy in 0..n {
+                    for sx in 0..n {
+                        let px = x as f64 + (sx as f64 + 0.5) * step;
+                        let py = y as f64 + (sy as f64 + 0.5) * step;
+                        if inside(px, py) {
+                            hits += 1;
+                        }
+                    }
+                }
+                if hits > 0 {
+                    self.blend(x, y, color, hits as f32 / (n * n) as f32);
+                }
+            }
+        }
+    }
+
+    /// Fill `rect`, `[x, y, width, height]` in pixels. Its edges are
+    /// rounded to whole pixels, so rectangles that touch, like the cells of
+    /// the board, leave no seam between them at any scale.
+    pub fn fill_rect(&mut self, color: Color, rect: [f64; 4]) {
+        let [x, y, w, h] = rect;
+        let (x0, y0) = (x.round(), y.round());
+        let (x1, y1) = ((x + w).round(), (y + h).round());
+        self.fill(color, [x0, y0, x1 - x0, y1 - y0], |px, py| {
+            px >= x0 && px < x1 && py >= y0 && py < y1
+        });
+    }
+
+    /// Fill the ellipse inside `rect`.
+    pub fn fill_ellipse(&mut self, color: Color, rect: [f64; 4]) {
+        let [x, y, w, h] = rect;
+        let (rx, ry) = (w / 2.0, h / 2.0);
+        let (cx, cy) = (x + rx, y + ry);
+        self.fill(color, rect, |px, py| {
+            let (dx, dy) = ((px - cx) / rx, (py - cy) / ry);
+            dx * dx + dy * dy <= 1.0
+        });
+    }
+
+    /// Fill a convex polygon given in either winding order.
+    pub fn fill_polygon(&mut self, color: Color, points: &[[f64; 2]]) {
+        if points.len() < 3 {
+            return;
+        }
+        let (mut min, mut max) = (points[0], points[0]);
+        for p in points.iter() {
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+
+        let bounds = [min[0], min[1], max[0] - min[0], max[1] - min[1]];
+        self.fill(color, bounds, |px, py| {
+            let mut sign = 0.0;
+            for (i, a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                let cross = (b[0] - a[0]) * (py - a[1]) - (b[1] - a[1]) * (px - a[0]);
+                if cross * sign < 0.0 {
+                    return false;
+                }
+                if cross != 0.0 {
+                    sign = cross;
+                }
+            }
+            true
+        });
+    }
+}
+
+/// A TrueType font to write text with, `retro-gaming.ttf` for the HUD.
+#[cfg(feature = "images")]
+pub struct Font(FontVec);
+
+#[cfg(feature = "images")]
+impl Font {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        FontVec::try_from_vec(data)
+            .map(Font)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Draws into a pixmap, in pixels until the transform is changed.
+#[cfg(feature = "images")]
+pub struct SoftwareRenderer<'a> {
+    pixmap: Pixmap,
+    font: &'a Font,
+    transform: Transform,
+}
+
+#[cfg(feature = "images")]
+impl<'a> SoftwareRenderer<'a> {
+    pub fn new(pixmap: Pixmap, font: &'a Font) -> Self {
+        Self {
+            pixmap,
+            font,
+            transform: Transform::default(),
+        }
+    }
+
+    pub fn get_pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.pixmap
+    }
+}
+
+#[cfg(feature = "images")]
+impl Renderer for SoftwareRenderer<'_> {
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn fill_rect(&mut self, color: Color, rect: [f64; 4]) {
+        self.pixmap
+            .fill_rect(color, self.transform.apply_rect(rect));
+    }
+
+    fn fill_ellipse(&mut self, color: Color, rect: [f64; 4]) {
+        self.pixmap
+            .fill_ellipse(color, self.transform.apply_rect(rect));
+    }
+
+    fn fill_polygon(&mut self, color: Color, points: &[[f64; 2]]) {
+        let points: Vec<[f64; 2]> = points.iter().map(|p| self.transform.apply(*p)).collect();
+        self.pixmap.fill_polygon(color, &points);
+    }
+
+    fn text(&mut self, color: Color, size: u32, text: &str, x: f64, y: f64) {
+        let [x, y] = self.transform.apply([x, y]);
+        let scale = PxScale::from((size as f64 * self.transform.get_scale()[1]) as f32);
+        let font = &self.font.0;
+        let scaled = font.as_scaled(scale);
+
+        let mut caret = x as f32;
+        let mut previous = None;
+        for ch in text.chars() {
+            let id = scaled.glyph_id(ch);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            previous = Some(id);
+            let glyph = id.with_scale_and_position(scale, point(caret, y as f32));
+            caret += scaled.h_advance(id);
+
+            let outline = match font.outline_glyph(glyph) {
+                Some(outline) => outline,
+                // Spaces have nothing to draw
+                None => continue,
+            };
+            let bounds = outline.px_bounds();
+            let pixmap = &mut self.pixmap;
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x + gx as f32;
+                let py = bounds.min.y + gy as f32;
+                if px >= 0.0 && py >= 0.0 {
+                    pixmap.blend(px as usize, py as usize, color, coverage);
+                }
+            });
+        }
+    }
+
+    fn text_width(&mut self, size: u32, text: &str) -> f64 {
+        let scaled = self.font.0.as_scaled(PxScale::from(size as f32));
+        let mut width = 0.0;
+        let mut previous = None;
+        for ch in text.chars() {
+            let id = scaled.glyph_id(ch);
+            if let Some(previous) = previous {
+                width += scaled.kern(previous, id);
+            }
+            previous = Some(id);
+            width += scaled.h_advance(id);
+        }
+        width as f64
+    }
+}
@@ -0,0 +1,53 @@
+//! Sound effects for eating the different kinds of fruit.
+//!
+//! Normal fruit plays `appleobtained.ogg` from the assets, the special ones
+//! get short synthesized jingles so they don't need extra asset files.
+
+use rodio::source::SineWave;
+use rodio::{Decoder, Sink, Source};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::fruit::FruitKind;
+
+const TONE_VOLUME: f32 = 0.25;
+
+/// Queue a sine tone of `millis` milliseconds on `sink`.
+fn tone(sink: &Sink, freq: f32, millis: u64) {
+    sink.append(
+        SineWave::new(freq)
+            .take_duration(Duration::from_millis(millis))
+            .amplify(TONE_VOLUME),
+    );
+}
+
+pub fn play_fruit_sound(sink: &Sink, assets: &Path, kind: FruitKind) {
+    match kind {
+        FruitKind::Normal => {
+            if let Ok(file) = File::open(assets.join("appleobtained.ogg")) {
+                if let Ok(source) = Decoder::new(BufReader::new(file)) {
+                    sink.append(source);
+                }
+            }
+        }
+        // Rising major arpeggio
+        FruitKind::Golden => {
+            for freq in [1047.0, 1319.0, 1568.0, 2093.0].iter() {
+                tone(sink, *freq, 70);
+            }
+        }
+        // Two low, falling notes
+        FruitKind::Poison => {
+            tone(sink, 220.0, 150);
+            tone(sink, 147.0, 250);
+        }
+        // Quick upward sweep
+        FruitKind::Speed => {
+            for step in 0..8 {
+                tone(sink, 400.0 + step as f32 * 120.0, 25);
+            }
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Watching live games from another instance.
+//!
+//! A game started with a spectator port accepts any number of read-only
+//! clients. Since `sim` is deterministic they only need the inputs: a new
+//! spectator gets the game so far as a replay and plays it through at once
+//! to catch up, then one message per tick with the inputs of every player.
+//! A restart, the next round or a new campaign level sends a new replay.
+//! Messages are JSON lines:
+//!
+//! ```text
+//! {"type":"game","version":1,"best_of":3,"replay":"rsnake-replay 1\nseed 42\n...\ninputs 120\n..."}
+//! {"type":"tick","tick":120,"inputs":"L."}
+//! {"type":"bye"}
+//! ```
+//!
+//! `best_of` is `null` for a game with one snake. `inputs` has one
+//! character per player, as in the replay format.
+//! Spectators never send anything.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::netplay::{send, spawn_reader};
+use crate::physics::Direction;
+use crate::replay::{char_to_input, input_to_char, Replay};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7879;
+/// Time a spectator gets to take a message before it is dropped, so a
+/// stuck one can't hold up the others for long
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+/// Time between two looks for new spectators while the game stands still
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+/// Time to wait for the game when connecting
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Game {
+        version: u32,
+        best_of: Option<u32>,
+        replay: String,
+    },
+    Tick {
+        tick: u64,
+        inputs: String,
+    },
+    Bye,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// What the game hands to the broadcaster thread.
+enum Update {
+    /// A new game, with the ticks played of it so far
+    Game {
+        replay: Box<Replay>,
+        best_of: Option<u32>,
+    },
+    /// The inputs of every player for the next tick
+    Tick(Vec<Option<Direction>>),
+}
+
+/// The playing side, sending the game to every spectator. The writes
+/// happen on a thread of their own, so spectators on a slow connection
+/// don't hold up the game.
+pub struct Broadcaster {
+    updates: Option<Sender<Update>>,
+    thread: Option<JoinHandle<()>>,
+    spectators: Arc<AtomicUsize>,
+    // Seed and rules of the game being sent, and its ticks sent so far
+    game: Option<Replay>,
+    best_of: Option<u32>,
+    sent: u64,
+    // Seed, tick and match length at the last `sync`
+    synced: Option<(u64, u64, Option<u32>)>,
+}
+
+fn broadcast(spectators: &mut Vec<TcpStream>, message: &Message) {
+    spectators.retain_mut(|stream| {
+        let sent = send(stream, message).is_ok();
+        if !sent {
+            println!("Spectator left");
+        }
+        sent
+    });
+}
+
+/// Send every update to the spectators and welcome new ones with the
+/// game so far, until the `Broadcaster` is dropped.
+fn run(listener: TcpListener, updates: Receiver<Update>, count: Arc<AtomicUsize>) {
+    let mut spectators = Vec::new();
+    // The game so far and the rounds of its match
+    let mut game: Option<(Box<Replay>, Option<u32>)> = None;
+
+    loop {
+        match updates.recv_timeout(ACCEPT_INTERVAL) {
+            Ok(Update::Game { replay, best_of }) => {
+                broadcast(
+                    &mut spectators,
+                    &Message::Game {
+                        version: PROTOCOL_VERSION,
+                        best_of,
+                        replay: replay.serialize(),
+                    },
+                );
+                game = Some((replay, best_of));
+            }
+            Ok(Update::Tick(inputs)) => {
+                if let Some((replay, _)) = &mut game {
+                    let tick = replay.ticks();
+                    replay.record(&inputs);
+                    let inputs = inputs.iter().map(|input| input_to_char(*input)).collect();
+                    broadcast(&mut spectators, &Message::Tick { tick, inputs });
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // After the broadcast, so the newcomers' replay already has it all
+        if let Some((replay, best_of)) = &game {
+            while let Ok((mut stream, addr)) = listener.accept() {
+                let welcome = stream
+                    .set_nonblocking(false)
+                    .and_then(|_| stream.set_nodelay(true))
+                    .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+                    .and_then(|_| {
+                        send(
+                            &mut stream,
+                            &Message::Game {
+                                version: PROTOCOL_VERSION,
+                                best_of: *best_of,
+                                replay: replay.serialize(),
+                            },
+                        )
+                    });
+                match welcome {
+                    Ok(()) => {
+                        println!("Spectator connected from {}", addr);
+                        spectators.push(stream);
+                    }
+                    Err(err) => eprintln!("Spectator {} could not join: {}", addr, err),
+                }
+            }
+        }
+        count.store(spectators.len(), Ordering::Relaxed);
+    }
+
+    broadcast(&mut spectators, &Message::Bye);
+    for stream in spectators.iter() {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl Broadcaster {
+    /// Accept spectators on `port`.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+
+        let (updates, receiver) = mpsc::channel();
+        let spectators = Arc::new(AtomicUsize::new(0));
+        let count = Arc::clone(&spectators);
+        let thread = thread::spawn(move || run(listener, receiver, count));
+
+        Ok(Self {
+            updates: Some(updates),
+            thread: Some(thread),
+            spectators,
+            game: None,
+            best_of: None,
+            sent: 0,
+            synced: None,
+        })
+    }
+
+    /// Number of spectators watching.
+    pub fn get_spectators(&self) -> usize {
+        self.spectators.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, update: Update) {
+        if let Some(updates) = &self.updates {
+            // Only fails if the thread is gone, with it the spectators
+            let _ = updates.send(update);
+        }
+    }
+
+    /// Bring every spectator up to date with the game with `seed` at
+    /// `tick`, and `best_of`, the rounds of the match if it is one: new
+    /// spectators get all of it, the others the ticks since the last call
+    /// or the new game if it changed. `played` is the game up to its
+    /// current tick, only asked for when the tick changed.
+    pub fn sync(
+        &mut self,
+        (seed, tick): (u64, u64),
+        best_of: Option<u32>,
+        played: impl FnOnce() -> Replay,
+    ) {
+        if self.synced == Some((seed, tick, best_of)) {
+            return;
+        }
+        self.synced = Some((seed, tick, best_of));
+
+        let played = played();
+        let header = Replay::new(played.seed, played.rules.clone());
+        if self.game.as_ref() != Some(&header)
+            || self.best_of != best_of
+            || played.ticks() < self.sent
+        {
+            self.sent = played.ticks();
+            self.game = Some(header);
+            self.best_of = best_of;
+            self.send(Update::Game {
+                replay: Box::new(played),
+                best_of,
+            });
+        } else {
+            for tick in self.sent..played.ticks() {
+                self.send(Update::Tick(played.inputs_at(tick).to_vec()));
+            }
+            self.sent = played.ticks();
+        }
+    }
+}
+
+impl Drop for Broadcaster {
+    fn drop(&mut self) {
+        // Closing the channel tells the thread to say bye
+        self.updates = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// What a spectator receives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feed {
+    /// A game to catch up with, replacing the current one
+    Game(Box<Replay>),
+    /// The inputs of every player for the next tick
+    Tick(Vec<Option<Direction>>),
+    /// The game is gone, with the reason
+    Ended(String),
+}
+
+/// The watching side.
+pub struct Spectator {
+    messages: Receiver<Result<Message, String>>,
+    // Ticks of the current game received so far
+    ticks: u64,
+    best_of: Option<u32>,
+    // Why the stream ended
+    ended: Option<String>,
+}
+
+impl Spectator {
+    /// Connect to the game at `addr` (`host:port`). Returns the game so far.
+    pub fn connect(addr: &str) -> io::Result<(Self, Replay)> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut spectator = Self {
+            messages: spawn_reader(stream),
+            ticks: 0,
+            best_of: None,
+            ended: None,
+        };
+
+        let message = spectator
+            .messages
+            .recv_timeout(CONNECT_TIMEOUT)
+            .map_err(|_| invalid("no game from the host".to_string()))?
+            .map_err(invalid)?;
+        match spectator.receive(message) {
+            Feed::Game(replay) => Ok((spectator, *replay)),
+            Feed::Ended(reason) => Err(invalid(reason)),
+            Feed::Tick(_) => Err(invalid("expected a game first".to_string())),
+        }
+    }
+
+    /// Rounds of the match being watched, `None` for a game with one snake.
+    pub fn get_best_of(&self) -> Option<u32> {
+        self.best_of
+    }
+
+    /// Why the stream ended, `None` while it goes on.
+    pub fn get_ended(&self) -> Option<&str> {
+        self.ended.as_deref()
+    }
+
+    fn end(&mut self, reason: String) -> Feed {
+        self.ended = Some(reason.clone());
+        Feed::Ended(reason)
+    }
+
+    fn receive(&mut self, message: Message) -> Feed {
+        match message {
+            Message::Game { version, .. } if version != PROTOCOL_VERSION => self.end(format!(
+                "the game speaks version {}, expected {}",
+                version, PROTOCOL_VERSION
+            )),
+            Message::Game {
+                best_of, replay, ..
+            } => match Replay::parse(&replay) {
+                Ok(replay) => {
+                    self.ticks = replay.ticks();
+                    self.best_of = best_of;
+                    Feed::Game(Box::new(replay))
+                }
+                Err(err) => self.end(format!("invalid game: {}", err)),
+            },
+            Message::Tick { tick, .. } if tick != self.ticks => {
+                self.end(format!("tick {} out of order", tick))
+            }
+            Message::Tick { inputs, .. } => {
+                let inputs: Option<Vec<_>> = inputs.chars().map(char_to_input).collect();
+                match inputs {
+                    Some(inputs) => {
+                        self.ticks += 1;
+                        Feed::Tick(inputs)
+                    }
+                    None => self.end("invalid inputs".to_string()),
+                }
+            }
+            Message::Bye => self.end("the game was closed".to_string()),
+        }
+    }
+
+    /// Everything received since the last call, in order.
+    pub fn poll(&mut self) -> Vec<Feed> {
+        let mut feed = Vec::new();
+
+        while self.ended.is_none() {
+            let item = match self.messages.try_recv() {
+                Ok(Ok(message)) => self.receive(message),
+                Ok(Err(err)) => self.end(err),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.end("connection closed".to_string()),
+            };
+            feed.push(item);
+        }
+
+        feed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, Topology};
+    use crate::sim::Rules;
+    use std::time::Instant;
+
+    fn broadcaster() -> (Broadcaster, String) {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        (
+            Broadcaster::bind(port).unwrap(),
+            format!("127.0.0.1:{}", port),
+        )
+    }
+
+    /// Everything `spectator` gets until it has seen `count` items.
+    fn receive(spectator: &mut Spectator, count: usize) -> Vec<Feed> {
+        let started = Instant::now();
+        let mut feed = Vec::new();
+        while feed.len() < count && started.elapsed() < CONNECT_TIMEOUT {
+            feed.extend(spectator.poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+        feed
+    }
+
+    #[test]
+    fn sends_only_new_ticks() {
+        let (mut broadcaster, addr) = broadcaster();
+        let mut replay = Replay::new(5, Rules::new(Board::new(10, 10, Topology::Walled)));
+        replay.record(&[Some(Direction::Up)]);
+        broadcaster.sync((5, 1), None, || replay.clone());
+
+        let (mut spectator, caught_up) = Spectator::connect(&addr).unwrap();
+        assert_eq!(caught_up, replay);
+
+        // Nothing happened, so there is nothing to copy
+        broadcaster.sync((5, 1), None, || panic!("the same tick again"));
+
+        replay.record(&[None]);
+        replay.record(&[Some(Direction::Left)]);
+        broadcaster.sync((5, 3), None, || replay.clone());
+        assert_eq!(
+            receive(&mut spectator, 2),
+            vec![
+                Feed::Tick(vec![None]),
+                Feed::Tick(vec![Some(Direction::Left)])
+            ]
+        );
+
+        // A restart sends the new game
+        let restarted = Replay::new(6, replay.rules.clone());
+        broadcaster.sync((6, 0), None, || restarted.clone());
+        assert_eq!(
+            receive(&mut spectator, 1),
+            vec![Feed::Game(Box::new(restarted))]
+        );
+
+        drop(broadcaster);
+        assert!(matches!(receive(&mut spectator, 1)[..], [Feed::Ended(_)]));
+    }
+}
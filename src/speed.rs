@@ -0,0 +1,97 @@
+//! Difficulty progression: the snake speeds up as it grows.
+
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub const DEFAULT_TICK_RATE: f64 = 10.0;
+pub const DEFAULT_MAX_TICK_RATE: f64 = 20.0;
+pub const DEFAULT_RAMP: f64 = 50.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    /// Always tick at the start rate
+    Constant,
+    /// Same speed-up for every fruit until the maximum is reached
+    Linear,
+    /// Big speed-ups early on that flatten out towards the maximum
+    Exponential,
+}
+
+impl FromStr for Curve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "constant" => Ok(Curve::Constant),
+            "linear" => Ok(Curve::Linear),
+            "exponential" => Ok(Curve::Exponential),
+            _ => Err(format!(
+                "unknown speed curve '{}', expected constant, linear or exponential",
+                s
+            )),
+        }
+    }
+}
+
+/// Maps the length the snake has grown by to a tick rate. Can be set in the
+/// `[speed]` section of the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeedCurve {
+    /// Ticks per second at the start of a game
+    pub start: f64,
+    /// Ticks per second the curve never goes above
+    pub max: f64,
+    pub curve: Curve,
+    /// Fruits it takes to reach `max` (linear) or to get about 95% of the
+    /// way there (exponential)
+    pub ramp: f64,
+}
+
+impl Default for SpeedCurve {
+    fn default() -> Self {
+        Self {
+            start: DEFAULT_TICK_RATE,
+            max: DEFAULT_MAX_TICK_RATE,
+            curve: Curve::Linear,
+            ramp: DEFAULT_RAMP,
+        }
+    }
+}
+
+impl SpeedCurve {
+    /// Ticks per second for a snake that has grown by `len`.
+    pub fn tick_rate(&self, len: usize) -> f64 {
+        let progress = len as f64 / self.ramp;
+        let t = match self.curve {
+            Curve::Constant => 0.0,
+            Curve::Linear => progress.min(1.0),
+            Curve::Exponential => 1.0 - (-3.0 * progress).exp(),
+        };
+
+        self.start + (self.max - self.start).max(0.0) * t
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1.0..=60.0).contains(&self.start) {
+            return Err(format!(
+                "speed.start must be between 1 and 60, got {}",
+                self.start
+            ));
+        }
+
+        if !(self.start..=60.0).contains(&self.max) {
+            return Err(format!(
+                "speed.max must be between speed.start ({}) and 60, got {}",
+                self.start, self.max
+            ));
+        }
+
+        if self.ramp.is_nan() || self.ramp <= 0.0 {
+            return Err(format!("speed.ramp must be positive, got {}", self.ramp));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+//! Text-mode frontend for terminals without a display server, e.g. over SSH.
+//!
+//! The board is drawn with Unicode half blocks in 24-bit ANSI colors: the
+//! upper half block `▀` shows one pixel in its foreground color and the one
+//! below it in its background color. A board cell is two columns wide and
+//! one row high, which makes it a square of 2x2 pixels, just enough for
+//! the eyes of the snake. Text is written over the pixels, one character
+//! per column, on the color of the upper pixel. The board is drawn through
+//! `Renderer` like in the window.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{self, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, terminal, QueueableCommand};
+use piston_window::keyboard::Key;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crate::colors::Color;
+use crate::keys::parse_key;
+use crate::render::{Renderer, Transform};
+use crate::software::Pixmap;
+
+/// Pixels per board cell, across and down
+pub const CELL_PIXELS: usize = 2;
+/// Columns per board cell
+pub const CELL_COLUMNS: usize = 2;
+
+const UPPER_HALF_BLOCK: char = '▀';
+
+/// A frame of the terminal in pixels, plus text on top of them. Its own
+/// units are pixels, one column wide and half a row high.
+pub struct Canvas {
+    columns: usize,
+    rows: usize,
+    pixmap: Pixmap,
+    text: Vec<Option<(char, Color)>>,
+    // Row the board starts at, so there is room for text above it
+    board_row: usize,
+    transform: Transform,
+}
+
+impl Canvas {
+    /// A canvas for a board of `width` x `height` cells, with `above` and
+    /// `below` rows of text around it.
+    pub fn new(width: u32, height: u32, above: usize, below: usize, color: Color) -> Self {
+        let columns = width as usize * CELL_COLUMNS;
+        let rows = above + height as usize + below;
+        let mut pixmap = Pixmap::new(columns, rows * 2, color);
+        pixmap.set_smooth(false);
+
+        Self {
+            columns,
+            rows,
+            pixmap,
+            text: vec![None; columns * rows],
+            board_row: above,
+            transform: Transform::default(),
+        }
+    }
+
+    /// Size in columns and rows.
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Transform to draw the board in cells.
+    pub fn board_transform(&self) -> Transform {
+        Transform::default()
+            .trans(0.0, (self.board_row * 2) as f64)
+            .scale(CELL_PIXELS as f64, CELL_PIXELS as f64)
+    }
+
+    /// Write `text` starting at `column` of `row`, cut off at the edge.
+    pub fn print(&mut self, column: usize, row: usize, text: &str, c: Color) {
+        if row >= self.rows {
+            return;
+        }
+        for (i, ch) in text.chars().enumerate() {
+            if column + i >= self.columns {
+                break;
+            }
+            self.text[row * self.columns + column + i] = Some((ch, c));
+        }
+    }
+
+    /// Write `text` centered on `row`.
+    pub fn print_centered(&mut self, row: usize, text: &str, c: Color) {
+        let len = text.chars().count();
+        self.print(self.columns.saturating_sub(len) / 2, row, text, c);
+    }
+
+    /// Row of the canvas that board row `y` is drawn on.
+    pub fn board_row(&self, y: u32) -> usize {
+        self.board_row + y as usize
+    }
+
+    fn cell(&self, column: usize, row: usize) -> TermCell {
+        let upper = self.pixmap.get_pixel(column, row * 2);
+        let lower = self.pixmap.get_pixel(column, row * 2 + 1);
+        match self.text[row * self.columns + column] {
+            Some((ch, c)) => TermCell {
+                ch,
+                fg: rgb(c),
+                bg: rgb(upper),
+            },
+            None => TermCell {
+                ch: UPPER_HALF_BLOCK,
+                fg: rgb(upper),
+                bg: rgb(lower),
+            },
+        }
+    }
+}
+
+impl Renderer for Canvas {
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn fill_rect(&mut self, color: Color, rect: [f64; 4]) {
+        self.pixmap
+            .fill_rect(color, self.transform.apply_rect(rect));
+    }
+
+    fn fill_ellipse(&mut self, color: Color, rect: [f64; 4]) {
+        self.pixmap
+            .fill_ellipse(color, self.transform.apply_rect(rect));
+    }
+
+    fn fill_polygon(&mut self, color: Color, points: &[[f64; 2]]) {
+        let points: Vec<[f64; 2]> = points.iter().map(|p| self.transform.apply(*p)).collect();
+        self.pixmap.fill_polygon(color, &points);
+    }
+
+    /// Text goes on the row the baseline is at the bottom of, whatever
+    /// its size.
+    fn text(&mut self, color: Color, _size: u32, text: &str, x: f64, y: f64) {
+        let [x, y] = self.transform.apply([x, y]);
+        let row = (y / 2.0).ceil() - 1.0;
+        if x >= 0.0 && row >= 0.0 {
+            self.print(x as usize, row as usize, text, color);
+        }
+    }
+
+    fn text_width(&mut self, _size: u32, text: &str) -> f64 {
+        text.chars().count() as f64 / self.transform.get_scale()[0]
+    }
+}
+
+fn rgb(c: Color) -> [u8; 3] {
+    let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(c[0]), channel(c[1]), channel(c[2])]
+}
+
+fn term_color(c: [u8; 3]) -> style::Color {
+    style::Color::Rgb {
+        r: c[0],
+        g: c[1],
+        b: c[2],
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct TermCell {
+    ch: char,
+    fg: [u8; 3],
+    bg: [u8; 3],
+}
+
+/// `Err` unless the terminal has at least `columns` x `rows`.
+fn check_size(columns: usize, rows: usize) -> io::Result<()> {
+    let (have_columns, have_rows) = terminal::size()?;
+    if (have_columns as usize) < columns || (have_rows as usize) < rows {
+        return Err(io::Error::other(format!(
+            "the terminal is {}x{}, the game needs {}x{}",
+            have_columns, have_rows, columns, rows
+        )));
+    }
+
+    Ok(())
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+pub struct Screen {
+    stdout: Stdout,
+    // What is on the screen, to only send the cells that changed
+    shown: Vec<TermCell>,
+    columns: usize,
+}
+
+impl Screen {
+    /// Take over a terminal of at least `columns` x `rows`.
+    pub fn open(columns: usize, rows: usize) -> io::Result<Self> {
+        check_size(columns, rows)?;
+
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        stdout
+            .queue(terminal::EnterAlternateScreen)?
+            .queue(cursor::Hide)?
+            .queue(terminal::Clear(terminal::ClearType::All))?;
+        stdout.flush()?;
+
+        Ok(Self {
+            stdout,
+            shown: Vec::new(),
+            columns: 0,
+        })
+    }
+
+    /// Make room for a canvas of `columns` x `rows`, e.g. for a game on
+    /// another board. `Err` if the terminal is too small for it.
+    pub fn resize(&mut self, columns: usize, rows: usize) -> io::Result<()> {
+        check_size(columns, rows)?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Draw everything again on the next `present`, e.g. after a resize.
+    pub fn invalidate(&mut self) {
+        self.shown.clear();
+    }
+
+    /// Show `canvas`, sending only what changed since the last frame.
+    pub fn present(&mut self, canvas: &Canvas) -> io::Result<()> {
+        let (columns, rows) = canvas.size();
+        if self.columns != columns || self.shown.len() != columns * rows {
+            self.stdout
+                .queue(terminal::Clear(terminal::ClearType::All))?;
+            self.shown.clear();
+            self.columns = columns;
+        }
+        let redraw = self.shown.is_empty();
+
+        // Colors and position of the cursor after the last cell written
+        let mut current: Option<([u8; 3], [u8; 3])> = None;
+        let mut cursor_at = None;
+        for row in 0..rows {
+            for column in 0..columns {
+                let cell = canvas.cell(column, row);
+                let index = row * columns + column;
+                if !redraw && self.shown[index] == cell {
+                    continue;
+                }
+
+                if cursor_at != Some((column, row)) {
+                    self.stdout
+                        .queue(cursor::MoveTo(column as u16, row as u16))?;
+                }
+                if current != Some((cell.fg, cell.bg)) {
+                    self.stdout
+                        .queue(SetForegroundColor(term_color(cell.fg)))?
+                        .queue(SetBackgroundColor(term_color(cell.bg)))?;
+                    current = Some((cell.fg, cell.bg));
+                }
+                self.stdout.queue(Print(cell.ch))?;
+                cursor_at = Some((column + 1, row));
+
+                if redraw {
+                    self.shown.push(cell);
+                } else {
+                    self.shown[index] = cell;
+                }
+            }
+        }
+
+        if cursor_at.is_some() {
+            self.stdout.queue(style::ResetColor)?;
+        }
+        self.stdout.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self
+            .stdout
+            .queue(style::ResetColor)
+            .and_then(|out| out.queue(cursor::Show))
+            .and_then(|out| out.queue(terminal::LeaveAlternateScreen))
+            .and_then(|out| out.flush());
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// What happened in the terminal.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Input {
+    Key(Key),
+    /// Ctrl-C, which raw mode no longer turns into a signal
+    Quit,
+    /// The terminal changed size and has to be drawn again
+    Resize,
+}
+
+/// Everything that happened within `timeout`, returns as soon as there is
+/// something.
+pub fn read_input(timeout: Duration) -> io::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    let mut timeout = timeout;
+
+    while event::poll(timeout)? {
+        match event::read()? {
+            Event::Key(event) if is_quit(&event) => inputs.push(Input::Quit),
+            Event::Key(event) => inputs.extend(key(&event).map(Input::Key)),
+            Event::Resize(_, _) => inputs.push(Input::Resize),
+            _ => {}
+        }
+        timeout = Duration::ZERO;
+    }
+
+    Ok(inputs)
+}
+
+fn is_quit(event: &KeyEvent) -> bool {
+    event.modifiers.contains(KeyModifiers::CONTROL) && event.code == KeyCode::Char('c')
+}
+
+/// The piston key for a key pressed in the terminal, so the key bindings
+/// from the config work the same. Letters are the same key in either case.
+fn key(event: &KeyEvent) -> Option<Key> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+
+    let name = match event.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char('-') => "Minus".to_string(),
+        KeyCode::Char('=') => "Equals".to_string(),
+        KeyCode::Char('.') => "Period".to_string(),
+        KeyCode::Char(',') => "Comma".to_string(),
+        KeyCode::Char('/') => "Slash".to_string(),
+        KeyCode::Char(';') => "Semicolon".to_string(),
+        KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Enter => "Return".to_string(),
+        KeyCode::Esc => "Escape".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => return None,
+    };
+
+    parse_key(&name)
+}
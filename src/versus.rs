@@ -0,0 +1,89 @@
+//! Score of a best-of-N match between two players on one keyboard.
+//!
+//! Every round is a game of `sim` with two snakes. A round is won by the
+//! snake that is still alive when the other one dies; if both die on the
+//! same tick it is a draw, which doesn't count for either player.
+
+pub const DEFAULT_BEST_OF: u32 = 3;
+pub const MAX_BEST_OF: u32 = 99;
+
+/// Check that a match of `best_of` rounds always has a winner and ends in
+/// reasonable time.
+pub fn check_best_of(best_of: u32) -> Result<(), String> {
+    if best_of == 0 || best_of.is_multiple_of(2) || best_of > MAX_BEST_OF {
+        return Err(format!(
+            "must be an odd number of rounds between 1 and {}",
+            MAX_BEST_OF
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    best_of: u32,
+    wins: [u32; 2],
+    rounds: u32,
+}
+
+impl Match {
+    /// A match of at most `best_of` won rounds, should be odd so there is
+    /// always a winner.
+    pub fn new(best_of: u32) -> Self {
+        Self {
+            best_of: best_of.max(1),
+            wins: [0; 2],
+            rounds: 0,
+        }
+    }
+
+    pub fn get_best_of(&self) -> u32 {
+        self.best_of
+    }
+
+    /// Rounds won by each player.
+    pub fn get_wins(&self) -> [u32; 2] {
+        self.wins
+    }
+
+    /// Rounds played, including draws.
+    pub fn get_rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    /// Record the outcome of a round, `None` for a draw. Ignored once the
+    /// match is over.
+    pub fn record(&mut self, winner: Option<usize>) {
+        if self.is_over() {
+            return;
+        }
+
+        self.rounds += 1;
+        if let Some(wins) = winner.and_then(|i| self.wins.get_mut(i)) {
+            *wins += 1;
+        }
+    }
+
+    /// The player who won the majority of the rounds, once there is one.
+    pub fn get_winner(&self) -> Option<usize> {
+        let needed = self.best_of / 2 + 1;
+        self.wins.iter().position(|wins| *wins >= needed)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.get_winner().is_some()
+    }
+
+    /// Start over with no rounds played.
+    pub fn reset(&mut self) {
+        self.wins = [0; 2];
+        self.rounds = 0;
+    }
+}
+
+impl Default for Match {
+    fn default() -> Self {
+        Self::new(DEFAULT_BEST_OF)
+    }
+}